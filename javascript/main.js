@@ -1,85 +1,51 @@
-window.keycloak = new Keycloak({
-    url: window.KEYCLOAK_FRONTEND_URL,
-    realm: window.KEYCLOAK_FRONTEND_REALM,
-    clientId: window.KEYCLOAK_FRONTEND_CLIENT_ID
-});
+// Authentication is handled by the backend: it runs the OIDC login and keeps the tokens server-side.
+// The browser only holds an HttpOnly session cookie, which is sent with every same-origin request.
 
 window.addEventListener("DOMContentLoaded", () => {
-    window.keycloak.init({ onLoad: 'check-sso' }).then(authenticated => {
-        if (authenticated) {
-            console.log("🔐 Keycloak authenticated.");
-            // If already on the login page and authenticated, redirect to the main app
-            if (window.location.pathname === '/login/') {
-                console.log("Redirecting authenticated user from /login/ to /");
-                window.location.href = '/';
-                return; // Stop further execution in this block
-            }
-            // Keep for existing backend compatibility for now, though backend should ideally validate the header token
-            localStorage.setItem("user-token", keycloak.token);
+    if (window.location.pathname === '/login/') {
+        initializeLoginFeatures();
+        return;
+    }
 
-            // Display username
-            if (keycloak.tokenParsed && keycloak.tokenParsed.preferred_username) {
-                const usernameDisplay = document.getElementById('username-display');
-                if (usernameDisplay) {
-                    usernameDisplay.innerHTML = `Welcome, ${keycloak.tokenParsed.preferred_username}!`;
-                }
+    fetch('/auth/oidc/session', { credentials: 'same-origin' })
+        .then(response => {
+            if (response.status === 401) {
+                console.log("No active session. Redirecting to /login/");
+                window.location.href = '/login/';
+                return null;
             }
-
-            // Refresh token regularly
-            setInterval(() => {
-                keycloak.updateToken(70).then(refreshed => {
-                    if (refreshed) {
-                        console.log('Token refreshed.');
-                        localStorage.setItem("user-token", keycloak.token);
-                    } else {
-                        console.log('Token not refreshed, valid for ' + Math.round(keycloak.tokenParsed.exp + keycloak.timeSkew - new Date().getTime() / 1000) + ' seconds');
-                    }
-                }).catch(() => {
-                    console.error('Failed to refresh token');
-                    doLogout(); // Redirect to login on refresh failure
-                });
-            }, 60000); // Check every minute
-
+            if (!response.ok) {
+                throw new Error(`HTTP error! status: ${response.status}`);
+            }
+            return response.json();
+        })
+        .then(session => {
+            if (!session) {
+                return;
+            }
+            console.log("🔐 Session active.");
             loadHeader().then(() => { // Call initializeAppFeatures AFTER loadHeader completes
+                const usernameDisplay = document.getElementById('username-display');
+                if (usernameDisplay && session.username) {
+                    usernameDisplay.textContent = `Welcome, ${session.username}!`;
+                }
                 getItems();
                 initializeAppFeatures();
             }).catch(error => {
                 console.error("Failed to initialize app features due to header loading error:", error);
-                // Optionally, display a user-friendly message or redirect to an error page
             });
-        } else { // User is not authenticated
-            console.log("Keycloak not authenticated.");
-            // If not authenticated and not on the login page, redirect to login
-            if (window.location.pathname !== '/login/') {
-                console.log("Redirecting unauthenticated user to /login/");
-                window.location.href = '/login/';
-                return; // Stop further execution
-            } else {
-                initializeLoginFeatures(); // Still initialize login features if on login page but not authenticated
-            }
-        }
-    }).catch((error) => {
-        console.error("Failed to initialize Keycloak:", error);
-        // If Keycloak initialization fails (e.g., Keycloak server unavailable), ensure login buttons are still functional
-        if (window.location.pathname === '/login/') {
-            initializeLoginFeatures();
-        } else {
-            // For other pages, if Keycloak init fails, consider a full logout or error state
-            doLogout();
-        }
-    });
-}); // Closing bracket for DOMContentLoaded event listener. Removed redundant '});'
+        })
+        .catch(error => {
+            console.error("Failed to check the session:", error);
+        });
+}); // Closing bracket for DOMContentLoaded event listener.
 
 // New function to initialize login page specific features
 function initializeLoginFeatures() {
     const loginButton = document.getElementById("loginButton");
     if (loginButton) {
         loginButton.addEventListener("click", () => {
-            if (window.keycloak) {
-                window.keycloak.login();
-            } else {
-                console.error("Keycloak instance not found for login button.");
-            }
+            window.location.href = '/auth/oidc/login';
         });
     } else {
         console.warn("Login button not found.");
@@ -88,11 +54,7 @@ function initializeLoginFeatures() {
     const registerButton = document.getElementById("registerButton");
     if (registerButton) {
         registerButton.addEventListener("click", () => {
-            if (window.keycloak) {
-                window.keycloak.register();
-            } else {
-                console.error("Keycloak instance not found for register button.");
-            }
+            window.location.href = '/auth/oidc/login?prompt=create';
         });
     } else {
         console.warn("Register button not found.");
@@ -101,8 +63,7 @@ function initializeLoginFeatures() {
 
 function doLogout() {
     console.log("Logging out...");
-    keycloak.logout({ redirectUri: document.location.origin + "/login/" });
-    localStorage.removeItem("user-token"); // Clear local storage token as well
+    window.location.href = '/auth/oidc/logout';
 }
 
 function renderItems(items, processType, elementId, processFunction) {
@@ -140,8 +101,8 @@ function apiCall(url, method, body = null) { // Added body parameter for logging
     xhr.addEventListener('readystatechange', function () {
         if (this.readyState === this.DONE) {
             if (this.status === 401) {
-                console.log("API call returned 401. Re-authenticating or logging out.");
-                doLogout();
+                console.log("API call returned 401. Signing in again.");
+                window.location.href = '/auth/oidc/login?return_to=' + encodeURIComponent(window.location.pathname);
             } else if (this.status >= 200 && this.status < 300) {
                 try {
                     const response = JSON.parse(this.responseText);
@@ -166,15 +127,6 @@ function apiCall(url, method, body = null) { // Added body parameter for logging
     let headers = {
         "Content-Type": "application/json"
     };
-    if (keycloak.token) {
-        const fullAuthHeader = "Bearer " + keycloak.token;
-        headers["Authorization"] = fullAuthHeader;
-        console.log(`   Authorization header: ${fullAuthHeader}`); // Log full token
-    } else {
-        console.warn("⚠️ Keycloak token not available for API call. Logging out.");
-        doLogout();
-        return; // Stop execution if no token
-    }
     if (body) {
         console.log("   Request body:", JSON.stringify(body)); // Log request body as string
     } else {
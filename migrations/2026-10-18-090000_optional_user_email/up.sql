@@ -0,0 +1,2 @@
+-- Service accounts and users with incomplete Keycloak profiles have no email address.
+ALTER TABLE users ALTER COLUMN email DROP NOT NULL;
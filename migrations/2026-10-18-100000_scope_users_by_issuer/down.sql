@@ -0,0 +1,7 @@
+ALTER TABLE users DROP CONSTRAINT uc_user_email;
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+ALTER TABLE users DROP CONSTRAINT uc_user_username;
+ALTER TABLE users ADD CONSTRAINT users_username_key UNIQUE (username);
+ALTER TABLE users DROP CONSTRAINT uc_user_subject;
+ALTER TABLE users DROP COLUMN subject;
+ALTER TABLE users DROP COLUMN issuer;
@@ -0,0 +1,16 @@
+-- Local users are identified by the token issuer plus its `sub`, so subjects from different realms
+-- cannot collide. `id` stays the local primary key that items point at; new rows get a generated ID.
+-- Existing rows keep an empty issuer, which no token matches. At startup the application moves them
+-- to the realm configured so far (`LEGACY_USER_ISSUER`) or, if they have a real password, to 'local'.
+ALTER TABLE users ADD COLUMN issuer TEXT NOT NULL DEFAULT '';
+ALTER TABLE users ADD COLUMN subject TEXT;
+UPDATE users SET subject = id;
+ALTER TABLE users ALTER COLUMN subject SET NOT NULL;
+ALTER TABLE users ALTER COLUMN issuer DROP DEFAULT;
+ALTER TABLE users ADD CONSTRAINT uc_user_subject UNIQUE (issuer, subject);
+
+-- Usernames and email addresses only have to be unique within a realm.
+ALTER TABLE users DROP CONSTRAINT users_username_key;
+ALTER TABLE users ADD CONSTRAINT uc_user_username UNIQUE (issuer, username);
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+ALTER TABLE users ADD CONSTRAINT uc_user_email UNIQUE (issuer, email);
@@ -0,0 +1,24 @@
+-- Logins in progress: the PKCE verifier and nonce wait here between the redirect to Keycloak and the callback.
+CREATE TABLE oidc_login_states (
+    state_hash TEXT PRIMARY KEY,        -- SHA-256 of the `state` parameter
+    issuer_name TEXT NOT NULL,
+    code_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    return_to TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL
+);
+
+-- Browser sessions. The cookie carries a random session ID; only its hash is stored, next to the tokens.
+CREATE TABLE sessions (
+    id_hash TEXT PRIMARY KEY,           -- SHA-256 of the session cookie value
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    issuer_name TEXT NOT NULL,
+    access_token TEXT NOT NULL,
+    access_token_expires_at TIMESTAMP NOT NULL,
+    refresh_token TEXT,
+    id_token TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
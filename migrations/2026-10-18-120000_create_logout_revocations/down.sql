@@ -0,0 +1,4 @@
+DROP TABLE logout_revocations;
+
+DROP INDEX idx_sessions_issuer_sid;
+ALTER TABLE sessions DROP COLUMN sid;
@@ -0,0 +1,18 @@
+-- The identity provider's session ID (`sid`), so a back-channel logout can find the browser sessions it ends.
+ALTER TABLE sessions ADD COLUMN sid TEXT;
+CREATE INDEX idx_sessions_issuer_sid ON sessions (issuer_name, sid);
+
+-- Logouts announced by an identity provider. Tokens that carry a revoked `sid`, or that were issued to a
+-- revoked `sub` before the logout, are rejected until they would have expired anyway.
+CREATE TABLE logout_revocations (
+    id SERIAL PRIMARY KEY,
+    issuer TEXT NOT NULL,               -- the `iss` of the logout token
+    sid TEXT,
+    subject TEXT,
+    revoked_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    CONSTRAINT chk_logout_revocations_target CHECK (sid IS NOT NULL OR subject IS NOT NULL)
+);
+
+CREATE INDEX idx_logout_revocations_sid ON logout_revocations (issuer, sid);
+CREATE INDEX idx_logout_revocations_subject ON logout_revocations (issuer, subject);
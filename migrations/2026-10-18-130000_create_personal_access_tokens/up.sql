@@ -0,0 +1,14 @@
+-- Personal access tokens for scripts and CLIs. Only the SHA-256 of the token is stored; the token itself
+-- is shown once, when it is created.
+CREATE TABLE personal_access_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    scopes TEXT NOT NULL,               -- space-separated, like a token's `scope` claim
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    last_used_at TIMESTAMP,
+    CONSTRAINT uc_personal_access_tokens_hash UNIQUE (token_hash),
+    CONSTRAINT uc_personal_access_tokens_name UNIQUE (user_id, name)
+);
@@ -0,0 +1,12 @@
+-- Refresh tokens issued by local login. Every refresh replaces the token with a new one in the same family;
+-- presenting a token that was already used revokes the whole family, as it has probably been stolen.
+CREATE TABLE refresh_tokens (
+    token_hash TEXT PRIMARY KEY,        -- SHA-256 of the refresh token
+    family_id TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP
+);
+
+CREATE INDEX idx_refresh_tokens_family_id ON refresh_tokens (family_id);
@@ -0,0 +1,7 @@
+-- Failed local logins, counted per username and per client IP so every worker enforces the same lockout.
+CREATE TABLE login_failures (
+    throttle_key TEXT PRIMARY KEY,      -- 'user:<username>' or 'ip:<address>'
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP
+);
@@ -0,0 +1,4 @@
+-- Profile names copied from the identity provider's token claims and refreshed on every login.
+ALTER TABLE users ADD COLUMN display_name TEXT;
+ALTER TABLE users ADD COLUMN given_name TEXT;
+ALTER TABLE users ADD COLUMN family_name TEXT;
@@ -0,0 +1,6 @@
+ALTER TABLE users ADD COLUMN password VARCHAR NOT NULL DEFAULT '';
+UPDATE users SET password = credentials.password_hash
+    FROM credentials
+    WHERE credentials.user_id = users.id AND credentials.kind = 'password';
+ALTER TABLE users ALTER COLUMN password DROP DEFAULT;
+DROP TABLE credentials;
@@ -0,0 +1,27 @@
+-- The ways a user can authenticate, one row each: a local password, an identity at an OIDC issuer,
+-- or (later) a passkey. SSO users have no password credential, so /auth/login cannot be used for them.
+CREATE TABLE credentials (
+    id SERIAL PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    password_hash TEXT,                 -- bcrypt hash, for kind = 'password'
+    issuer TEXT,                        -- issuer and subject, for kind = 'oidc'
+    subject TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT ck_credentials_kind CHECK (kind IN ('password', 'oidc', 'passkey')),
+    CONSTRAINT ck_credentials_password CHECK (kind <> 'password' OR password_hash IS NOT NULL),
+    CONSTRAINT ck_credentials_oidc CHECK (kind <> 'oidc' OR (issuer IS NOT NULL AND subject IS NOT NULL))
+);
+
+CREATE UNIQUE INDEX uc_credentials_password ON credentials (user_id) WHERE kind = 'password';
+CREATE UNIQUE INDEX uc_credentials_oidc ON credentials (issuer, subject) WHERE kind = 'oidc';
+
+-- Every stored hash is kept, including those of rows from before issuers, whose issuer is still
+-- empty: they may be local accounts. SSO users of a known issuer only had a hash of a placeholder,
+-- which is dropped.
+INSERT INTO credentials (user_id, kind, password_hash)
+    SELECT id, 'password', password FROM users WHERE password <> '' AND issuer IN ('local', '');
+INSERT INTO credentials (user_id, kind, issuer, subject)
+    SELECT id, 'oidc', issuer, subject FROM users WHERE issuer NOT IN ('local', '');
+
+ALTER TABLE users DROP COLUMN password;
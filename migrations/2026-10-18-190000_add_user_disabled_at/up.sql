@@ -0,0 +1,2 @@
+-- Set while an administrator has disabled the user; their tokens are refused until it is cleared.
+ALTER TABLE users ADD COLUMN disabled_at TIMESTAMP;
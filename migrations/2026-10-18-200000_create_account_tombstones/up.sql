@@ -0,0 +1,13 @@
+-- One row per deleted account, kept for compliance. It holds no personal data: the user ID is random
+-- and the subject is only stored hashed, which is enough to refuse tokens issued before the deletion.
+CREATE TABLE account_tombstones (
+    user_id TEXT PRIMARY KEY,
+    issuer TEXT NOT NULL,
+    subject_hash TEXT NOT NULL,
+    requested_by TEXT NOT NULL,         -- the user ID of the user or administrator who asked for the deletion
+    items_deleted INT NOT NULL,
+    items_reassigned INT NOT NULL,
+    deleted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX ix_account_tombstones_subject ON account_tombstones (issuer, subject_hash);
@@ -0,0 +1,35 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use tracing::warn;
+
+use crate::auth::processes::Claims;
+use crate::auth::validation_policy::TokenRejection;
+use crate::models::account_tombstone::account_tombstone_utils;
+use crate::models::user::user_utils;
+
+/// Refuses tokens of users an administrator has disabled, and tokens issued before their account was
+/// deleted, however long the token itself is valid.
+///
+/// # Arguments
+/// * claims (&Claims): the verified claims of the token
+///
+/// # Returns
+/// * (Result<(), TokenRejection>): `AccountDisabled` or `AccountDeleted` if the token must be refused
+pub fn ensure_active(claims: &Claims) -> Result<(), TokenRejection> {
+    let unavailable = |e: String| {
+        warn!("Could not check the account of user {}: {}", claims.sub, e);
+        TokenRejection::Malformed(e)
+    };
+    match user_utils::find_user_for_token(&claims.iss, &claims.sub).map_err(unavailable)? {
+        Some(user) if user.disabled_at.is_some() => Err(TokenRejection::AccountDisabled),
+        Some(_) => Ok(()),
+        // Without a row the caller is new, or deleted their account after the token was issued
+        None => {
+            let issued_at = UNIX_EPOCH + Duration::from_secs(claims.iat as u64);
+            if account_tombstone_utils::was_deleted_since(&claims.iss, &claims.sub, issued_at).map_err(unavailable)? {
+                return Err(TokenRejection::AccountDeleted);
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use tracing::warn;
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::models::user::user::User;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What an account token may be used for; a token for one purpose is refused for the other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccountTokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl AccountTokenPurpose {
+    fn name(&self) -> &'static str {
+        match self {
+            AccountTokenPurpose::VerifyEmail => "verify-email",
+            AccountTokenPurpose::ResetPassword => "reset-password",
+        }
+    }
+}
+
+/// Signs and checks the tokens sent by email to verify an address or reset a password.
+///
+/// A token is `<user id>.<expiry>.<HMAC-SHA256>`. The MAC also covers the account state the token
+/// changes (the email verification status, or the hash of the password credential), so a token stops
+/// working once it has been used; no token is stored.
+///
+/// # Attributes
+/// * secret (Vec<u8>): the HMAC key
+/// * verification_ttl (Duration): how long an email verification link is valid
+/// * reset_ttl (Duration): how long a password reset token is valid
+/// * public_url (String): the address of this application, used in links sent by email
+pub struct AccountTokenSigner {
+    secret: Vec<u8>,
+    pub verification_ttl: Duration,
+    pub reset_ttl: Duration,
+    pub public_url: String,
+}
+
+impl AccountTokenSigner {
+
+    /// Builds the signer from `ACCOUNT_TOKEN_SECRET`, `EMAIL_VERIFICATION_TTL_SECS`,
+    /// `PASSWORD_RESET_TTL_SECS` and `APP_PUBLIC_URL`.
+    ///
+    /// Without `ACCOUNT_TOKEN_SECRET` a random key is used, so outstanding tokens stop working when
+    /// the server restarts and are not accepted by other instances.
+    ///
+    /// # Returns
+    /// (AccountTokenSigner): the signer
+    pub fn from_env() -> AccountTokenSigner {
+        let secret = match env::var("ACCOUNT_TOKEN_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+            _ => {
+                warn!("ACCOUNT_TOKEN_SECRET is not set; email verification and password reset tokens will not survive a restart.");
+                let mut secret = vec![0u8; 32];
+                rand::rng().fill_bytes(&mut secret);
+                secret
+            }
+        };
+        let env_u64 = |name: &str, default: u64| {
+            env::var(name).ok().and_then(|value| value.parse::<u64>().ok()).unwrap_or(default)
+        };
+        AccountTokenSigner {
+            secret,
+            verification_ttl: Duration::from_secs(env_u64("EMAIL_VERIFICATION_TTL_SECS", 48 * 60 * 60)),
+            reset_ttl: Duration::from_secs(env_u64("PASSWORD_RESET_TTL_SECS", 60 * 60)),
+            public_url: env::var("APP_PUBLIC_URL")
+                .unwrap_or_else(|_| "http://localhost:8000".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+
+    /// Signs a token for a user.
+    ///
+    /// # Arguments
+    /// * purpose (AccountTokenPurpose): what the token is for
+    /// * user (&User): the user
+    /// * password_hash (Option<&str>): the user's current password hash, for a password reset
+    ///
+    /// # Returns
+    /// (String): the token
+    pub fn issue(&self, purpose: AccountTokenPurpose, user: &User, password_hash: Option<&str>) -> String {
+        let ttl = match purpose {
+            AccountTokenPurpose::VerifyEmail => self.verification_ttl,
+            AccountTokenPurpose::ResetPassword => self.reset_ttl,
+        };
+        let expires_at = (SystemTime::now() + ttl).duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mac = self.mac(purpose, user, password_hash, expires_at).finalize().into_bytes();
+        format!("{}.{}.{}", URL_SAFE_NO_PAD.encode(&user.id), expires_at, URL_SAFE_NO_PAD.encode(mac))
+    }
+
+    /// Reads the user ID out of a token without checking it, so the user can be loaded.
+    ///
+    /// # Arguments
+    /// * token (&str): the token
+    ///
+    /// # Returns
+    /// (Option<String>): the user ID, or `None` if the token is malformed
+    pub fn user_id(token: &str) -> Option<String> {
+        let encoded = token.split('.').next()?;
+        URL_SAFE_NO_PAD.decode(encoded).ok().and_then(|bytes| String::from_utf8(bytes).ok())
+    }
+
+    /// Checks a token against the current state of the user it names.
+    ///
+    /// # Arguments
+    /// * purpose (AccountTokenPurpose): what the token is being used for
+    /// * token (&str): the token
+    /// * user (&User): the user loaded with `user_id`
+    /// * password_hash (Option<&str>): the user's current password hash, for a password reset
+    ///
+    /// # Returns
+    /// * (Result<(), String>): why the token is refused, if it is
+    pub fn verify(&self, purpose: AccountTokenPurpose, token: &str, user: &User, password_hash: Option<&str>) -> Result<(), String> {
+        let parts: Vec<&str> = token.split('.').collect();
+        if parts.len() != 3 || AccountTokenSigner::user_id(token).as_deref() != Some(user.id.as_str()) {
+            return Err("token is malformed".to_string());
+        }
+        let expires_at = parts[1].parse::<u64>().map_err(|_| "token is malformed".to_string())?;
+        let signature = URL_SAFE_NO_PAD.decode(parts[2]).map_err(|_| "token is malformed".to_string())?;
+        // Checked first, so an expired token is not reported as expired unless it is genuine
+        self.mac(purpose, user, password_hash, expires_at)
+            .verify_slice(&signature)
+            .map_err(|_| "token is invalid or has already been used".to_string())?;
+        if UNIX_EPOCH + Duration::from_secs(expires_at) <= SystemTime::now() {
+            return Err("token has expired".to_string());
+        }
+        Ok(())
+    }
+
+    fn mac(&self, purpose: AccountTokenPurpose, user: &User, password_hash: Option<&str>, expires_at: u64) -> HmacSha256 {
+        let state = match purpose {
+            AccountTokenPurpose::VerifyEmail => format!("{}|{}", user.email.as_deref().unwrap_or(""), user.email_verified_at.is_some()),
+            AccountTokenPurpose::ResetPassword => password_hash.unwrap_or("").to_string(),
+        };
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        for part in [purpose.name(), user.id.as_str(), &expires_at.to_string(), &state] {
+            mac.update(part.as_bytes());
+            mac.update(b"\0");
+        }
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::new_user::LOCAL_ISSUER;
+
+    fn signer() -> AccountTokenSigner {
+        AccountTokenSigner {
+            secret: b"test-secret".to_vec(),
+            verification_ttl: Duration::from_secs(60),
+            reset_ttl: Duration::from_secs(60),
+            public_url: "http://localhost:8000".to_string(),
+        }
+    }
+
+    fn user() -> User {
+        User::for_test("user-1", LOCAL_ISSUER, "user-1")
+    }
+
+    const HASH: Option<&str> = Some("$2b$12$hash");
+
+    #[test]
+    fn token_is_accepted_for_its_user_and_purpose() {
+        let token = signer().issue(AccountTokenPurpose::VerifyEmail, &user(), None);
+        assert_eq!(AccountTokenSigner::user_id(&token).as_deref(), Some("user-1"));
+        assert!(signer().verify(AccountTokenPurpose::VerifyEmail, &token, &user(), None).is_ok());
+        assert!(signer().verify(AccountTokenPurpose::ResetPassword, &token, &user(), None).is_err());
+    }
+
+    #[test]
+    fn token_stops_working_once_used() {
+        let token = signer().issue(AccountTokenPurpose::VerifyEmail, &user(), None);
+        let verified = User { email_verified_at: Some(SystemTime::now()), ..user() };
+        assert!(signer().verify(AccountTokenPurpose::VerifyEmail, &token, &verified, None).is_err());
+
+        let token = signer().issue(AccountTokenPurpose::ResetPassword, &user(), HASH);
+        assert!(signer().verify(AccountTokenPurpose::ResetPassword, &token, &user(), HASH).is_ok());
+        assert!(signer().verify(AccountTokenPurpose::ResetPassword, &token, &user(), Some("$2b$12$other")).is_err());
+    }
+
+    #[test]
+    fn expired_and_forged_tokens_are_refused() {
+        let expired = AccountTokenSigner { reset_ttl: Duration::ZERO, ..signer() };
+        let token = expired.issue(AccountTokenPurpose::ResetPassword, &user(), HASH);
+        assert_eq!(expired.verify(AccountTokenPurpose::ResetPassword, &token, &user(), HASH), Err("token has expired".to_string()));
+
+        let other = AccountTokenSigner { secret: b"other-secret".to_vec(), ..signer() };
+        let token = other.issue(AccountTokenPurpose::ResetPassword, &user(), HASH);
+        assert!(signer().verify(AccountTokenPurpose::ResetPassword, &token, &user(), HASH).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::rc::Rc;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error, HttpMessage, HttpResponse};
+use futures_util::future::{self, LocalBoxFuture, Ready};
+use tracing::warn;
+
+use crate::auth::processes::Claims;
+use crate::auth::provider::ProviderRegistry;
+use crate::auth::KeycloakClientConfig;
+use crate::json_serialization::error_response::ErrorResponse;
+
+/// Requires a Keycloak realm role, or a client role of the token issuer's client, on a route.
+///
+/// ```rust
+/// web::get().to(handler).wrap(RequireRole(permissions::ADMIN_ROLE))
+/// ```
+pub struct RequireRole(pub &'static str);
+
+/// Requires a scope in the token's space-separated `scope` claim on a route.
+///
+/// ```rust
+/// web::post().to(handler).wrap(RequireScope(permissions::ITEMS_WRITE))
+/// ```
+pub struct RequireScope(pub &'static str);
+
+/// A single permission checked against the `Claims` the authentication middleware stored on the request.
+#[derive(Clone, Copy, Debug)]
+pub enum Permission {
+    Role(&'static str),
+    Scope(&'static str),
+}
+
+impl Permission {
+
+    /// Checks the permission against the claims.
+    ///
+    /// # Arguments
+    /// * claims (&Claims): the authenticated user's claims
+    /// * client_id (&str): the client whose roles count as well as realm roles
+    ///
+    /// # Returns
+    /// * (Result<(), String>): the reason for the refusal if the permission is missing
+    pub fn check(&self, claims: &Claims, client_id: &str) -> Result<(), String> {
+        match self {
+            Permission::Role(role) if claims.has_role(role, client_id) => Ok(()),
+            Permission::Role(role) => Err(format!("Forbidden: missing role '{}'", role)),
+            Permission::Scope(scope) if claims.has_scope(scope) => Ok(()),
+            Permission::Scope(scope) => Err(format!("Forbidden: missing scope '{}'", scope)),
+        }
+    }
+
+    /// The error code of a 403 for this permission, `missing_role` or `missing_scope`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Permission::Role(_) => "missing_role",
+            Permission::Scope(_) => "missing_scope",
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = PermissionGuard<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(PermissionGuard { service: Rc::new(service), permission: Permission::Role(self.0) })
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireScope
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = PermissionGuard<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(PermissionGuard { service: Rc::new(service), permission: Permission::Scope(self.0) })
+    }
+}
+
+/// The service built by `RequireRole` and `RequireScope`.
+///
+/// Requests without claims get a 401, requests whose claims lack the permission get a 403 with the reason.
+pub struct PermissionGuard<S> {
+    service: Rc<S>,
+    permission: Permission,
+}
+
+impl<S, B> Service<ServiceRequest> for PermissionGuard<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let permission = self.permission;
+
+        Box::pin(async move {
+            let claims = req.extensions().get::<Claims>().cloned();
+            // Client roles are read for the token issuer's own client
+            let client_id = claims
+                .as_ref()
+                .and_then(|claims| {
+                    req.app_data::<web::Data<ProviderRegistry>>()
+                        .and_then(|registry| registry.find_by_issuer(&claims.iss).map(|provider| provider.issuer.client_id.clone()))
+                })
+                .or_else(|| req.app_data::<web::Data<KeycloakClientConfig>>().map(|config| config.client_id.clone()))
+                .unwrap_or_default();
+            let outcome = match claims {
+                Some(claims) => permission.check(&claims, &client_id).map_err(|reason| (claims.sub, reason)),
+                None => {
+                    warn!("No claims on request to {} guarded by {:?}.", req.path(), permission);
+                    return Ok(req.into_response(
+                        HttpResponse::Unauthorized().json(ErrorResponse::new("unauthorized", "Missing user claims")),
+                    ));
+                }
+            };
+
+            match outcome {
+                Ok(()) => Ok(service.call(req).await?.map_into_boxed_body()),
+                Err((sub, reason)) => {
+                    warn!("Access to {} denied for user {}: {}", req.path(), sub, reason);
+                    Ok(req.into_response(HttpResponse::Forbidden().json(ErrorResponse::new(permission.code(), &reason))))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::App;
+    use serde_json::{json, Value};
+
+    fn claims(extra: Value) -> Claims {
+        let mut claims = json!({
+            "exp": 2_000_000_000usize,
+            "iat": 1_700_000_000usize,
+            "iss": "https://keycloak.test/realms/myrealm",
+            "sub": "user-1",
+        });
+        claims.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(claims).unwrap()
+    }
+
+    #[test]
+    fn realm_roles_count_for_any_client() {
+        let claims = claims(json!({ "realm_access": { "roles": ["todo-admin"] } }));
+
+        assert!(Permission::Role("todo-admin").check(&claims, "todo-client").is_ok());
+        assert!(Permission::Role("todo-admin").check(&claims, "other-client").is_ok());
+    }
+
+    #[test]
+    fn client_roles_only_count_for_their_client() {
+        let claims = claims(json!({ "resource_access": { "todo-client": { "roles": ["todo-admin"] } } }));
+
+        assert!(Permission::Role("todo-admin").check(&claims, "todo-client").is_ok());
+        assert_eq!(
+            Permission::Role("todo-admin").check(&claims, "other-client"),
+            Err("Forbidden: missing role 'todo-admin'".to_string())
+        );
+    }
+
+    #[test]
+    fn roles_are_missing_without_role_claims() {
+        let claims = claims(json!({}));
+
+        assert!(!claims.has_role("todo-admin", "todo-client"));
+        assert!(Permission::Role("todo-admin").check(&claims, "todo-client").is_err());
+    }
+
+    #[test]
+    fn scopes_are_read_from_the_space_separated_claim() {
+        let claims = claims(json!({ "scope": "openid profile  items:read\titems:write" }));
+
+        assert!(Permission::Scope("items:read").check(&claims, "todo-client").is_ok());
+        assert!(Permission::Scope("items:write").check(&claims, "todo-client").is_ok());
+        assert!(Permission::Scope("profile").check(&claims, "todo-client").is_ok());
+    }
+
+    #[test]
+    fn scopes_match_whole_words_only() {
+        let claims = claims(json!({ "scope": "items:read-only items" }));
+
+        assert!(!claims.has_scope("items:read"));
+        assert_eq!(
+            Permission::Scope("items:read").check(&claims, "todo-client"),
+            Err("Forbidden: missing scope 'items:read'".to_string())
+        );
+    }
+
+    #[test]
+    fn scopes_are_missing_without_a_scope_claim() {
+        let claims = claims(json!({}));
+
+        assert!(!claims.has_scope("items:read"));
+        assert!(Permission::Scope("items:read").check(&claims, "todo-client").is_err());
+    }
+
+    /// Calls a route guarded by `RequireScope("items:write")`, as a user with `claims` if there are any.
+    async fn call_guarded(claims: Option<Claims>) -> (StatusCode, Value) {
+        let app = init_service(
+            App::new()
+                .wrap_fn(move |req, srv| {
+                    if let Some(claims) = claims.clone() {
+                        req.extensions_mut().insert(claims);
+                    }
+                    srv.call(req)
+                })
+                .route("/items", web::post().to(HttpResponse::Ok).wrap(RequireScope("items:write"))),
+        )
+        .await;
+        let res = call_service(&app, TestRequest::post().uri("/items").to_request()).await;
+        let status = res.status();
+        (status, read_body_json(res).await)
+    }
+
+    #[actix_web::test]
+    async fn a_missing_permission_answers_a_json_403() {
+        let (status, body) = call_guarded(Some(claims(json!({ "scope": "items:read" })))).await;
+
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "missing_scope");
+        assert_eq!(body["message"], "Forbidden: missing scope 'items:write'");
+    }
+
+    #[actix_web::test]
+    async fn a_request_without_claims_answers_a_json_401() {
+        let (status, body) = call_guarded(None).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "unauthorized");
+    }
+}
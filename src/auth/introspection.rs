@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn, error};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::auth::issuers::IssuerConfig;
+use crate::auth::processes::Claims;
+use crate::auth::validation_policy::{TokenRejection, TokenValidationPolicy};
+use crate::middleware::request_id;
+
+/// Upper bound on cached introspection results; expired entries are purged when it is reached.
+const MAX_CACHED_TOKENS: usize = 10_000;
+
+/// How access tokens are verified, selected per deployment with `AUTH_TOKEN_MODE`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenMode {
+    /// Verify JWT signatures locally against the cached JWKS (`jwks`, the default).
+    Jwks,
+    /// Ask Keycloak's RFC 7662 introspection endpoint about every token (`introspection`).
+    Introspection,
+    /// Verify locally, and introspect tokens that cannot be checked against the JWKS (`jwks_with_fallback`).
+    JwksWithIntrospectionFallback,
+}
+
+/// Introspection settings read at startup.
+///
+/// # Attributes
+/// * mode (TokenMode): how tokens are verified
+/// * client_id (String): the client that authenticates to the introspection endpoint
+/// * client_secret (Option<String>): that client's secret, required unless the mode is `Jwks`
+#[derive(Clone, Debug)]
+pub struct IntrospectionSettings {
+    pub mode: TokenMode,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+}
+
+impl IntrospectionSettings {
+
+    /// Builds the settings for one issuer from `AUTH_TOKEN_MODE` and `KEYCLOAK_CLIENT_SECRET`,
+    /// both of which can be overridden per issuer.
+    ///
+    /// # Arguments
+    /// * issuer (&IssuerConfig): the issuer; its client ID is used for client authentication
+    ///
+    /// # Returns
+    /// * (Result<IntrospectionSettings, String>): the settings, or an error for an unknown mode or a missing secret
+    pub fn from_env(issuer: &IssuerConfig) -> Result<IntrospectionSettings, String> {
+        let mode = match issuer.var("AUTH_TOKEN_MODE").unwrap_or_else(|| "jwks".to_string()).as_str() {
+            "jwks" => TokenMode::Jwks,
+            "introspection" => TokenMode::Introspection,
+            "jwks_with_fallback" => TokenMode::JwksWithIntrospectionFallback,
+            other => return Err(format!("Unknown AUTH_TOKEN_MODE '{}'", other)),
+        };
+        let client_secret = issuer.client_secret.clone();
+        if mode != TokenMode::Jwks && client_secret.is_none() {
+            return Err(format!("KEYCLOAK_CLIENT_SECRET must be set for issuer '{}' when token introspection is enabled", issuer.name));
+        }
+        info!("Token verification mode for issuer '{}': {:?}", issuer.name, mode);
+        Ok(IntrospectionSettings {
+            mode,
+            client_id: issuer.client_id.clone(),
+            client_secret,
+        })
+    }
+}
+
+struct CachedIntrospection {
+    claims: Claims,
+    expires_at: u64,
+}
+
+/// Client for the realm's token introspection endpoint, with a cache of active tokens.
+///
+/// Only positive answers are cached, and only until the token's `exp`, so a revoked token is
+/// never accepted for longer than it would have been as a self-contained JWT.
+pub struct IntrospectionClient {
+    endpoint: String,
+    client_id: String,
+    client_secret: String,
+    client: reqwest::Client,
+    cache: RwLock<HashMap<Vec<u8>, CachedIntrospection>>,
+}
+
+impl IntrospectionClient {
+
+    /// Creates a client for the given endpoint.
+    ///
+    /// # Arguments
+    /// * endpoint (String): the `introspection_endpoint` from the discovery document
+    /// * client_id (String): the client that authenticates to the endpoint
+    /// * client_secret (String): that client's secret
+    ///
+    /// # Returns
+    /// (IntrospectionClient): a client with an empty cache
+    pub fn new(endpoint: String, client_id: String, client_secret: String) -> IntrospectionClient {
+        IntrospectionClient {
+            endpoint,
+            client_id,
+            client_secret,
+            client: reqwest::Client::new(),
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Introspects a token and maps an active response into `Claims`.
+    ///
+    /// # Arguments
+    /// * token (&str): the opaque or JWT access token
+    /// * policy (&TokenValidationPolicy): the rules the introspected claims must satisfy
+    ///
+    /// # Returns
+    /// * (Result<Claims, TokenRejection>): the claims, or the reason the token was rejected
+    pub async fn introspect(&self, token: &str, policy: &TokenValidationPolicy) -> Result<Claims, TokenRejection> {
+        let key = Sha256::digest(token.as_bytes()).to_vec();
+        let now = now_secs();
+        if let Some(claims) = self.cached(&key, now) {
+            info!("Using cached introspection result for subject {}", claims.sub);
+            return Ok(claims);
+        }
+
+        info!("Introspecting token at {}", self.endpoint);
+        let response = request_id::propagate(self.client.post(&self.endpoint))
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[("token", token), ("token_type_hint", "access_token")])
+            .send()
+            .await
+            .map_err(|e| {
+                error!("Failed to call the introspection endpoint {}: {}", self.endpoint, e);
+                TokenRejection::IntrospectionFailed(e.to_string())
+            })?;
+        if !response.status().is_success() {
+            error!("Introspection endpoint {} answered HTTP Status {}", self.endpoint, response.status());
+            return Err(TokenRejection::IntrospectionFailed(format!("HTTP Status {}", response.status())));
+        }
+        let body: Map<String, Value> = response.json().await.map_err(|e| {
+            error!("Failed to parse the introspection response: {}", e);
+            TokenRejection::IntrospectionFailed(e.to_string())
+        })?;
+
+        let claims = introspected_claims(body, policy, now)?;
+        self.store(key, claims.clone(), now);
+        Ok(claims)
+    }
+
+    fn cached(&self, key: &[u8], now: u64) -> Option<Claims> {
+        let cache = self.cache.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache
+            .get(key)
+            .filter(|entry| entry.expires_at > now)
+            .map(|entry| entry.claims.clone())
+    }
+
+    fn store(&self, key: Vec<u8>, claims: Claims, now: u64) {
+        let mut cache = self.cache.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if cache.len() >= MAX_CACHED_TOKENS {
+            cache.retain(|_, entry| entry.expires_at > now);
+            if cache.len() >= MAX_CACHED_TOKENS {
+                warn!("Introspection cache is full; dropping all cached results.");
+                cache.clear();
+            }
+        }
+        let expires_at = claims.exp as u64;
+        cache.insert(key, CachedIntrospection { claims, expires_at });
+    }
+}
+
+/// Maps an introspection response into `Claims`, once the token is active and its claims satisfy the policy.
+///
+/// # Arguments
+/// * body (Map<String, Value>): the JSON object returned by the introspection endpoint
+/// * policy (&TokenValidationPolicy): the rules the introspected claims must satisfy
+/// * now (u64): the current time in seconds since the epoch
+///
+/// # Returns
+/// * (Result<Claims, TokenRejection>): the claims, or the reason the token was rejected
+fn introspected_claims(mut body: Map<String, Value>, policy: &TokenValidationPolicy, now: u64) -> Result<Claims, TokenRejection> {
+    if body.get("active").and_then(Value::as_bool) != Some(true) {
+        return Err(TokenRejection::Inactive);
+    }
+    body.remove("active");
+    // RFC 7662 calls it `username`; Claims uses the OIDC name.
+    if !body.contains_key("preferred_username") {
+        if let Some(username) = body.get("username").cloned() {
+            body.insert("preferred_username".to_string(), username);
+        }
+    }
+    policy.check_introspected(&body, now)?;
+
+    serde_json::from_value::<Claims>(Value::Object(body))
+        .map_err(|e| TokenRejection::Malformed(format!("Unexpected introspection claims: {}", e)))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use jsonwebtoken::Algorithm;
+    use serde_json::json;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn policy() -> TokenValidationPolicy {
+        TokenValidationPolicy {
+            issuer: "https://keycloak.test/realms/myrealm".to_string(),
+            required_claims: vec!["exp".to_string(), "iss".to_string(), "sub".to_string()],
+            audiences: vec!["todo-app".to_string()],
+            allowed_azp: vec!["todo-app".to_string()],
+            leeway: 0,
+            algorithms: vec![Algorithm::RS256],
+        }
+    }
+
+    fn response(exp: u64) -> Map<String, Value> {
+        json!({
+            "active": true,
+            "iss": "https://keycloak.test/realms/myrealm",
+            "sub": "user-1",
+            "aud": "todo-app",
+            "azp": "todo-app",
+            "iat": NOW - 60,
+            "exp": exp,
+            "username": "alice",
+            "scope": "openid items:read",
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    fn client(endpoint: String) -> IntrospectionClient {
+        IntrospectionClient::new(endpoint, "todo-app".to_string(), "secret".to_string())
+    }
+
+    /// An introspection endpoint answering `answer` and counting the calls it receives.
+    async fn endpoint(answer: Arc<Mutex<Value>>, calls: Arc<AtomicUsize>) -> (String, actix_web::dev::ServerHandle) {
+        let server = HttpServer::new(move || {
+            let answer = answer.clone();
+            let calls = calls.clone();
+            App::new().route("/introspect", web::post().to(move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                let body = answer.lock().unwrap().clone();
+                async move { HttpResponse::Ok().json(body) }
+            }))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let url = format!("http://{}/introspect", server.addrs()[0]);
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+        (url, handle)
+    }
+
+    #[test]
+    fn an_active_response_becomes_claims() {
+        let claims = introspected_claims(response(NOW + 300), &policy(), NOW).unwrap();
+
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.preferred_username.as_deref(), Some("alice"));
+        assert!(claims.has_scope("items:read"));
+    }
+
+    #[test]
+    fn a_preferred_username_is_not_replaced_by_the_username() {
+        let mut body = response(NOW + 300);
+        body.insert("preferred_username".to_string(), json!("alice.smith"));
+
+        let claims = introspected_claims(body, &policy(), NOW).unwrap();
+
+        assert_eq!(claims.preferred_username.as_deref(), Some("alice.smith"));
+    }
+
+    #[test]
+    fn inactive_responses_are_rejected() {
+        let mut body = response(NOW + 300);
+        body.insert("active".to_string(), json!(false));
+        assert!(matches!(introspected_claims(body, &policy(), NOW), Err(TokenRejection::Inactive)));
+
+        assert!(matches!(
+            introspected_claims(json!({ "active": false }).as_object().unwrap().clone(), &policy(), NOW),
+            Err(TokenRejection::Inactive)
+        ));
+
+        let mut body = response(NOW + 300);
+        body.remove("active");
+        assert!(matches!(introspected_claims(body, &policy(), NOW), Err(TokenRejection::Inactive)));
+    }
+
+    #[test]
+    fn responses_that_break_the_policy_are_rejected() {
+        assert!(matches!(introspected_claims(response(NOW - 1), &policy(), NOW), Err(TokenRejection::Expired)));
+
+        let mut body = response(NOW + 300);
+        body.insert("azp".to_string(), json!("other-app"));
+        assert!(matches!(introspected_claims(body, &policy(), NOW), Err(TokenRejection::DisallowedAzp(_))));
+    }
+
+    #[test]
+    fn cached_results_are_used_until_the_token_expires() {
+        let client = client(String::new());
+        let claims = introspected_claims(response(NOW + 300), &policy(), NOW).unwrap();
+        client.store(b"token".to_vec(), claims, NOW);
+
+        assert_eq!(client.cached(b"token", NOW).unwrap().sub, "user-1");
+        assert_eq!(client.cached(b"token", NOW + 299).unwrap().sub, "user-1");
+        assert!(client.cached(b"token", NOW + 300).is_none());
+        assert!(client.cached(b"other-token", NOW).is_none());
+    }
+
+    #[test]
+    fn a_full_cache_drops_expired_results_first() {
+        let client = client(String::new());
+        let expired = introspected_claims(response(NOW + 10), &policy(), NOW).unwrap();
+        let live = introspected_claims(response(NOW + 300), &policy(), NOW).unwrap();
+        for i in 0..MAX_CACHED_TOKENS {
+            let claims = if i % 2 == 0 { expired.clone() } else { live.clone() };
+            client.store(i.to_be_bytes().to_vec(), claims, NOW);
+        }
+
+        client.store(b"new".to_vec(), live.clone(), NOW + 20);
+
+        assert_eq!(client.cache.read().unwrap().len(), MAX_CACHED_TOKENS / 2 + 1);
+        assert!(client.cached(&1usize.to_be_bytes(), NOW + 20).is_some());
+        assert!(client.cached(b"new", NOW + 20).is_some());
+    }
+
+    #[test]
+    fn a_cache_full_of_live_results_is_cleared() {
+        let client = client(String::new());
+        let live = introspected_claims(response(NOW + 300), &policy(), NOW).unwrap();
+        for i in 0..MAX_CACHED_TOKENS {
+            client.store(i.to_be_bytes().to_vec(), live.clone(), NOW);
+        }
+
+        client.store(b"new".to_vec(), live, NOW);
+
+        assert_eq!(client.cache.read().unwrap().len(), 1);
+        assert!(client.cached(b"new", NOW).is_some());
+    }
+
+    #[actix_web::test]
+    async fn active_tokens_are_introspected_once_and_inactive_ones_every_time() {
+        let now = now_secs();
+        let answer = Arc::new(Mutex::new(Value::Object(response(now + 300))));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (url, server) = endpoint(answer.clone(), calls.clone()).await;
+        let client = client(url);
+
+        assert_eq!(client.introspect("active-token", &policy()).await.unwrap().sub, "user-1");
+        assert_eq!(client.introspect("active-token", &policy()).await.unwrap().sub, "user-1");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        *answer.lock().unwrap() = json!({ "active": false });
+        assert!(matches!(client.introspect("revoked-token", &policy()).await, Err(TokenRejection::Inactive)));
+        assert!(matches!(client.introspect("revoked-token", &policy()).await, Err(TokenRejection::Inactive)));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        server.stop(false).await;
+    }
+}
@@ -0,0 +1,104 @@
+use std::env;
+use std::fmt;
+
+use tracing::info;
+
+/// One trusted token issuer (a Keycloak realm), read from the environment at startup.
+///
+/// # Attributes
+/// * name (String): short name of the issuer, used for per-issuer variables and in logs
+/// * issuer_url (String): the issuer URL; discovery is fetched from `<issuer_url>/.well-known/openid-configuration`
+/// * client_id (String): this application's client in that realm
+/// * client_secret (Option<String>): that client's secret, for confidential clients
+#[derive(Clone)]
+pub struct IssuerConfig {
+    pub name: String,
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+}
+
+impl fmt::Debug for IssuerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IssuerConfig")
+            .field("name", &self.name)
+            .field("issuer_url", &self.issuer_url)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &self.client_secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl IssuerConfig {
+
+    /// Builds the list of trusted issuers.
+    ///
+    /// `OIDC_ISSUERS` lists them as comma-separated `name=url` pairs. When it is not set, the single
+    /// realm given by `KEYCLOAK_AUTH_SERVER_URL` and `KEYCLOAK_REALM` is trusted, as before. Set to an
+    /// empty value, no OIDC issuer is trusted, for deployments that only use local login.
+    ///
+    /// # Arguments
+    /// * auth_server_url (&str): the default Keycloak server
+    /// * realm (&str): the default realm
+    /// * client_id (&str): the default client ID, overridable per issuer with `KEYCLOAK_CLIENT_ID_<NAME>`
+    ///
+    /// # Returns
+    /// * (Result<Vec<IssuerConfig>, String>): the issuers, or an error if `OIDC_ISSUERS` is malformed
+    pub fn from_env(auth_server_url: &str, realm: &str, client_id: &str) -> Result<Vec<IssuerConfig>, String> {
+        let entries: Vec<(String, String)> = match env::var("OIDC_ISSUERS") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| match entry.split_once('=') {
+                    Some((name, url)) if !name.trim().is_empty() && !url.trim().is_empty() => {
+                        Ok((name.trim().to_string(), url.trim().trim_end_matches('/').to_string()))
+                    },
+                    _ => Err(format!("Invalid OIDC_ISSUERS entry '{}': expected name=url", entry)),
+                })
+                .collect::<Result<Vec<(String, String)>, String>>()?,
+            Err(_) => vec![(
+                realm.to_string(),
+                format!("{}/realms/{}", auth_server_url.trim_end_matches('/'), realm),
+            )],
+        };
+        for (i, (name, _)) in entries.iter().enumerate() {
+            if entries[..i].iter().any(|(other, _)| other.eq_ignore_ascii_case(name)) {
+                return Err(format!("OIDC_ISSUERS lists the issuer '{}' twice", name));
+            }
+        }
+
+        let issuers: Vec<IssuerConfig> = entries
+            .into_iter()
+            .map(|(name, issuer_url)| {
+                let mut issuer = IssuerConfig { name, issuer_url, client_id: String::new(), client_secret: None };
+                issuer.client_id = issuer.var("KEYCLOAK_CLIENT_ID").unwrap_or_else(|| client_id.to_string());
+                issuer.client_secret = issuer.var("KEYCLOAK_CLIENT_SECRET").filter(|secret| !secret.is_empty());
+                issuer
+            })
+            .collect();
+        for issuer in &issuers {
+            info!("Trusting issuer '{}' at {} (client {})", issuer.name, issuer.issuer_url, issuer.client_id);
+        }
+        Ok(issuers)
+    }
+
+    /// Reads a setting for this issuer: `<NAME>_<ISSUER>` if set, otherwise the global `<NAME>`.
+    ///
+    /// The issuer name is upper-cased and `-` and `.` become `_`, so issuer `acme-eu` reads
+    /// `TOKEN_AUDIENCES_ACME_EU` before `TOKEN_AUDIENCES`.
+    ///
+    /// # Arguments
+    /// * name (&str): the global variable name
+    ///
+    /// # Returns
+    /// (Option<String>): the value, if either variable is set
+    pub fn var(&self, name: &str) -> Option<String> {
+        let suffix: String = self
+            .name
+            .chars()
+            .map(|c| if c == '-' || c == '.' { '_' } else { c.to_ascii_uppercase() })
+            .collect();
+        env::var(format!("{}_{}", name, suffix)).or_else(|_| env::var(name)).ok()
+    }
+}
@@ -0,0 +1,270 @@
+use std::env;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use tracing::{info, warn, error};
+use reqwest::header::CACHE_CONTROL;
+use tokio::sync::Mutex;
+
+use crate::middleware::request_id;
+
+/// Tuning knobs for the JWKS cache, read from the environment at startup.
+///
+/// # Attributes
+/// * default_ttl (Duration): TTL used when the JWKS response has no usable `Cache-Control: max-age`
+/// * min_refetch_interval (Duration): minimum time between two fetches triggered by an unknown `kid`
+/// * refresh_ahead (Duration): how long before expiry the background task refreshes the key set
+#[derive(Clone, Debug)]
+pub struct JwksCacheConfig {
+    pub default_ttl: Duration,
+    pub min_refetch_interval: Duration,
+    pub refresh_ahead: Duration,
+}
+
+impl JwksCacheConfig {
+
+    /// Builds the cache configuration from `JWKS_DEFAULT_TTL_SECS`, `JWKS_MIN_REFETCH_INTERVAL_SECS`
+    /// and `JWKS_REFRESH_AHEAD_SECS`, falling back to sensible defaults.
+    ///
+    /// # Returns
+    /// (JwksCacheConfig): the configuration for the cache
+    pub fn from_env() -> JwksCacheConfig {
+        JwksCacheConfig {
+            default_ttl: Duration::from_secs(env_secs("JWKS_DEFAULT_TTL_SECS", 300)),
+            min_refetch_interval: Duration::from_secs(env_secs("JWKS_MIN_REFETCH_INTERVAL_SECS", 10)),
+            refresh_ahead: Duration::from_secs(env_secs("JWKS_REFRESH_AHEAD_SECS", 30)),
+        }
+    }
+}
+
+fn env_secs(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+/// The last key set that was fetched successfully.
+struct CachedKeys {
+    keys: JwkSet,
+    expires_at: Instant,
+}
+
+/// Shared cache of the JSON Web Key Set published by Keycloak.
+///
+/// The cache is stored in the application data and shared by every worker. Tokens are validated
+/// against the last good key set, so validation keeps working while Keycloak is unreachable.
+/// An unknown `kid` forces a (rate-limited) refetch, and a background task refreshes the set
+/// before the TTL taken from the response's `Cache-Control` header runs out.
+pub struct JwksCache {
+    jwks_uri: String,
+    config: JwksCacheConfig,
+    client: reqwest::Client,
+    keys: RwLock<Option<CachedKeys>>,
+    last_fetch: Mutex<Option<Instant>>,
+}
+
+impl JwksCache {
+
+    /// Creates an empty cache for the given JWKS URI.
+    ///
+    /// # Arguments
+    /// * jwks_uri (String): the URI the key set is fetched from
+    /// * config (JwksCacheConfig): TTL and rate-limit settings
+    ///
+    /// # Returns
+    /// (JwksCache): a cache with no keys loaded yet
+    pub fn new(jwks_uri: String, config: JwksCacheConfig) -> JwksCache {
+        JwksCache {
+            jwks_uri,
+            config,
+            client: reqwest::Client::new(),
+            keys: RwLock::new(None),
+            last_fetch: Mutex::new(None),
+        }
+    }
+
+    /// Creates a cache that always holds the given keys and never fetches, for keys this application
+    /// publishes itself.
+    ///
+    /// # Arguments
+    /// * keys (JwkSet): the key set
+    ///
+    /// # Returns
+    /// (JwksCache): a cache with the keys loaded
+    pub fn with_keys(keys: JwkSet) -> JwksCache {
+        let cache = JwksCache::new(String::new(), JwksCacheConfig::from_env());
+        *cache.keys.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(CachedKeys {
+            keys,
+            expires_at: Instant::now() + Duration::from_secs(365 * 24 * 60 * 60),
+        });
+        cache
+    }
+
+    /// Looks up the JWK for a `kid`, refetching the key set if the `kid` is not known yet.
+    ///
+    /// # Arguments
+    /// * kid (&str): the key ID taken from the JWT header
+    ///
+    /// # Returns
+    /// * (Result<Jwk, String>): the matching key, or an error message if it cannot be found
+    pub async fn get_key(&self, kid: &str) -> Result<Jwk, String> {
+        if let Some(jwk) = self.find_cached(kid) {
+            return Ok(jwk);
+        }
+        if self.jwks_uri.is_empty() {
+            return Err(format!("No matching JWK found for kid: {}", kid));
+        }
+        info!("No cached JWK for kid '{}'. Attempting a forced JWKS refetch.", kid);
+
+        // Only one refetch runs at a time; callers queued behind it re-check the cache first.
+        let mut last_fetch = self.last_fetch.lock().await;
+        if let Some(jwk) = self.find_cached(kid) {
+            return Ok(jwk);
+        }
+        if let Some(at) = *last_fetch {
+            if at.elapsed() < self.config.min_refetch_interval {
+                warn!("Skipping JWKS refetch for kid '{}': last fetch was {:?} ago.", kid, at.elapsed());
+                return Err(format!("No matching JWK found for kid: {}", kid));
+            }
+        }
+        *last_fetch = Some(Instant::now());
+        self.fetch().await?;
+
+        self.find_cached(kid)
+            .ok_or_else(|| {
+                warn!("No JWK found with kid '{}' in the refreshed JWKS.", kid);
+                format!("No matching JWK found for kid: {}", kid)
+            })
+    }
+
+    /// Fetches the key set and replaces the cached copy. On failure the previous copy is kept.
+    ///
+    /// # Returns
+    /// * (Result<(), String>): an error message if the key set could not be fetched or parsed
+    pub async fn refresh(&self) -> Result<(), String> {
+        let mut last_fetch = self.last_fetch.lock().await;
+        *last_fetch = Some(Instant::now());
+        self.fetch().await
+    }
+
+    /// Spawns a task that keeps the cache warm, refreshing it shortly before the TTL runs out.
+    ///
+    /// # Arguments
+    /// * cache (Arc<JwksCache>): the shared cache to refresh
+    pub fn spawn_refresh_task(cache: Arc<JwksCache>) {
+        actix_rt::spawn(async move {
+            loop {
+                let wait = match cache.refresh().await {
+                    Ok(()) => cache.time_until_refresh(),
+                    Err(e) => {
+                        warn!("Background JWKS refresh failed, keeping the last good key set: {}", e);
+                        cache.config.min_refetch_interval.max(Duration::from_secs(1))
+                    }
+                };
+                info!("Next background JWKS refresh in {:?}.", wait);
+                actix_rt::time::sleep(wait).await;
+            }
+        });
+    }
+
+    fn find_cached(&self, kid: &str) -> Option<Jwk> {
+        let keys = self.keys.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        keys.as_ref().and_then(|cached| cached.keys.find(kid).cloned())
+    }
+
+    fn time_until_refresh(&self) -> Duration {
+        let keys = self.keys.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let until_expiry = keys
+            .as_ref()
+            .map(|cached| cached.expires_at.saturating_duration_since(Instant::now()))
+            .unwrap_or_default();
+        until_expiry
+            .saturating_sub(self.config.refresh_ahead)
+            .max(self.config.min_refetch_interval)
+    }
+
+    async fn fetch(&self) -> Result<(), String> {
+        info!("Fetching JWKS from {}", self.jwks_uri);
+        let response = request_id::propagate(self.client.get(&self.jwks_uri)).send().await.map_err(|e| {
+            error!("Failed to fetch JWKS from {}: {}", self.jwks_uri, e);
+            format!("Failed to fetch JWKS: {}", e)
+        })?;
+        if !response.status().is_success() {
+            error!("Failed to fetch JWKS from {}: HTTP Status {}", self.jwks_uri, response.status());
+            return Err(format!("Failed to fetch JWKS: HTTP Status {}", response.status()));
+        }
+
+        let ttl = response
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_max_age)
+            .unwrap_or(self.config.default_ttl);
+
+        let keys: JwkSet = response.json().await.map_err(|e| {
+            error!("Failed to parse JWKS JSON from {}: {}", self.jwks_uri, e);
+            format!("Failed to parse JWKS: {}", e)
+        })?;
+        info!("Successfully fetched and parsed JWKS with {} keys (TTL {:?}).", keys.keys.len(), ttl);
+
+        let mut cached = self.keys.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *cached = Some(CachedKeys {
+            keys,
+            expires_at: Instant::now() + ttl,
+        });
+        Ok(())
+    }
+}
+
+/// Extracts the `max-age` directive from a `Cache-Control` header value.
+///
+/// `no-cache` and `no-store` are treated as "no usable TTL" so the configured default applies.
+fn parse_max_age(cache_control: &str) -> Option<Duration> {
+    let directives: Vec<String> = cache_control
+        .split(',')
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .collect();
+    if directives.iter().any(|d| d == "no-cache" || d == "no-store") {
+        return None;
+    }
+    directives
+        .iter()
+        .find_map(|d| d.strip_prefix("max-age="))
+        .and_then(|seconds| seconds.trim_matches('"').parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_age_is_read_among_other_directives() {
+        assert_eq!(parse_max_age("max-age=300"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_max_age("public, Max-Age=\"60\", must-revalidate"), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn header_without_max_age_has_no_ttl() {
+        assert_eq!(parse_max_age("public, must-revalidate"), None);
+        assert_eq!(parse_max_age(""), None);
+    }
+
+    #[test]
+    fn malformed_max_age_has_no_ttl() {
+        assert_eq!(parse_max_age("max-age=soon"), None);
+        assert_eq!(parse_max_age("max-age=-5"), None);
+        assert_eq!(parse_max_age("max-age="), None);
+        assert_eq!(parse_max_age("max-age=0"), None);
+    }
+
+    #[test]
+    fn no_cache_overrides_max_age() {
+        assert_eq!(parse_max_age("no-cache"), None);
+        assert_eq!(parse_max_age("max-age=300, no-cache"), None);
+        assert_eq!(parse_max_age("no-store, max-age=300"), None);
+    }
+}
@@ -1,14 +1,95 @@
 use serde::Deserialize;
 use reqwest;
-use log::{info, warn, error};
+use tracing::{info, warn, error};
 
-#[derive(Debug, Deserialize)]
+/// The OpenID Connect discovery document published by the Keycloak realm.
+///
+/// Held by each issuer's `ProviderState` in the `ProviderRegistry` once its discovery succeeds, so
+/// handlers and middleware can reach the realm's endpoints without rebuilding URLs from settings. Endpoints that are
+/// optional in the OpenID Connect Discovery and OAuth 2.0 metadata specifications are `Option`s.
+///
+/// # Attributes
+/// * issuer (String): the `iss` value of every token the realm issues
+/// * authorization_endpoint (String): where browsers are sent to log in
+/// * token_endpoint (String): where codes and refresh tokens are exchanged for tokens
+/// * userinfo_endpoint (Option<String>): returns the profile of the token's user
+/// * jwks_uri (String): the realm's public signing keys
+/// * introspection_endpoint (Option<String>): RFC 7662 token introspection
+/// * end_session_endpoint (Option<String>): RP-initiated logout
+/// * revocation_endpoint (Option<String>): RFC 7009 token revocation, used to revoke a session's refresh token on logout
+/// * id_token_signing_alg_values_supported (Vec<String>): algorithms the realm signs tokens with
+/// * code_challenge_methods_supported (Vec<String>): PKCE methods the realm accepts
+/// * grant_types_supported (Vec<String>): OAuth 2.0 grant types the realm accepts; when the document
+///   leaves it out, `authorization_code` and `implicit`, as RFC 8414 specifies
+#[derive(Debug, Clone, Deserialize)]
 pub struct OpenIdConfig {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    #[serde(default)]
+    pub userinfo_endpoint: Option<String>,
     pub jwks_uri: String,
-    // Add other fields from the OpenID Connect configuration that might be useful
+    #[serde(default)]
+    pub introspection_endpoint: Option<String>,
+    #[serde(default)]
+    pub end_session_endpoint: Option<String>,
+    #[serde(default)]
+    pub revocation_endpoint: Option<String>,
+    #[serde(default)]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    #[serde(default)]
+    pub code_challenge_methods_supported: Vec<String>,
+    #[serde(default)]
+    pub grant_types_supported: Vec<String>,
 }
 
-pub async fn fetch_keycloak_openid_config(keycloak_base_url: &str) -> Result<String, String> {
+impl OpenIdConfig {
+
+    /// Checks whether the realm advertises a signing algorithm, e.g. `"RS256"`.
+    ///
+    /// # Arguments
+    /// * algorithm (&str): the JOSE algorithm name
+    ///
+    /// # Returns
+    /// (bool): true if the algorithm is listed in `id_token_signing_alg_values_supported`
+    pub fn supports_signing_alg(&self, algorithm: &str) -> bool {
+        self.id_token_signing_alg_values_supported.iter().any(|alg| alg == algorithm)
+    }
+
+    /// Checks whether the realm accepts a PKCE code challenge method, e.g. `"S256"`.
+    ///
+    /// # Arguments
+    /// * method (&str): the code challenge method
+    ///
+    /// # Returns
+    /// (bool): true if the method is listed in `code_challenge_methods_supported`
+    pub fn supports_code_challenge_method(&self, method: &str) -> bool {
+        self.code_challenge_methods_supported.iter().any(|supported| supported == method)
+    }
+
+    /// Checks whether the realm accepts an OAuth 2.0 grant type, e.g. `"authorization_code"`.
+    ///
+    /// # Arguments
+    /// * grant_type (&str): the grant type
+    ///
+    /// # Returns
+    /// (bool): true if the grant type is listed in `grant_types_supported`, or is a default one when the list is missing
+    pub fn supports_grant_type(&self, grant_type: &str) -> bool {
+        if self.grant_types_supported.is_empty() {
+            return ["authorization_code", "implicit"].contains(&grant_type);
+        }
+        self.grant_types_supported.iter().any(|supported| supported == grant_type)
+    }
+}
+
+/// Fetches and parses the realm's OpenID Connect discovery document.
+///
+/// # Arguments
+/// * keycloak_base_url (&str): the realm URL, e.g. `http://localhost:8080/realms/myrealm`
+///
+/// # Returns
+/// * (Result<OpenIdConfig, String>): the discovery document, or an error message
+pub async fn fetch_keycloak_openid_config(keycloak_base_url: &str) -> Result<OpenIdConfig, String> {
     let config_url = format!("{}/.well-known/openid-configuration", keycloak_base_url);
     info!("Attempting to fetch Keycloak OpenID Connect configuration from: {}", config_url);
 
@@ -18,8 +99,16 @@ pub async fn fetch_keycloak_openid_config(keycloak_base_url: &str) -> Result<Str
                 match response.json::<OpenIdConfig>().await {
                     Ok(config) => {
                         info!("Successfully fetched Keycloak OpenID Connect configuration.");
-                        info!("JWKS URI: {}", config.jwks_uri);
-                        Ok(config.jwks_uri)
+                        info!("Issuer: {}, JWKS URI: {}", config.issuer, config.jwks_uri);
+                        info!(
+                            "Token endpoint: {}, introspection endpoint: {:?}, end session endpoint: {:?}",
+                            config.token_endpoint, config.introspection_endpoint, config.end_session_endpoint
+                        );
+                        info!(
+                            "Userinfo endpoint: {:?}, revocation endpoint: {:?}",
+                            config.userinfo_endpoint, config.revocation_endpoint
+                        );
+                        Ok(config)
                     },
                     Err(e) => {
                         error!("Failed to parse OpenID Connect configuration JSON: {}", e);
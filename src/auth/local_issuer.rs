@@ -0,0 +1,325 @@
+use std::env;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use tracing::{info, warn};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use serde::Serialize;
+
+use crate::auth::introspection::TokenMode;
+use crate::auth::issuers::IssuerConfig;
+use crate::auth::jwks_cache::JwksCache;
+use crate::auth::keycloak_config::OpenIdConfig;
+use crate::auth::provider::{OidcProvider, ProviderState};
+use crate::auth::validation_policy::TokenValidationPolicy;
+use crate::models::user::new_user::LOCAL_ISSUER;
+use crate::models::user::user::User;
+
+/// One key this application signs its own access tokens with.
+///
+/// # Attributes
+/// * kid (String): the key ID put in token headers and in the published JWKS
+/// * encoding_key (EncodingKey): the private key
+/// * jwk (Jwk): the public key, as published at `/auth/jwks`
+pub struct LocalSigningKey {
+    pub kid: String,
+    encoding_key: EncodingKey,
+    pub jwk: Jwk,
+}
+
+impl LocalSigningKey {
+
+    /// Loads an RSA private key from a PEM file (PKCS#1 or PKCS#8).
+    ///
+    /// # Arguments
+    /// * kid (&str): the key ID
+    /// * path (&str): the PEM file
+    ///
+    /// # Returns
+    /// * (Result<LocalSigningKey, String>): the key, or an error if the file is not an RSA private key
+    pub fn from_pem_file(kid: &str, path: &str) -> Result<LocalSigningKey, String> {
+        let pem = fs::read_to_string(path).map_err(|e| format!("Cannot read signing key '{}' from {}: {}", kid, path, e))?;
+        let private_key = RsaPrivateKey::from_pkcs8_pem(&pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&pem))
+            .map_err(|e| format!("Signing key '{}' in {} is not an RSA private key: {}", kid, path, e))?;
+        let encoding_key = EncodingKey::from_rsa_pem(pem.as_bytes())
+            .map_err(|e| format!("Signing key '{}' in {} cannot be used: {}", kid, path, e))?;
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::RS256),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+                e: URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+            }),
+        };
+        Ok(LocalSigningKey { kid: kid.to_string(), encoding_key, jwk })
+    }
+}
+
+/// Loads the keys listed in `LOCAL_SIGNING_KEYS`, in order.
+///
+/// # Arguments
+/// * value (&str): comma-separated `kid=path` pairs
+///
+/// # Returns
+/// * (Result<Vec<LocalSigningKey>, String>): the keys, or an error for a malformed entry, a repeated kid or an unusable key
+fn load_signing_keys(value: &str) -> Result<Vec<LocalSigningKey>, String> {
+    let mut keys: Vec<LocalSigningKey> = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (kid, path) = entry
+            .split_once('=')
+            .map(|(kid, path)| (kid.trim(), path.trim()))
+            .filter(|(kid, path)| !kid.is_empty() && !path.is_empty())
+            .ok_or_else(|| format!("Invalid LOCAL_SIGNING_KEYS entry '{}': expected kid=path", entry))?;
+        if keys.iter().any(|key| key.kid == kid) {
+            return Err(format!("LOCAL_SIGNING_KEYS lists the key '{}' twice", kid));
+        }
+        keys.push(LocalSigningKey::from_pem_file(kid, path)?);
+    }
+    Ok(keys)
+}
+
+/// The claims of an access token issued by `/auth/login`, shaped like a Keycloak token so the same
+/// validation and permission checks apply.
+#[derive(Serialize)]
+struct LocalAccessClaims<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    aud: &'a str,
+    azp: &'a str,
+    iat: u64,
+    exp: u64,
+    preferred_username: &'a str,
+    email: Option<&'a str>,
+    scope: &'a str,
+}
+
+/// Issues access tokens for accounts registered with this application, for deployments without
+/// Keycloak. Disabled unless signing keys are configured.
+///
+/// # Attributes
+/// * keys (Vec<LocalSigningKey>): the first key signs new tokens; all are published so tokens signed
+///   with a retired key stay valid until they expire
+/// * audience (String): the `aud` and `azp` of the issued tokens
+/// * scopes (String): the space-separated scopes granted to local users
+/// * access_token_ttl (Duration): lifetime of an access token
+/// * refresh_token_ttl (Duration): lifetime of a refresh token; every refresh issues a new one
+/// * require_verified_email (bool): whether users must verify their email address before logging in
+pub struct LocalTokenIssuer {
+    keys: Vec<LocalSigningKey>,
+    pub audience: String,
+    pub scopes: String,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+    pub require_verified_email: bool,
+}
+
+impl LocalTokenIssuer {
+
+    /// Builds the issuer from `LOCAL_SIGNING_KEYS` (comma-separated `kid=path` pairs, the signing key
+    /// first), `LOCAL_TOKEN_AUDIENCE`, `LOCAL_TOKEN_SCOPES`, `LOCAL_ACCESS_TOKEN_TTL_SECS`,
+    /// `LOCAL_REFRESH_TOKEN_TTL_SECS` and `LOCAL_LOGIN_REQUIRE_VERIFIED_EMAIL`.
+    ///
+    /// # Returns
+    /// * (Result<LocalTokenIssuer, String>): the issuer, or an error if a key cannot be loaded
+    pub fn from_env() -> Result<LocalTokenIssuer, String> {
+        let keys = load_signing_keys(&env::var("LOCAL_SIGNING_KEYS").unwrap_or_default())?;
+        let env_u64 = |name: &str, default: u64| {
+            env::var(name).ok().and_then(|value| value.parse::<u64>().ok()).unwrap_or(default)
+        };
+        let issuer = LocalTokenIssuer {
+            keys,
+            audience: env::var("LOCAL_TOKEN_AUDIENCE").unwrap_or_else(|_| "todo".to_string()),
+            scopes: env::var("LOCAL_TOKEN_SCOPES").unwrap_or_else(|_| "profile items:read items:write".to_string()),
+            access_token_ttl: Duration::from_secs(env_u64("LOCAL_ACCESS_TOKEN_TTL_SECS", 5 * 60)),
+            refresh_token_ttl: Duration::from_secs(env_u64("LOCAL_REFRESH_TOKEN_TTL_SECS", 14 * 24 * 60 * 60)),
+            require_verified_email: env::var("LOCAL_LOGIN_REQUIRE_VERIFIED_EMAIL").map(|value| value == "true").unwrap_or(false),
+        };
+        match issuer.keys.first() {
+            Some(key) => info!("Local login enabled; signing with key '{}', publishing {} key(s).", key.kid, issuer.keys.len()),
+            None => warn!("LOCAL_SIGNING_KEYS is not set; /auth/login will not issue tokens."),
+        }
+        Ok(issuer)
+    }
+
+    /// Whether local login can issue tokens.
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Returns the public keys, for the JWKS endpoint.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet { keys: self.keys.iter().map(|key| key.jwk.clone()).collect() }
+    }
+
+    /// Builds the provider that verifies the tokens this issuer signs. It is ready immediately, as
+    /// there is nothing to discover.
+    ///
+    /// # Returns
+    /// (Option<OidcProvider>): the provider, or `None` if local login is disabled
+    pub fn provider(&self) -> Option<OidcProvider> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let openid_config = OpenIdConfig {
+            issuer: LOCAL_ISSUER.to_string(),
+            authorization_endpoint: String::new(),
+            token_endpoint: "/auth/login".to_string(),
+            userinfo_endpoint: None,
+            jwks_uri: "/auth/jwks".to_string(),
+            introspection_endpoint: None,
+            end_session_endpoint: None,
+            revocation_endpoint: None,
+            id_token_signing_alg_values_supported: vec!["RS256".to_string()],
+            code_challenge_methods_supported: Vec::new(),
+            grant_types_supported: vec!["password".to_string(), "refresh_token".to_string()],
+        };
+        let policy = TokenValidationPolicy {
+            issuer: LOCAL_ISSUER.to_string(),
+            required_claims: ["exp", "iat", "iss", "sub"].iter().map(|claim| claim.to_string()).collect(),
+            audiences: vec![self.audience.clone()],
+            allowed_azp: vec![self.audience.clone()],
+            leeway: 30,
+            algorithms: vec![Algorithm::RS256],
+        };
+        let issuer = IssuerConfig {
+            name: LOCAL_ISSUER.to_string(),
+            issuer_url: LOCAL_ISSUER.to_string(),
+            client_id: self.audience.clone(),
+            client_secret: None,
+        };
+        Some(OidcProvider::with_state(issuer, ProviderState {
+            openid_config,
+            jwks_cache: Arc::new(JwksCache::with_keys(self.jwks())),
+            policy,
+            mode: TokenMode::Jwks,
+            introspection: None,
+        }))
+    }
+
+    /// Signs an access token for a local user with the current signing key.
+    ///
+    /// # Arguments
+    /// * user (&User): the user who logged in
+    ///
+    /// # Returns
+    /// * (Result<String, String>): the token, or an error if local login is disabled or signing failed
+    pub fn issue_access_token(&self, user: &User) -> Result<String, String> {
+        let key = self.keys.first().ok_or_else(|| "Local login is not configured".to_string())?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let claims = LocalAccessClaims {
+            iss: LOCAL_ISSUER,
+            sub: &user.subject,
+            aud: &self.audience,
+            azp: &self.audience,
+            iat: now,
+            exp: now + self.access_token_ttl.as_secs(),
+            preferred_username: &user.username,
+            email: user.email.as_deref(),
+            scope: &self.scopes,
+        };
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(key.kid.clone());
+        encode(&header, &claims, &key.encoding_key).map_err(|e| format!("Failed to sign access token: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::processes::{check_password, decode_token_header};
+    use crate::auth::validation_policy::TokenRejection;
+
+    // Keys generated locally with `openssl genpkey` for these tests only.
+    const CURRENT_KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/auth/test_keys/rsa2.pem");
+    const RETIRED_KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/auth/test_keys/rsa.pem");
+
+    fn issuer(signing_keys: &str) -> LocalTokenIssuer {
+        LocalTokenIssuer {
+            keys: load_signing_keys(signing_keys).unwrap(),
+            audience: "todo".to_string(),
+            scopes: "profile items:read".to_string(),
+            access_token_ttl: Duration::from_secs(300),
+            refresh_token_ttl: Duration::from_secs(3600),
+            require_verified_email: false,
+        }
+    }
+
+    fn user() -> User {
+        User::for_test("local-1", LOCAL_ISSUER, "local-1")
+    }
+
+    async fn verify(issuer: &LocalTokenIssuer, token: &str) -> Result<crate::auth::processes::Claims, TokenRejection> {
+        let provider = issuer.provider().unwrap();
+        let state = provider.get().unwrap();
+        check_password(token, &state.jwks_cache, &state.policy).await
+    }
+
+    #[test]
+    fn the_first_listed_key_signs_and_every_key_is_published() {
+        let issuer = issuer(&format!("2026={}, 2025={}", CURRENT_KEY, RETIRED_KEY));
+
+        let token = issuer.issue_access_token(&user()).unwrap();
+
+        assert_eq!(decode_token_header(&token).unwrap().kid.as_deref(), Some("2026"));
+        let kids: Vec<Option<String>> = issuer.jwks().keys.into_iter().map(|jwk| jwk.common.key_id).collect();
+        assert_eq!(kids, vec![Some("2026".to_string()), Some("2025".to_string())]);
+    }
+
+    #[actix_web::test]
+    async fn tokens_verify_through_the_published_jwk() {
+        let issuer = issuer(&format!("2026={}", CURRENT_KEY));
+
+        let token = issuer.issue_access_token(&user()).unwrap();
+        let claims = verify(&issuer, &token).await.unwrap();
+
+        assert_eq!(claims.iss, LOCAL_ISSUER);
+        assert_eq!(claims.sub, "local-1");
+        assert_eq!(claims.aud, "todo");
+        assert!(claims.has_scope("profile"));
+        assert!(claims.has_scope("items:read"));
+        assert!(!claims.has_scope("items:write"));
+    }
+
+    #[actix_web::test]
+    async fn tokens_of_a_retired_key_verify_while_it_is_published() {
+        let before_rotation = issuer(&format!("2025={}", RETIRED_KEY));
+        let token = before_rotation.issue_access_token(&user()).unwrap();
+
+        let after_rotation = issuer(&format!("2026={},2025={}", CURRENT_KEY, RETIRED_KEY));
+        assert!(verify(&after_rotation, &token).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn tokens_signed_with_another_key_under_a_published_kid_are_rejected() {
+        let forger = issuer(&format!("2026={}", RETIRED_KEY));
+        let token = forger.issue_access_token(&user()).unwrap();
+
+        let issuer = issuer(&format!("2026={}", CURRENT_KEY));
+        assert!(matches!(verify(&issuer, &token).await, Err(TokenRejection::InvalidSignature)));
+    }
+
+    #[test]
+    fn malformed_signing_key_lists_are_refused() {
+        assert!(load_signing_keys("").unwrap().is_empty());
+        assert!(load_signing_keys(CURRENT_KEY).is_err());
+        assert!(load_signing_keys(&format!("=={}", CURRENT_KEY)).is_err());
+        assert!(load_signing_keys(&format!("a={},a={}", CURRENT_KEY, RETIRED_KEY)).is_err());
+        assert!(load_signing_keys("a=/nonexistent/key.pem").is_err());
+    }
+}
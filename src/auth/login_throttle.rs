@@ -0,0 +1,253 @@
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime};
+
+use actix_web::HttpRequest;
+use tracing::warn;
+
+use crate::models::login_failure::login_failure_utils;
+
+/// Limits on failed local logins, read from the environment at startup.
+///
+/// Failures are counted per username and per client IP. Each failure makes the next attempt wait
+/// longer, and a key that reaches its limit is locked for a while. The counts are stored in the
+/// database, so every worker sees the same state.
+///
+/// # Attributes
+/// * max_failures_per_user (i32): failures that lock a username
+/// * max_failures_per_ip (i32): failures that lock a client IP
+/// * failure_window (Duration): how long a failure is remembered
+/// * lockout (Duration): how long a locked username or IP is refused
+/// * base_delay (Duration): the wait after the first failure, doubled after each further failure
+/// * max_delay (Duration): upper bound for the wait
+/// * trusted_proxies (Vec<IpAddr>): reverse proxies whose `Forwarded` or `X-Forwarded-For` header names the client
+#[derive(Clone, Debug)]
+pub struct LoginThrottlePolicy {
+    pub max_failures_per_user: i32,
+    pub max_failures_per_ip: i32,
+    pub failure_window: Duration,
+    pub lockout: Duration,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// Whether a login may be attempted.
+pub enum LoginThrottle {
+    /// The login may go ahead after waiting this long.
+    Allowed(Duration),
+    /// The username or client IP is locked for this much longer.
+    Locked(Duration),
+}
+
+impl LoginThrottlePolicy {
+
+    /// Builds the policy from `LOGIN_MAX_FAILURES_PER_USER`, `LOGIN_MAX_FAILURES_PER_IP`,
+    /// `LOGIN_FAILURE_WINDOW_SECS`, `LOGIN_LOCKOUT_SECS`, `LOGIN_BASE_DELAY_MS`, `LOGIN_MAX_DELAY_MS` and
+    /// the comma-separated IP addresses in `TRUSTED_PROXIES`.
+    ///
+    /// # Returns
+    /// (LoginThrottlePolicy): the policy
+    pub fn from_env() -> LoginThrottlePolicy {
+        let env_u64 = |name: &str, default: u64| {
+            env::var(name).ok().and_then(|value| value.parse::<u64>().ok()).unwrap_or(default)
+        };
+        LoginThrottlePolicy {
+            max_failures_per_user: env_u64("LOGIN_MAX_FAILURES_PER_USER", 5).clamp(1, i32::MAX as u64) as i32,
+            max_failures_per_ip: env_u64("LOGIN_MAX_FAILURES_PER_IP", 20).clamp(1, i32::MAX as u64) as i32,
+            failure_window: Duration::from_secs(env_u64("LOGIN_FAILURE_WINDOW_SECS", 15 * 60)),
+            lockout: Duration::from_secs(env_u64("LOGIN_LOCKOUT_SECS", 15 * 60)),
+            base_delay: Duration::from_millis(env_u64("LOGIN_BASE_DELAY_MS", 250)),
+            max_delay: Duration::from_millis(env_u64("LOGIN_MAX_DELAY_MS", 5000)),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .filter_map(|entry| match entry.parse::<IpAddr>() {
+                    Ok(ip) => Some(ip),
+                    Err(_) => {
+                        warn!("Ignoring TRUSTED_PROXIES entry '{}': not an IP address", entry);
+                        None
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// The address failed logins from a request are counted under: the peer of the connection, or,
+    /// when the peer is a trusted proxy, the client it names in `Forwarded` or `X-Forwarded-For`.
+    /// Headers from any other peer are ignored, as a client could set them to dodge the limit.
+    ///
+    /// # Arguments
+    /// * req (&HttpRequest): the login request
+    ///
+    /// # Returns
+    /// (String): the client IP, or "unknown" if the connection has no peer address
+    pub fn client_ip(&self, req: &HttpRequest) -> String {
+        let peer = match req.peer_addr() {
+            Some(addr) => addr.ip(),
+            None => return "unknown".to_string(),
+        };
+        if !self.trusted_proxies.contains(&peer) {
+            return peer.to_string();
+        }
+        req.connection_info()
+            .realip_remote_addr()
+            .and_then(parse_ip)
+            .unwrap_or(peer)
+            .to_string()
+    }
+
+    /// Decides whether a login may be attempted, and how long it has to wait first.
+    ///
+    /// # Arguments
+    /// * username (&str): the username being logged in
+    /// * client_ip (&str): the address the request came from
+    ///
+    /// # Returns
+    /// * (Result<LoginThrottle, String>): the decision, or an error message if the database call failed
+    pub fn check(&self, username: &str, client_ip: &str) -> Result<LoginThrottle, String> {
+        let now = SystemTime::now();
+        let failures = login_failure_utils::find_failures(&[user_key(username), ip_key(client_ip)])?;
+
+        let locked_for = failures
+            .iter()
+            .filter_map(|failure| failure.locked_until)
+            .filter_map(|locked_until| locked_until.duration_since(now).ok())
+            .max();
+        if let Some(locked_for) = locked_for {
+            return Ok(LoginThrottle::Locked(locked_for));
+        }
+
+        let recent_failures = failures
+            .iter()
+            .filter(|failure| failure.last_failure_at + self.failure_window > now)
+            .map(|failure| failure.failures)
+            .max()
+            .unwrap_or(0);
+        Ok(LoginThrottle::Allowed(self.delay_for(recent_failures)))
+    }
+
+    /// Records a failed login against both the username and the client IP.
+    ///
+    /// # Arguments
+    /// * username (&str): the username that failed to log in
+    /// * client_ip (&str): the address the request came from
+    ///
+    /// # Returns
+    /// * (Result<(), String>): an error message if the database call failed
+    pub fn record_failure(&self, username: &str, client_ip: &str) -> Result<(), String> {
+        let user = login_failure_utils::record_failure(&user_key(username), self.max_failures_per_user, self.failure_window, self.lockout)?;
+        let ip = login_failure_utils::record_failure(&ip_key(client_ip), self.max_failures_per_ip, self.failure_window, self.lockout)?;
+        warn!("Failed login for '{}' from {} ({} failure(s) for the user, {} for the address).", username, client_ip, user.failures, ip.failures);
+        Ok(())
+    }
+
+    /// Forgets the failures of a username after it logged in. The client IP keeps its count, so one
+    /// valid account cannot be used to reset the limit while guessing the passwords of others.
+    ///
+    /// # Arguments
+    /// * username (&str): the username that logged in
+    ///
+    /// # Returns
+    /// * (Result<(), String>): an error message if the database call failed
+    pub fn record_success(&self, username: &str) -> Result<(), String> {
+        login_failure_utils::clear_failures(&user_key(username))
+    }
+
+    fn delay_for(&self, failures: i32) -> Duration {
+        if failures <= 0 {
+            return Duration::ZERO;
+        }
+        let factor = 1u32 << (failures - 1).min(16);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// The key the failed logins of a username are counted under.
+pub fn user_key(username: &str) -> String {
+    format!("user:{}", username)
+}
+
+fn ip_key(client_ip: &str) -> String {
+    format!("ip:{}", client_ip)
+}
+
+/// Reads an address from a forwarding header, with or without a port.
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| value.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn policy(trusted_proxies: &[&str]) -> LoginThrottlePolicy {
+        LoginThrottlePolicy {
+            max_failures_per_user: 5,
+            max_failures_per_ip: 20,
+            failure_window: Duration::from_secs(900),
+            lockout: Duration::from_secs(900),
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_millis(5000),
+            trusted_proxies: trusted_proxies.iter().map(|ip| ip.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn the_peer_address_is_used_without_trusted_proxies() {
+        let req = TestRequest::default()
+            .peer_addr("203.0.113.7:51000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .to_http_request();
+
+        assert_eq!(policy(&[]).client_ip(&req), "203.0.113.7");
+    }
+
+    #[test]
+    fn forwarding_headers_of_untrusted_peers_are_ignored() {
+        let req = TestRequest::default()
+            .peer_addr("203.0.113.7:51000".parse().unwrap())
+            .insert_header(("Forwarded", "for=198.51.100.1"))
+            .to_http_request();
+
+        assert_eq!(policy(&["10.0.0.2"]).client_ip(&req), "203.0.113.7");
+    }
+
+    #[test]
+    fn trusted_proxies_name_the_client() {
+        let policy = policy(&["10.0.0.2", "::1"]);
+
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.2:40000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .to_http_request();
+        assert_eq!(policy.client_ip(&req), "198.51.100.1");
+
+        let req = TestRequest::default()
+            .peer_addr("[::1]:40000".parse().unwrap())
+            .insert_header(("Forwarded", "for=\"[2001:db8::1]:4711\""))
+            .to_http_request();
+        assert_eq!(policy.client_ip(&req), "2001:db8::1");
+    }
+
+    #[test]
+    fn trusted_proxies_without_a_usable_header_count_as_the_client() {
+        let policy = policy(&["10.0.0.2"]);
+
+        let req = TestRequest::default().peer_addr("10.0.0.2:40000".parse().unwrap()).to_http_request();
+        assert_eq!(policy.client_ip(&req), "10.0.0.2");
+
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.2:40000".parse().unwrap())
+            .insert_header(("Forwarded", "for=_hidden"))
+            .to_http_request();
+        assert_eq!(policy.client_ip(&req), "10.0.0.2");
+    }
+}
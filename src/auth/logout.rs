@@ -0,0 +1,332 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use diesel::pg::PgConnection;
+use jsonwebtoken::{decode, DecodingKey};
+use tracing::warn;
+use serde_json::{Map, Value};
+
+use crate::auth::processes::{self, Claims};
+use crate::auth::provider::ProviderRegistry;
+use crate::auth::validation_policy::{algorithm_for_key, TokenRejection};
+use crate::database::establish_connection;
+use crate::models::session::session_utils;
+
+/// The event a logout token must announce (OpenID Connect Back-Channel Logout 1.0, section 2.4).
+const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+/// A verified logout token: which issuer ended which session or user, and when.
+///
+/// # Attributes
+/// * issuer_name (String): the configured name of the issuer
+/// * iss (String): the issuer URL, as found in its tokens
+/// * sid (Option<String>): the identity provider session that ended
+/// * sub (Option<String>): the user that was logged out
+/// * issued_at (SystemTime): when the identity provider issued the logout
+#[derive(Debug)]
+pub struct LogoutToken {
+    pub issuer_name: String,
+    pub iss: String,
+    pub sid: Option<String>,
+    pub sub: Option<String>,
+    pub issued_at: SystemTime,
+}
+
+/// Verifies a back-channel `logout_token`: signature against the issuer's cached JWKS, issuer,
+/// audience, expiry, the logout event, and the absence of a nonce.
+///
+/// # Arguments
+/// * token (&str): the logout token posted by the identity provider
+/// * registry (&ProviderRegistry): the trusted issuers
+///
+/// # Returns
+/// * (Result<LogoutToken, TokenRejection>): the logout, or the reason the token was rejected
+pub async fn verify_logout_token(token: &str, registry: &ProviderRegistry) -> Result<LogoutToken, TokenRejection> {
+    let iss = processes::unverified_issuer(token)
+        .ok_or_else(|| TokenRejection::InvalidLogoutToken("not a JWT naming its issuer".to_string()))?;
+    let provider = registry.find_by_issuer(&iss).ok_or_else(|| TokenRejection::UnknownIssuer(iss.clone()))?;
+    let state = provider
+        .get()
+        .ok_or_else(|| TokenRejection::IssuerUnavailable(provider.issuer.name.clone()))?;
+
+    let header = processes::decode_token_header(token)?;
+    let kid = header.kid.clone().ok_or(TokenRejection::MissingKid)?;
+    let jwk = state.jwks_cache.get_key(&kid).await.map_err(TokenRejection::UnknownKey)?;
+    let algorithm = algorithm_for_key(header.alg, &jwk)?;
+    let decoding_key = DecodingKey::from_jwk(&jwk)
+        .map_err(|e| TokenRejection::Malformed(format!("Failed to create decoding key: {}", e)))?;
+
+    // A logout token is addressed to this client and may name a session instead of a subject
+    let mut validation = state.policy.validation_for(algorithm)?;
+    validation.set_audience(&[&provider.issuer.client_id]);
+    validation.set_required_spec_claims(&["iss", "aud", "exp"]);
+    let claims = decode::<Map<String, Value>>(token, &decoding_key, &validation)?.claims;
+
+    let issued_at = claims
+        .get("iat")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| TokenRejection::MissingClaim("iat".to_string()))?;
+    let announces_logout = claims
+        .get("events")
+        .and_then(Value::as_object)
+        .is_some_and(|events| events.contains_key(BACKCHANNEL_LOGOUT_EVENT));
+    if !announces_logout {
+        return Err(TokenRejection::InvalidLogoutToken("missing the back-channel logout event".to_string()));
+    }
+    // A nonce would make the token usable as an ID token, so the specification forbids it
+    if claims.contains_key("nonce") {
+        return Err(TokenRejection::InvalidLogoutToken("must not carry a nonce".to_string()));
+    }
+    let sid = claims.get("sid").and_then(Value::as_str).map(str::to_string);
+    let sub = claims.get("sub").and_then(Value::as_str).map(str::to_string);
+    if sid.is_none() && sub.is_none() {
+        return Err(TokenRejection::InvalidLogoutToken("names neither 'sid' nor 'sub'".to_string()));
+    }
+
+    Ok(LogoutToken {
+        issuer_name: provider.issuer.name.clone(),
+        iss,
+        sid,
+        sub,
+        issued_at: UNIX_EPOCH + Duration::from_secs(issued_at),
+    })
+}
+
+/// Rejects a token whose session or user has been logged out through the back channel since it was issued.
+///
+/// # Arguments
+/// * claims (&Claims): the verified claims of the token
+///
+/// # Returns
+/// * (Result<(), TokenRejection>): `LoggedOut` if the token was ended by a logout
+pub fn ensure_not_logged_out(claims: &Claims) -> Result<(), TokenRejection> {
+    check_not_logged_out(&mut establish_connection(), claims)
+}
+
+fn check_not_logged_out(connection: &mut PgConnection, claims: &Claims) -> Result<(), TokenRejection> {
+    let issued_at = UNIX_EPOCH + Duration::from_secs(claims.iat as u64);
+    match session_utils::is_logged_out(connection, &claims.iss, claims.sid.as_deref(), &claims.sub, issued_at) {
+        Ok(false) => Ok(()),
+        Ok(true) => Err(TokenRejection::LoggedOut),
+        Err(e) => {
+            warn!("Could not check logouts for user {}: {}", claims.sub, e);
+            Err(TokenRejection::Malformed(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::prelude::*;
+    use jsonwebtoken::jwk::{Jwk, JwkSet};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::json;
+    use std::sync::Arc;
+    use crate::auth::introspection::TokenMode;
+    use crate::auth::issuers::IssuerConfig;
+    use crate::auth::jwks_cache::JwksCache;
+    use crate::auth::provider::{OidcProvider, ProviderState};
+    use crate::auth::validation_policy::TokenValidationPolicy;
+    use crate::database::try_establish_connection;
+    use crate::models::session::new_logout_revocation::NewLogoutRevocation;
+    use crate::schema::logout_revocations;
+
+    const ISSUER: &str = "https://keycloak.test/realms/myrealm";
+    // Generated locally with `openssl genpkey` for these tests only.
+    const RSA_PRIVATE_KEY: &str = include_str!("test_keys/rsa.pem");
+
+    fn registry() -> ProviderRegistry {
+        let jwk: Jwk = serde_json::from_value(json!({
+            "kty": "RSA",
+            "kid": "rsa-key",
+            "use": "sig",
+            "n": "xeQtrdGFJdRDbXZ0MOxTRM1iZ7_aTN5kVhypSzHj5uBoc44EjWF3_8nwalD7brGbvpmXYefPKa6fmAEXlHnOX57mQG_zrh4UNd8vbUHB9tdCQ55iRdYv9fnMd5VnlAkUqKkVmYt3_kv_kTx1GI7md3TdxNndwC0z1v_cG7Ozt-U3-lZGYYR7v-bbu05QJtGwfkZPGW9Ohen2O_U974IefElEugRzqryzxQuu5gTBLLy8PL9OLV400ecFVtJ1__RAa7sdn2eV1cYHJYqJhXCHA7pGOohbTle4lIKR1AykEyggLw--01-XxTS4LYe4rlepNIfong1YUJYREVL2B2kCTw",
+            "e": "AQAB",
+        }))
+        .unwrap();
+        let issuer = IssuerConfig {
+            name: "myrealm".to_string(),
+            issuer_url: ISSUER.to_string(),
+            client_id: "todo-app".to_string(),
+            client_secret: None,
+        };
+        let openid_config = serde_json::from_value(json!({
+            "issuer": ISSUER,
+            "authorization_endpoint": format!("{}/protocol/openid-connect/auth", ISSUER),
+            "token_endpoint": format!("{}/protocol/openid-connect/token", ISSUER),
+            "jwks_uri": format!("{}/protocol/openid-connect/certs", ISSUER),
+        }))
+        .unwrap();
+        let policy = TokenValidationPolicy {
+            issuer: ISSUER.to_string(),
+            required_claims: vec!["exp".to_string(), "iss".to_string(), "sub".to_string()],
+            audiences: vec!["todo-app".to_string()],
+            allowed_azp: vec!["todo-app".to_string()],
+            leeway: 0,
+            algorithms: vec![Algorithm::RS256],
+        };
+        let provider = OidcProvider::with_state(issuer, ProviderState {
+            openid_config,
+            jwks_cache: Arc::new(JwksCache::with_keys(JwkSet { keys: vec![jwk] })),
+            policy,
+            mode: TokenMode::Jwks,
+            introspection: None,
+        });
+        ProviderRegistry::new(Vec::new(), Some(provider))
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    /// A valid logout token for session `sid-1` of `user-1`, with `change` applied to its claims.
+    fn logout_token(change: impl FnOnce(&mut Map<String, Value>)) -> String {
+        let mut claims = json!({
+            "iss": ISSUER,
+            "aud": "todo-app",
+            "iat": now(),
+            "exp": now() + 120,
+            "jti": "logout-1",
+            "sid": "sid-1",
+            "sub": "user-1",
+            "events": { BACKCHANNEL_LOGOUT_EVENT: {} },
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+        change(&mut claims);
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("rsa-key".to_string());
+        encode(&header, &claims, &EncodingKey::from_rsa_pem(RSA_PRIVATE_KEY.as_bytes()).unwrap()).unwrap()
+    }
+
+    async fn rejection(change: impl FnOnce(&mut Map<String, Value>)) -> TokenRejection {
+        verify_logout_token(&logout_token(change), &registry()).await.unwrap_err()
+    }
+
+    #[actix_web::test]
+    async fn a_valid_logout_token_names_the_session_and_user() {
+        let logout = verify_logout_token(&logout_token(|_| {}), &registry()).await.unwrap();
+
+        assert_eq!(logout.issuer_name, "myrealm");
+        assert_eq!(logout.iss, ISSUER);
+        assert_eq!(logout.sid.as_deref(), Some("sid-1"));
+        assert_eq!(logout.sub.as_deref(), Some("user-1"));
+    }
+
+    #[actix_web::test]
+    async fn a_logout_token_naming_only_a_session_is_accepted() {
+        let logout = verify_logout_token(&logout_token(|claims| { claims.remove("sub"); }), &registry()).await.unwrap();
+
+        assert_eq!(logout.sid.as_deref(), Some("sid-1"));
+        assert_eq!(logout.sub, None);
+    }
+
+    #[actix_web::test]
+    async fn a_logout_token_without_the_logout_event_is_rejected() {
+        assert!(matches!(
+            rejection(|claims| { claims.remove("events"); }).await,
+            TokenRejection::InvalidLogoutToken(_)
+        ));
+        assert!(matches!(
+            rejection(|claims| { claims.insert("events".to_string(), json!({ "http://example.com/other-event": {} })); }).await,
+            TokenRejection::InvalidLogoutToken(_)
+        ));
+        assert!(matches!(
+            rejection(|claims| { claims.insert("events".to_string(), json!([BACKCHANNEL_LOGOUT_EVENT])); }).await,
+            TokenRejection::InvalidLogoutToken(_)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn a_logout_token_with_a_nonce_is_rejected() {
+        assert!(matches!(
+            rejection(|claims| { claims.insert("nonce".to_string(), json!("n-0S6_WzA2Mj")); }).await,
+            TokenRejection::InvalidLogoutToken(_)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn a_logout_token_naming_neither_session_nor_user_is_rejected() {
+        assert!(matches!(
+            rejection(|claims| {
+                claims.remove("sid");
+                claims.remove("sub");
+            })
+            .await,
+            TokenRejection::InvalidLogoutToken(_)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn a_logout_token_from_another_issuer_or_for_another_client_is_rejected() {
+        assert!(matches!(
+            rejection(|claims| { claims.insert("iss".to_string(), json!("https://evil.test/realms/myrealm")); }).await,
+            TokenRejection::UnknownIssuer(_)
+        ));
+        assert!(matches!(
+            rejection(|claims| { claims.insert("aud".to_string(), json!("other-app")); }).await,
+            TokenRejection::InvalidAudience
+        ));
+    }
+
+    // These run against the database at DATABASE_URL, inside a transaction that is rolled back:
+    // cargo test -- --ignored
+
+    fn claims(sid: Option<&str>, iat: u64) -> Claims {
+        serde_json::from_value(json!({
+            "exp": 2_000_000_000usize,
+            "iat": iat,
+            "iss": ISSUER,
+            "sub": "user-1",
+            "sid": sid,
+        }))
+        .unwrap()
+    }
+
+    fn with_logout(sid: Option<&str>, subject: Option<&str>, revoked_at: u64, test: impl FnOnce(&mut PgConnection)) {
+        let mut connection = try_establish_connection().unwrap();
+        connection.test_transaction::<_, diesel::result::Error, _>(|connection| {
+            diesel::insert_into(logout_revocations::table)
+                .values(&NewLogoutRevocation {
+                    issuer: ISSUER.to_string(),
+                    sid: sid.map(str::to_string),
+                    subject: subject.map(str::to_string),
+                    revoked_at: UNIX_EPOCH + Duration::from_secs(revoked_at),
+                    expires_at: SystemTime::now() + Duration::from_secs(60),
+                })
+                .execute(connection)?;
+            test(connection);
+            Ok(())
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn a_session_logout_refuses_tokens_of_that_session_issued_before_it() {
+        with_logout(Some("sid-1"), None, 1_700_000_100, |connection| {
+            assert!(matches!(
+                check_not_logged_out(connection, &claims(Some("sid-1"), 1_700_000_000)),
+                Err(TokenRejection::LoggedOut)
+            ));
+            assert!(check_not_logged_out(connection, &claims(Some("sid-1"), 1_700_000_200)).is_ok());
+            assert!(check_not_logged_out(connection, &claims(Some("sid-2"), 1_700_000_000)).is_ok());
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn a_user_logout_refuses_tokens_of_that_user_issued_before_it() {
+        with_logout(None, Some("user-1"), 1_700_000_100, |connection| {
+            assert!(matches!(
+                check_not_logged_out(connection, &claims(None, 1_700_000_000)),
+                Err(TokenRejection::LoggedOut)
+            ));
+            assert!(matches!(
+                check_not_logged_out(connection, &claims(Some("sid-2"), 1_700_000_000)),
+                Err(TokenRejection::LoggedOut)
+            ));
+            assert!(check_not_logged_out(connection, &claims(None, 1_700_000_200)).is_ok());
+        });
+    }
+}
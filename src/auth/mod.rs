@@ -1,9 +1,33 @@
-use actix_web::{web, HttpRequest};
+use actix_web::HttpRequest;
+use actix_web::http::header::AUTHORIZATION;
 use actix_web::HttpMessage; // Import HttpMessage trait for extensions_mut()
-use log::{info, warn, error};
+use std::time::Instant;
+
+use tracing::field::Empty;
+use tracing::{info, info_span, warn, Instrument, Span};
 pub mod processes; // Make processes module public
+pub mod account_status;
+pub mod account_token;
 pub mod keycloak_config;
+pub mod jwks_cache;
+pub mod validation_policy;
+pub mod guards;
+pub mod permissions;
+pub mod provider;
+pub mod introspection;
+pub mod issuers;
+pub mod local_issuer;
+pub mod login_throttle;
+pub mod logout;
+pub mod oidc_login;
+pub mod password_policy;
+pub mod personal_access_token;
+pub mod session;
 use crate::auth::processes::Claims;
+use crate::auth::introspection::TokenMode;
+use crate::auth::oidc_login::SESSION_COOKIE;
+use crate::auth::provider::{ProviderRegistry, ProviderState};
+use crate::auth::validation_policy::TokenRejection;
 
 #[derive(Clone, Debug)]
 pub struct KeycloakClientConfig {
@@ -12,29 +36,166 @@ pub struct KeycloakClientConfig {
     pub client_id: String,
 }
 
-pub async fn process_token(request: &HttpRequest, jwks_uri: web::Data<String>) -> Result<Claims, String> {
+/// Authenticates a request from its `Authorization: Bearer` token, its `Authorization: Token`
+/// personal access token, or from its session cookie when there is no `Authorization` header.
+///
+/// The check runs in a `token_check` span with the caller's `sub`, or the reason of the rejection,
+/// and its latency; the `sub` is also recorded on the `request` span.
+///
+/// A JWT is routed to the provider of the issuer named in its `iss` claim; an opaque token is
+/// offered to every issuer that introspects tokens. Depending on that issuer's token mode, the
+/// token is verified against the JWKS, introspected, or verified locally with introspection as a
+/// fallback for tokens the JWKS cannot vouch for.
+///
+/// # Arguments
+/// * request (&HttpRequest): the incoming request; the claims are stored in its extensions
+/// * registry (&ProviderRegistry): the trusted issuers
+///
+/// # Returns
+/// * (Result<Claims, TokenRejection>): the caller's claims, or the reason the token was rejected
+pub async fn process_token(request: &HttpRequest, registry: &ProviderRegistry) -> Result<Claims, TokenRejection> {
+    let span = info_span!("token_check", sub = Empty, rejection = Empty, latency_ms = Empty);
+    let started = Instant::now();
+    let result = authenticate(request, registry).instrument(span.clone()).await;
+    span.record("latency_ms", started.elapsed().as_micros() as f64 / 1000.0);
+    match &result {
+        Ok(claims) => {
+            span.record("sub", claims.sub.as_str());
+            Span::current().record("sub", claims.sub.as_str());
+        },
+        Err(rejection) => {
+            span.record("rejection", rejection.reason());
+        }
+    }
+    result
+}
+
+async fn authenticate(request: &HttpRequest, registry: &ProviderRegistry) -> Result<Claims, TokenRejection> {
     info!("Attempting to process token in auth::mod.rs");
-    let jwks_uri_str: &str = &jwks_uri;
-
-    match processes::extract_header_token(request) {
-        Ok(token) => {
-            info!("Authorization header token extracted successfully.");
-            match processes::check_password(token, jwks_uri_str).await {
-                Ok(claims) => {
-                    info!("Token validation successful. User ID: {}", claims.sub);
-                    // Insert claims into the request extensions for later use by route handlers
-                    request.extensions_mut().insert(claims.clone());
-                    Ok(claims)
-                },
-                Err(message) => {
-                    warn!("Token validation failed: {}", message);
-                    Err(message)
-                }
+
+    if let Some(token) = personal_access_token::extract_token(request) {
+        return match personal_access_token::authenticate(&token) {
+            Ok(claims) => {
+                request.extensions_mut().insert(claims.clone());
+                Ok(claims)
+            },
+            Err(rejection) => {
+                warn!(reason = rejection.reason(), detail = %rejection, "Personal access token rejected");
+                Err(rejection)
             }
-        },
+        };
+    }
+
+    let token = match processes::extract_header_token(request) {
+        Ok(token) => token,
         Err(message) => {
+            if request.headers().get(AUTHORIZATION).is_none() {
+                if let Some(cookie) = request.cookie(SESSION_COOKIE) {
+                    return process_session(request, cookie.value(), registry).await;
+                }
+            }
             warn!("Token extraction failed: {}", message);
-            Err(message.to_string())
+            return Err(TokenRejection::MissingToken(message.to_string()));
+        }
+    };
+    info!("Authorization header token extracted successfully.");
+
+    let result = match processes::unverified_issuer(&token) {
+        Some(iss) => match registry.find_by_issuer(&iss) {
+            Some(provider) => match provider.get() {
+                Some(state) => verify_token(&token, state).await,
+                None => Err(TokenRejection::IssuerUnavailable(provider.issuer.name.clone())),
+            },
+            None => Err(TokenRejection::UnknownIssuer(iss)),
+        },
+        None => introspect_opaque(&token, registry).await,
+    };
+    // A valid token is still refused once the identity provider has logged its session out, while
+    // an administrator has disabled its user, or after its user deleted their account
+    let result = result
+        .and_then(|claims| logout::ensure_not_logged_out(&claims).map(|_| claims))
+        .and_then(|claims| account_status::ensure_active(&claims).map(|_| claims));
+
+    match result {
+        Ok(claims) => {
+            info!("Token validation successful. User ID: {}", claims.sub);
+            // Insert claims into the request extensions for later use by route handlers
+            request.extensions_mut().insert(claims.clone());
+            Ok(claims)
+        },
+        Err(rejection) => {
+            processes::log_rejection(&token, &rejection);
+            Err(rejection)
         }
     }
 }
+
+async fn process_session(request: &HttpRequest, session_id: &str, registry: &ProviderRegistry) -> Result<Claims, TokenRejection> {
+    let result = session::authenticate_session(session_id, registry)
+        .await
+        .and_then(|claims| logout::ensure_not_logged_out(&claims).map(|_| claims))
+        .and_then(|claims| account_status::ensure_active(&claims).map(|_| claims));
+    match result {
+        Ok(claims) => {
+            info!("Session validation successful. User ID: {}", claims.sub);
+            request.extensions_mut().insert(claims.clone());
+            Ok(claims)
+        },
+        Err(rejection) => {
+            warn!(reason = rejection.reason(), detail = %rejection, "Session rejected");
+            Err(rejection)
+        }
+    }
+}
+
+/// Verifies a token with one issuer, according to that issuer's token mode.
+///
+/// # Arguments
+/// * token (&str): the access token
+/// * provider (&ProviderState): the issuer the token claims to come from
+///
+/// # Returns
+/// * (Result<Claims, TokenRejection>): the claims, or the reason the token was rejected
+pub async fn verify_token(token: &str, provider: &ProviderState) -> Result<Claims, TokenRejection> {
+    match provider.mode {
+        TokenMode::Jwks => processes::check_password(token, &provider.jwks_cache, &provider.policy).await,
+        TokenMode::Introspection => introspect(token, provider).await,
+        TokenMode::JwksWithIntrospectionFallback => {
+            match processes::check_password(token, &provider.jwks_cache, &provider.policy).await {
+                Err(rejection) if rejection.allows_introspection_fallback() => {
+                    info!("Token cannot be verified against the JWKS ({}); falling back to introspection.", rejection.reason());
+                    introspect(token, provider).await
+                },
+                result => result,
+            }
+        }
+    }
+}
+
+/// Opaque tokens carry no issuer, so each issuer that introspects tokens is asked in turn.
+async fn introspect_opaque(token: &str, registry: &ProviderRegistry) -> Result<Claims, TokenRejection> {
+    let mut rejection = None;
+    let mut pending = None;
+    for provider in registry.providers() {
+        match provider.get() {
+            Some(state) if state.mode == TokenMode::Jwks => {},
+            Some(state) => match introspect(token, state).await {
+                Ok(claims) => return Ok(claims),
+                Err(e) => rejection = Some(e),
+            },
+            None => pending = Some(provider.issuer.name.clone()),
+        }
+    }
+    Err(match (rejection, pending) {
+        (Some(rejection), _) => rejection,
+        (None, Some(name)) => TokenRejection::IssuerUnavailable(name),
+        (None, None) => TokenRejection::MalformedHeader("token is not a JWT and no issuer introspects tokens".to_string()),
+    })
+}
+
+async fn introspect(token: &str, provider: &ProviderState) -> Result<Claims, TokenRejection> {
+    match &provider.introspection {
+        Some(client) => client.introspect(token, &provider.policy).await,
+        None => Err(TokenRejection::IntrospectionFailed("no introspection endpoint configured".to_string())),
+    }
+}
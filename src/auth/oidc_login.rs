@@ -0,0 +1,283 @@
+use std::env;
+use std::time::{Duration, SystemTime};
+
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::cookie::{Cookie, SameSite};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::decode;
+use jsonwebtoken::DecodingKey;
+use tracing::{info, error};
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::auth::processes;
+use crate::auth::provider::{OidcProvider, ProviderState};
+use crate::auth::validation_policy::{algorithm_for_key, TokenRejection};
+use crate::middleware::request_id;
+
+/// Name of the cookie that carries the browser's session ID.
+pub const SESSION_COOKIE: &str = "todo_session";
+
+/// Name of the short-lived cookie that binds a login in progress to the browser that started it.
+pub const LOGIN_STATE_COOKIE: &str = "todo_login_state";
+
+/// How long a user has to complete the login at the identity provider.
+pub const LOGIN_STATE_TTL: Duration = Duration::from_secs(600);
+
+/// Settings for the server-side (backend-for-frontend) login, read from the environment at startup.
+///
+/// # Attributes
+/// * redirect_uri (String): the callback URL registered with the client
+/// * post_logout_redirect_uri (String): where the identity provider sends the browser after logout
+/// * scopes (String): the space-separated scopes requested at login
+/// * session_ttl (Duration): absolute lifetime of a session, however often its tokens are refreshed
+/// * secure_cookie (bool): whether cookies are marked `Secure`; true when the redirect URI uses HTTPS
+/// * logout_revocation_ttl (Duration): how long a back-channel logout keeps rejecting the tokens it covers
+#[derive(Clone, Debug)]
+pub struct OidcLoginSettings {
+    pub redirect_uri: String,
+    pub post_logout_redirect_uri: String,
+    pub scopes: String,
+    pub session_ttl: Duration,
+    pub secure_cookie: bool,
+    pub logout_revocation_ttl: Duration,
+}
+
+impl OidcLoginSettings {
+
+    /// Builds the settings from `OIDC_REDIRECT_URI`, `OIDC_POST_LOGOUT_REDIRECT_URI`, `OIDC_SCOPES`,
+    /// `SESSION_TTL_SECS` and `LOGOUT_REVOCATION_TTL_SECS`.
+    ///
+    /// # Returns
+    /// (OidcLoginSettings): the login settings
+    pub fn from_env() -> OidcLoginSettings {
+        let redirect_uri = env::var("OIDC_REDIRECT_URI")
+            .unwrap_or_else(|_| "http://localhost:8000/auth/oidc/callback".to_string());
+        let env_u64 = |name: &str, default: u64| {
+            env::var(name).ok().and_then(|value| value.parse::<u64>().ok()).unwrap_or(default)
+        };
+        OidcLoginSettings {
+            secure_cookie: redirect_uri.starts_with("https://"),
+            redirect_uri,
+            post_logout_redirect_uri: env::var("OIDC_POST_LOGOUT_REDIRECT_URI")
+                .unwrap_or_else(|_| "http://localhost:8000/login/".to_string()),
+            scopes: env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid profile email items:read items:write".to_string()),
+            session_ttl: Duration::from_secs(env_u64("SESSION_TTL_SECS", 8 * 60 * 60)),
+            // must outlive the access tokens issued before a logout
+            logout_revocation_ttl: Duration::from_secs(env_u64("LOGOUT_REVOCATION_TTL_SECS", 24 * 60 * 60)),
+        }
+    }
+}
+
+/// Builds the session cookie: HttpOnly, SameSite=Lax, and Secure when the site is served over HTTPS.
+///
+/// # Arguments
+/// * session_id (&str): the session ID, or an empty string together with `make_removal`
+/// * settings (&OidcLoginSettings): the login settings
+///
+/// # Returns
+/// (Cookie<'static>): the cookie
+pub fn session_cookie(session_id: &str, settings: &OidcLoginSettings) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, session_id.to_string())
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(settings.secure_cookie)
+        .max_age(CookieDuration::seconds(settings.session_ttl.as_secs() as i64))
+        .finish()
+}
+
+/// Builds the cookie that binds a login in progress to the browser; it is only sent to the callback.
+///
+/// # Arguments
+/// * state (&str): the `state` parameter of the login
+/// * settings (&OidcLoginSettings): the login settings
+///
+/// # Returns
+/// (Cookie<'static>): the cookie
+pub fn login_state_cookie(state: &str, settings: &OidcLoginSettings) -> Cookie<'static> {
+    Cookie::build(LOGIN_STATE_COOKIE, state.to_string())
+        .path("/auth/oidc/callback")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(settings.secure_cookie)
+        .max_age(CookieDuration::seconds(LOGIN_STATE_TTL.as_secs() as i64))
+        .finish()
+}
+
+/// Generates a random, URL-safe secret (256 bits) for session IDs, `state`, `nonce` and PKCE verifiers.
+pub fn random_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hashes a secret before it is used as a database key, so a leaked table cannot be replayed.
+pub fn hash_secret(secret: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// Derives the S256 PKCE code challenge for a verifier.
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// The token endpoint's answer to a code exchange or a refresh.
+#[derive(Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub expires_in: u64,
+    pub refresh_token: Option<String>,
+    pub id_token: Option<String>,
+}
+
+impl TokenResponse {
+
+    /// When the access token expires.
+    pub fn access_token_expires_at(&self) -> SystemTime {
+        SystemTime::now() + Duration::from_secs(self.expires_in)
+    }
+}
+
+/// Exchanges an authorization code for tokens, proving possession of the PKCE verifier.
+///
+/// # Arguments
+/// * provider (&OidcProvider): the issuer the login was started with
+/// * state (&ProviderState): its discovery document
+/// * code (&str): the authorization code from the callback
+/// * code_verifier (&str): the PKCE verifier stored when the login started
+/// * redirect_uri (&str): the callback URL, which must match the one used at login
+///
+/// # Returns
+/// * (Result<TokenResponse, String>): the tokens, or an error message
+pub async fn exchange_code(
+    provider: &OidcProvider,
+    state: &ProviderState,
+    code: &str,
+    code_verifier: &str,
+    redirect_uri: &str,
+) -> Result<TokenResponse, String> {
+    token_request(provider, state, &[
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("code_verifier", code_verifier),
+        ("redirect_uri", redirect_uri),
+    ]).await
+}
+
+/// Uses a refresh token to get a new access token.
+///
+/// # Arguments
+/// * provider (&OidcProvider): the issuer of the session
+/// * state (&ProviderState): its discovery document
+/// * refresh_token (&str): the session's refresh token
+///
+/// # Returns
+/// * (Result<TokenResponse, String>): the new tokens, or an error message
+pub async fn refresh_tokens(provider: &OidcProvider, state: &ProviderState, refresh_token: &str) -> Result<TokenResponse, String> {
+    token_request(provider, state, &[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+    ]).await
+}
+
+/// Revokes a session's refresh token at the issuer (RFC 7009), so it cannot be used after logout.
+///
+/// # Arguments
+/// * provider (&OidcProvider): the issuer of the session
+/// * state (&ProviderState): its discovery document
+/// * refresh_token (&str): the session's refresh token
+///
+/// # Returns
+/// * (Result<(), String>): an error message if the issuer has no revocation endpoint or refused the call
+pub async fn revoke_refresh_token(provider: &OidcProvider, state: &ProviderState, refresh_token: &str) -> Result<(), String> {
+    let endpoint = state
+        .openid_config
+        .revocation_endpoint
+        .as_deref()
+        .ok_or_else(|| format!("Issuer '{}' has no revocation endpoint", provider.issuer.name))?;
+    info!("Calling the revocation endpoint {}", endpoint);
+    let response = client_request(provider, endpoint, &[("token", refresh_token), ("token_type_hint", "refresh_token")])
+        .send()
+        .await
+        .map_err(|e| {
+            error!("Failed to call the revocation endpoint {}: {}", endpoint, e);
+            format!("Failed to call the revocation endpoint: {}", e)
+        })?;
+    if !response.status().is_success() {
+        let status = response.status();
+        error!("Revocation endpoint {} answered HTTP Status {}", endpoint, status);
+        return Err(format!("Revocation endpoint answered HTTP Status {}", status));
+    }
+    Ok(())
+}
+
+/// A form POST to an endpoint of the issuer, authenticated as this client: with the client secret
+/// when there is one, with the client ID in the form for public clients.
+fn client_request(provider: &OidcProvider, endpoint: &str, form: &[(&str, &str)]) -> reqwest::RequestBuilder {
+    let mut form = form.to_vec();
+    let request = request_id::propagate(reqwest::Client::new().post(endpoint));
+    let request = match &provider.issuer.client_secret {
+        Some(secret) => request.basic_auth(&provider.issuer.client_id, Some(secret)),
+        None => {
+            form.push(("client_id", &provider.issuer.client_id));
+            request
+        }
+    };
+    request.form(&form)
+}
+
+async fn token_request(provider: &OidcProvider, state: &ProviderState, form: &[(&str, &str)]) -> Result<TokenResponse, String> {
+    let endpoint = &state.openid_config.token_endpoint;
+    info!("Calling the token endpoint {} ({})", endpoint, form[0].1);
+    let response = client_request(provider, endpoint, form).send().await.map_err(|e| {
+        error!("Failed to call the token endpoint {}: {}", endpoint, e);
+        format!("Failed to call the token endpoint: {}", e)
+    })?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_else(|_| "N/A".to_string());
+        error!("Token endpoint {} answered HTTP Status {}, Body: {}", endpoint, status, text);
+        return Err(format!("Token endpoint answered HTTP Status {}", status));
+    }
+    response.json::<TokenResponse>().await.map_err(|e| {
+        error!("Failed to parse the token endpoint response: {}", e);
+        format!("Failed to parse the token response: {}", e)
+    })
+}
+
+/// Verifies the ID token returned by the code exchange: signature, issuer, audience and nonce.
+///
+/// # Arguments
+/// * provider (&OidcProvider): the issuer the login was started with
+/// * state (&ProviderState): its discovery document, JWKS and policy
+/// * id_token (&str): the ID token
+/// * nonce (&str): the nonce sent with the authorization request
+///
+/// # Returns
+/// * (Result<Map<String, Value>, TokenRejection>): the ID token claims, or the reason it was rejected
+pub async fn verify_id_token(
+    provider: &OidcProvider,
+    state: &ProviderState,
+    id_token: &str,
+    nonce: &str,
+) -> Result<Map<String, Value>, TokenRejection> {
+    let header = processes::decode_token_header(id_token)?;
+    let kid = header.kid.clone().ok_or(TokenRejection::MissingKid)?;
+    let jwk = state.jwks_cache.get_key(&kid).await.map_err(TokenRejection::UnknownKey)?;
+    let algorithm = algorithm_for_key(header.alg, &jwk)?;
+    let decoding_key = DecodingKey::from_jwk(&jwk)
+        .map_err(|e| TokenRejection::Malformed(format!("Failed to create decoding key: {}", e)))?;
+
+    // An ID token is always addressed to this client, whatever audiences access tokens may carry
+    let mut validation = state.policy.validation_for(algorithm)?;
+    validation.set_audience(&[&provider.issuer.client_id]);
+    let claims = decode::<Map<String, Value>>(id_token, &decoding_key, &validation)?.claims;
+    if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+        return Err(TokenRejection::Malformed("ID token nonce does not match the login".to_string()));
+    }
+    Ok(claims)
+}
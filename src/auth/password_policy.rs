@@ -0,0 +1,128 @@
+use std::env;
+
+/// bcrypt ignores everything after the first 72 bytes of a password.
+pub const MAX_PASSWORD_BYTES: usize = 72;
+
+/// Rules a password must follow when a local account is registered, read from the environment at startup.
+///
+/// # Attributes
+/// * min_length (usize): the fewest characters a password may have
+/// * min_character_classes (usize): how many of lowercase, uppercase, digits and symbols it must mix
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub min_character_classes: usize,
+}
+
+impl PasswordPolicy {
+
+    /// Builds the policy from `PASSWORD_MIN_LENGTH` and `PASSWORD_MIN_CHARACTER_CLASSES`.
+    ///
+    /// # Returns
+    /// (PasswordPolicy): the policy
+    pub fn from_env() -> PasswordPolicy {
+        let env_usize = |name: &str, default: usize| {
+            env::var(name).ok().and_then(|value| value.parse::<usize>().ok()).unwrap_or(default)
+        };
+        PasswordPolicy {
+            min_length: env_usize("PASSWORD_MIN_LENGTH", 10),
+            min_character_classes: env_usize("PASSWORD_MIN_CHARACTER_CLASSES", 2).min(4),
+        }
+    }
+
+    /// Checks a password against the policy.
+    ///
+    /// # Arguments
+    /// * password (&str): the password
+    /// * username (&str): the account's username, which the password must not contain
+    ///
+    /// # Returns
+    /// * (Vec<String>): what is wrong with the password; empty if it is acceptable
+    pub fn check(&self, password: &str, username: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        if password.chars().count() < self.min_length {
+            problems.push(format!("must be at least {} characters long", self.min_length));
+        }
+        if password.len() > MAX_PASSWORD_BYTES {
+            problems.push(format!("must be at most {} bytes long", MAX_PASSWORD_BYTES));
+        }
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if classes.iter().filter(|present| **present).count() < self.min_character_classes {
+            problems.push(format!(
+                "must mix at least {} of lowercase letters, uppercase letters, digits and symbols",
+                self.min_character_classes
+            ));
+        }
+        if !username.is_empty() && password.to_lowercase().contains(&username.to_lowercase()) {
+            problems.push("must not contain the username".to_string());
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min_character_classes: usize) -> PasswordPolicy {
+        PasswordPolicy { min_length: 10, min_character_classes }
+    }
+
+    #[test]
+    fn a_password_that_follows_every_rule_is_accepted() {
+        assert!(policy(2).check("correct-horse-battery", "alice").is_empty());
+        assert!(policy(4).check("Correct-Horse-42", "alice").is_empty());
+    }
+
+    #[test]
+    fn passwords_must_reach_the_minimum_length_in_characters() {
+        assert_eq!(policy(1).check("short-pw1", "alice"), vec!["must be at least 10 characters long"]);
+        assert!(policy(1).check("long-pw-12", "alice").is_empty());
+        // Ten characters, but twenty bytes
+        assert!(policy(1).check("éééééééééé", "alice").is_empty());
+    }
+
+    #[test]
+    fn passwords_must_fit_in_the_bytes_bcrypt_reads() {
+        let longest = "a1".repeat(MAX_PASSWORD_BYTES / 2);
+        assert!(policy(2).check(&longest, "alice").is_empty());
+
+        assert_eq!(
+            policy(2).check(&format!("{}b", longest), "alice"),
+            vec!["must be at most 72 bytes long"]
+        );
+        // 37 characters, 74 bytes
+        assert_eq!(policy(1).check(&"é".repeat(37), "alice"), vec!["must be at most 72 bytes long"]);
+    }
+
+    #[test]
+    fn passwords_must_mix_enough_character_classes() {
+        let problem = |classes: usize| {
+            format!("must mix at least {} of lowercase letters, uppercase letters, digits and symbols", classes)
+        };
+        assert_eq!(policy(2).check("lowercaseonly", "alice"), vec![problem(2)]);
+        assert_eq!(policy(2).check("1234567890", "alice"), vec![problem(2)]);
+        assert!(policy(2).check("lowercase42", "alice").is_empty());
+        assert_eq!(policy(3).check("lowercase42", "alice"), vec![problem(3)]);
+        assert!(policy(3).check("Lowercase42", "alice").is_empty());
+        assert_eq!(policy(4).check("Lowercase42", "alice"), vec![problem(4)]);
+        assert!(policy(4).check("Lowercase-42", "alice").is_empty());
+    }
+
+    #[test]
+    fn passwords_must_not_contain_the_username() {
+        assert_eq!(policy(2).check("my-alice-password", "alice"), vec!["must not contain the username"]);
+        assert_eq!(policy(2).check("my-ALICE-password", "Alice"), vec!["must not contain the username"]);
+        assert!(policy(2).check("my-alice-password", "").is_empty());
+    }
+
+    #[test]
+    fn every_broken_rule_is_reported() {
+        assert_eq!(policy(2).check("alice", "alice").len(), 3);
+    }
+}
@@ -0,0 +1,20 @@
+//! Names of the Keycloak roles and scopes the API checks.
+//!
+//! The scopes are expected to be set up as client scopes in Keycloak and the role as a realm
+//! role (or a client role of `KEYCLOAK_CLIENT_ID`).
+
+/// Scope needed to read a user's to-do items.
+pub const ITEMS_READ: &str = "items:read";
+
+/// Scope needed to create, edit or delete a user's to-do items.
+pub const ITEMS_WRITE: &str = "items:write";
+
+/// Scope needed to manage the caller's own account and personal access tokens. It is the standard
+/// OIDC `profile` scope, which Keycloak grants by default; personal access tokens never carry it.
+pub const PROFILE: &str = "profile";
+
+/// Scopes a personal access token may be granted; the owner must hold each one when creating it.
+pub const PERSONAL_ACCESS_TOKEN_SCOPES: [&str; 2] = [ITEMS_READ, ITEMS_WRITE];
+
+/// Role needed for the administrative endpoints.
+pub const ADMIN_ROLE: &str = "todo-admin";
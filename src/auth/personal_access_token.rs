@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::HttpRequest;
+use tracing::info;
+
+use crate::auth::oidc_login::{hash_secret, random_secret};
+use crate::auth::permissions::PERSONAL_ACCESS_TOKEN_SCOPES;
+use crate::auth::processes::Claims;
+use crate::auth::validation_policy::TokenRejection;
+use crate::models::personal_access_token::personal_access_token::PersonalAccessToken;
+use crate::models::personal_access_token::personal_access_token_utils;
+use crate::models::user::user::User;
+
+/// Prefix of every personal access token, so leaked tokens are easy to recognise and scan for.
+pub const TOKEN_PREFIX: &str = "todo_pat_";
+
+/// Why a personal access token cannot be given the scopes asked for.
+#[derive(Debug, PartialEq)]
+pub enum ScopeRefusal {
+    /// The scope is not one a personal access token can carry.
+    Unknown(String),
+    /// The caller does not hold the scope itself.
+    NotHeld(String),
+}
+
+/// Generates a new personal access token.
+///
+/// # Returns
+/// (String): the token, to be shown once and stored only as a hash
+pub fn generate_token() -> String {
+    format!("{}{}", TOKEN_PREFIX, random_secret())
+}
+
+/// Extracts a personal access token from an `Authorization: Token <pat>` header.
+///
+/// # Arguments
+/// * request (&HttpRequest): the incoming request
+///
+/// # Returns
+/// (Option<String>): the token, if the request uses the `Token` scheme
+pub fn extract_token(request: &HttpRequest) -> Option<String> {
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Token "))
+        .map(|token| token.trim().to_string())
+}
+
+/// Checks the scopes asked for a new personal access token: each must be one a token can carry, and
+/// held by the caller, so a token never grants more than its owner has.
+///
+/// # Arguments
+/// * claims (&Claims): the claims of the caller creating the token
+/// * requested (&[String]): the scopes asked for
+///
+/// # Returns
+/// * (Result<String, ScopeRefusal>): the scopes, sorted and space-separated, or the first one that cannot be granted
+pub fn grantable_scopes(claims: &Claims, requested: &[String]) -> Result<String, ScopeRefusal> {
+    for scope in requested {
+        if !PERSONAL_ACCESS_TOKEN_SCOPES.contains(&scope.as_str()) {
+            return Err(ScopeRefusal::Unknown(scope.clone()));
+        }
+        if !claims.has_scope(scope) {
+            return Err(ScopeRefusal::NotHeld(scope.clone()));
+        }
+    }
+    let mut scopes = requested.to_vec();
+    scopes.sort();
+    scopes.dedup();
+    Ok(scopes.join(" "))
+}
+
+/// Resolves a personal access token to the claims of its owner, limited to the token's scopes.
+///
+/// # Arguments
+/// * token (&str): the presented token
+///
+/// # Returns
+/// * (Result<Claims, TokenRejection>): the owner's claims, or why the token was rejected
+pub fn authenticate(token: &str) -> Result<Claims, TokenRejection> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return Err(TokenRejection::InvalidPersonalAccessToken);
+    }
+    match personal_access_token_utils::use_token(&hash_secret(token)) {
+        Ok(found) => accept(found, SystemTime::now()),
+        Err(e) => Err(TokenRejection::Malformed(e)),
+    }
+}
+
+/// Decides on the token found for a presented secret. A revoked token has been deleted, so it is
+/// not found; an expired one is already left out by the lookup and is refused here as well.
+fn accept(found: Option<(PersonalAccessToken, User)>, now: SystemTime) -> Result<Claims, TokenRejection> {
+    match found {
+        None => Err(TokenRejection::InvalidPersonalAccessToken),
+        Some((token, _)) if token.expires_at <= now => Err(TokenRejection::Expired),
+        Some((_, user)) if user.disabled_at.is_some() => Err(TokenRejection::AccountDisabled),
+        Some((token, user)) => {
+            info!("Personal access token {} of user {} accepted.", token.id, user.id);
+            Ok(claims_for(&token, &user))
+        },
+    }
+}
+
+/// The claims a personal access token stands for: its owner's identity, so it resolves to the same
+/// local user as the owner's tokens, and only the scopes granted to the token.
+fn claims_for(token: &PersonalAccessToken, user: &User) -> Claims {
+    let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as usize).unwrap_or(0);
+    Claims {
+        aud: String::new(),
+        exp: seconds(token.expires_at),
+        iat: seconds(token.created_at),
+        iss: user.issuer.clone(),
+        sub: user.subject.clone(),
+        sid: None,
+        azp: None,
+        client_id: None,
+        preferred_username: Some(user.username.clone()),
+        name: None,
+        given_name: None,
+        family_name: None,
+        email: user.email.clone(),
+        realm_access: None,
+        resource_access: HashMap::new(),
+        scope: Some(token.scopes.clone()),
+        personal_access_token: Some(token.id.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    fn caller(scope: &str) -> Claims {
+        serde_json::from_value(json!({
+            "exp": 2_000_000_000usize,
+            "iat": 1_700_000_000usize,
+            "iss": "https://keycloak.test/realms/myrealm",
+            "sub": "user-1",
+            "scope": scope,
+        }))
+        .unwrap()
+    }
+
+    fn scopes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn stored(expires_at: SystemTime) -> (PersonalAccessToken, User) {
+        let token = PersonalAccessToken {
+            id: "token-1".to_string(),
+            user_id: "local-1".to_string(),
+            name: "ci".to_string(),
+            token_hash: hash_secret("todo_pat_secret"),
+            scopes: "items:read".to_string(),
+            created_at: SystemTime::now() - Duration::from_secs(60),
+            expires_at,
+            last_used_at: None,
+        };
+        let user = User::for_test("local-1", "https://keycloak.test/realms/myrealm", "user-1");
+        (token, user)
+    }
+
+    #[test]
+    fn generated_tokens_have_the_prefix_and_are_stored_as_a_hash() {
+        let token = generate_token();
+
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert!(token.len() > TOKEN_PREFIX.len());
+        assert_ne!(token, generate_token());
+        assert_eq!(hash_secret(&token), hash_secret(&token));
+        assert_ne!(hash_secret(&token), token);
+    }
+
+    #[test]
+    fn tokens_are_read_from_the_token_scheme_only() {
+        let request = TestRequest::default().insert_header((AUTHORIZATION, "Token todo_pat_abc ")).to_http_request();
+        assert_eq!(extract_token(&request), Some("todo_pat_abc".to_string()));
+
+        let request = TestRequest::default().insert_header((AUTHORIZATION, "Bearer todo_pat_abc")).to_http_request();
+        assert_eq!(extract_token(&request), None);
+        assert_eq!(extract_token(&TestRequest::default().to_http_request()), None);
+    }
+
+    #[test]
+    fn tokens_without_the_prefix_are_rejected_before_lookup() {
+        assert!(matches!(authenticate("abc"), Err(TokenRejection::InvalidPersonalAccessToken)));
+        assert!(matches!(authenticate("todo_jwt_abc"), Err(TokenRejection::InvalidPersonalAccessToken)));
+    }
+
+    #[test]
+    fn scopes_are_limited_to_those_the_caller_holds() {
+        let claims = caller("openid profile items:read");
+
+        assert_eq!(grantable_scopes(&claims, &scopes(&["items:read", "items:read"])), Ok("items:read".to_string()));
+        assert_eq!(
+            grantable_scopes(&claims, &scopes(&["items:read", "items:write"])),
+            Err(ScopeRefusal::NotHeld("items:write".to_string()))
+        );
+        assert_eq!(
+            grantable_scopes(&claims, &scopes(&["profile"])),
+            Err(ScopeRefusal::Unknown("profile".to_string()))
+        );
+    }
+
+    #[test]
+    fn granted_scopes_are_sorted() {
+        let claims = caller("items:write items:read");
+
+        assert_eq!(
+            grantable_scopes(&claims, &scopes(&["items:write", "items:read"])),
+            Ok("items:read items:write".to_string())
+        );
+    }
+
+    #[test]
+    fn valid_tokens_carry_only_their_own_scopes() {
+        let claims = accept(Some(stored(SystemTime::now() + Duration::from_secs(60))), SystemTime::now()).unwrap();
+
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.personal_access_token.as_deref(), Some("token-1"));
+        assert!(claims.has_scope("items:read"));
+        assert!(!claims.has_scope("items:write"));
+        assert!(!claims.has_scope("profile"));
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let now = SystemTime::now();
+
+        assert!(matches!(accept(Some(stored(now)), now), Err(TokenRejection::Expired)));
+        assert!(matches!(accept(Some(stored(now - Duration::from_secs(1))), now), Err(TokenRejection::Expired)));
+    }
+
+    #[test]
+    fn revoked_tokens_are_rejected() {
+        // Revoking deletes the token, so its secret no longer finds anything
+        assert!(matches!(accept(None, SystemTime::now()), Err(TokenRejection::InvalidPersonalAccessToken)));
+    }
+
+    #[test]
+    fn tokens_of_disabled_users_are_rejected() {
+        let (token, mut user) = stored(SystemTime::now() + Duration::from_secs(60));
+        user.disabled_at = Some(SystemTime::now());
+
+        assert!(matches!(accept(Some((token, user)), SystemTime::now()), Err(TokenRejection::AccountDisabled)));
+    }
+}
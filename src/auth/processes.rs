@@ -1,16 +1,21 @@
-use std::env;
+use std::collections::HashMap;
+use std::str::FromStr;
 
 use actix_web::{FromRequest, HttpRequest, HttpResponse, Responder, web, Error};
 use actix_web::dev::Payload;
 use actix_web::error::ErrorUnauthorized;
 use actix_web::HttpMessage;
 use futures_util::future::{ready, Ready};
-use log::{info, warn, error};
-use jsonwebtoken::{decode, decode_header, DecodingKey, Validation, Algorithm};
-use jsonwebtoken::jwk::JwkSet;
-use reqwest;
+use tracing::{info, warn, error};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Header};
+use jsonwebtoken::jwk::Jwk;
 use serde::{Deserialize, Deserializer};
-use serde_json::Value;
+use serde_json::{Map, Value};
+
+use crate::auth::jwks_cache::JwksCache;
+use crate::auth::validation_policy::{algorithm_for_key, TokenRejection, TokenValidationPolicy};
 
 // Custom deserialization for the 'aud' field, which can be a string or an array of strings.
 fn deserialize_aud<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -28,20 +33,222 @@ D: Deserializer<'de>,
     }
 }
 
+/// Claims of a validated access token.
+///
+/// Only the registered claims are mandatory; profile claims are missing from service-account
+/// tokens and from users with incomplete Keycloak profiles.
 #[derive(Debug, Deserialize, Clone)]
 pub struct Claims {
-    #[serde(deserialize_with = "deserialize_aud")]
+    #[serde(default, deserialize_with = "deserialize_aud")]
     pub aud: String,
     pub exp: usize,
     pub iat: usize,
     pub iss: String,
     pub sub: String,
-    pub azp: String,
-    pub preferred_username: String,
-    pub name: String,
-    pub given_name: String,
-    pub family_name: String,
-    pub email: String,
+    #[serde(default)]
+    pub sid: Option<String>,
+    #[serde(default)]
+    pub azp: Option<String>,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub preferred_username: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub given_name: Option<String>,
+    #[serde(default)]
+    pub family_name: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub realm_access: Option<RoleSet>,
+    #[serde(default)]
+    pub resource_access: HashMap<String, RoleSet>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// The ID of the personal access token the caller authenticated with, if any; never read from a token.
+    #[serde(skip)]
+    pub personal_access_token: Option<String>,
+}
+
+/// The `roles` object Keycloak puts under `realm_access` and under each `resource_access.<client>` entry.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RoleSet {
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl Claims {
+
+    /// Checks whether the token grants a role, either as a realm role or as a client role of `client_id`.
+    ///
+    /// # Arguments
+    /// * role (&str): the role to look for
+    /// * client_id (&str): the client whose `resource_access` roles are also considered
+    ///
+    /// # Returns
+    /// (bool): true if the role was granted
+    pub fn has_role(&self, role: &str, client_id: &str) -> bool {
+        let realm_role = self
+            .realm_access
+            .as_ref()
+            .is_some_and(|access| access.roles.iter().any(|r| r == role));
+        let client_role = self
+            .resource_access
+            .get(client_id)
+            .is_some_and(|access| access.roles.iter().any(|r| r == role));
+        realm_role || client_role
+    }
+
+    /// Checks whether the space-separated `scope` claim contains a scope.
+    ///
+    /// # Arguments
+    /// * scope (&str): the scope to look for
+    ///
+    /// # Returns
+    /// (bool): true if the scope was granted
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_some_and(|granted| granted.split_whitespace().any(|s| s == scope))
+    }
+}
+
+/// Who is calling the API: a human user or a machine client using the client-credentials grant.
+#[derive(Debug, Clone)]
+pub enum Principal {
+    User {
+        iss: String,
+        sub: String,
+        username: Option<String>,
+        email: Option<String>,
+        name: Option<String>,
+        given_name: Option<String>,
+        family_name: Option<String>,
+    },
+    ServiceClient {
+        iss: String,
+        sub: String,
+        client_id: String,
+    },
+}
+
+impl Principal {
+
+    /// Classifies the caller behind a set of claims.
+    ///
+    /// Keycloak marks service-account tokens with a `client_id` claim and a
+    /// `service-account-<client>` username; anything else is treated as a human user.
+    ///
+    /// # Arguments
+    /// * claims (&Claims): the validated token claims
+    ///
+    /// # Returns
+    /// (Principal): the caller
+    pub fn from_claims(claims: &Claims) -> Principal {
+        let is_service_account = claims.client_id.is_some()
+            || claims
+                .preferred_username
+                .as_deref()
+                .is_some_and(|username| username.starts_with("service-account-"));
+        if is_service_account {
+            let client_id = claims
+                .client_id
+                .clone()
+                .or_else(|| claims.azp.clone())
+                .unwrap_or_else(|| claims.sub.clone());
+            Principal::ServiceClient { iss: claims.iss.clone(), sub: claims.sub.clone(), client_id }
+        } else {
+            Principal::User {
+                iss: claims.iss.clone(),
+                sub: claims.sub.clone(),
+                username: claims.preferred_username.clone(),
+                email: claims.email.clone(),
+                name: claims.name.clone(),
+                given_name: claims.given_name.clone(),
+                family_name: claims.family_name.clone(),
+            }
+        }
+    }
+
+    /// Returns the issuer that vouched for the caller.
+    pub fn issuer(&self) -> &str {
+        match self {
+            Principal::User { iss, .. } => iss,
+            Principal::ServiceClient { iss, .. } => iss,
+        }
+    }
+
+    /// Returns the token subject; it is only unique together with the issuer.
+    pub fn subject(&self) -> &str {
+        match self {
+            Principal::User { sub, .. } => sub,
+            Principal::ServiceClient { sub, .. } => sub,
+        }
+    }
+
+    /// Returns the username stored for the caller in the local `users` table; the subject stands in
+    /// for users whose token carries no `preferred_username`.
+    pub fn username(&self) -> String {
+        self.claimed_username().unwrap_or_else(|| self.subject().to_string())
+    }
+
+    /// Returns the username the token names, if it names one.
+    pub fn claimed_username(&self) -> Option<String> {
+        match self {
+            Principal::User { username, .. } => username.clone(),
+            Principal::ServiceClient { client_id, .. } => Some(format!("service-account-{}", client_id)),
+        }
+    }
+
+    /// Returns the caller's email address, if it has one.
+    pub fn email(&self) -> Option<&str> {
+        match self {
+            Principal::User { email, .. } => email.as_deref(),
+            Principal::ServiceClient { .. } => None,
+        }
+    }
+
+    /// Returns the caller's display name (`name` claim), if the token carries one.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Principal::User { name, .. } => name.as_deref(),
+            Principal::ServiceClient { .. } => None,
+        }
+    }
+
+    /// Returns the caller's `given_name` claim, if the token carries one.
+    pub fn given_name(&self) -> Option<&str> {
+        match self {
+            Principal::User { given_name, .. } => given_name.as_deref(),
+            Principal::ServiceClient { .. } => None,
+        }
+    }
+
+    /// Returns the caller's `family_name` claim, if the token carries one.
+    pub fn family_name(&self) -> Option<&str> {
+        match self {
+            Principal::User { family_name, .. } => family_name.as_deref(),
+            Principal::ServiceClient { .. } => None,
+        }
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let claims = req.extensions().get::<Claims>().cloned();
+        ready(match claims {
+            Some(c) => Ok(Principal::from_claims(&c)),
+            None => {
+                warn!("Claims not found in request extensions during Principal extraction. Returning 401 Unauthorized.");
+                Err(ErrorUnauthorized("Unauthorized: Missing user claims"))
+            }
+        })
+    }
 }
 
 impl FromRequest for Claims {
@@ -60,94 +267,105 @@ impl FromRequest for Claims {
     }
 }
 
-/// Checks to see if the token matches and is valid using dynamic JWKS.
+/// Logs a rejected token once, with a stable `reason` code and the identifying header fields.
+///
+/// # Parameters
+/// * token_string (&str): The rejected token.
+/// * rejection (&TokenRejection): Why it was rejected.
+pub fn log_rejection(token_string: &str, rejection: &TokenRejection) {
+    let header = decode_header(token_string).ok();
+    let kid = header.as_ref().and_then(|h| h.kid.clone());
+    let alg = header.as_ref().map(|h| h.alg);
+    warn!(reason = rejection.reason(), kid = ?kid, alg = ?alg, detail = %rejection, "Token rejected");
+}
+
+/// Checks to see if the token matches and is valid using the cached JWKS.
 ///
 /// # Parameters
-/// * token_string (String): The JWT to be validated.
-/// * jwks_uri (&str): The URI to fetch the JSON Web Key Set from.
+/// * token_string (&str): The JWT to be validated.
+/// * jwks_cache (&JwksCache): The shared cache holding the JSON Web Key Set.
+/// * policy (&TokenValidationPolicy): The validation rules built at startup.
 ///
 /// # Returns
-/// * (Result<Claims, String>): Claims if the token is valid, an error message if not.
-pub async fn check_password(token_string: String, jwks_uri: &str) -> Result<Claims, String> {
-    info!("Attempting to check password/validate token using JWKS from: {}", jwks_uri);
-    
+/// * (Result<Claims, TokenRejection>): Claims if the token is valid, the reason it was rejected if not.
+pub async fn check_password(token_string: &str, jwks_cache: &JwksCache, policy: &TokenValidationPolicy) -> Result<Claims, TokenRejection> {
+    info!("Attempting to check password/validate token using the cached JWKS.");
     // 1. Decode the header to get the `kid` (Key ID)
-    let header = match decode_header(&token_string) {
-        Ok(h) => h,
-        Err(e) => {
-            warn!("Failed to decode JWT header: {}", e);
-            return Err(format!("Invalid JWT header: {}", e));
-        }
-    };
-    
-    let kid = match header.kid {
-        Some(k) => k,
-        None => {
-            warn!("JWT header does not contain 'kid'. Cannot identify key for validation.");
-            return Err("JWT header missing 'kid'".to_string());
-        }
-    };
+    let header = decode_token_header(token_string)?;
+    let kid = header.kid.clone().ok_or(TokenRejection::MissingKid)?;
     info!("Extracted 'kid' from JWT header: {}", kid);
-    
-    // 2. Fetch the JWKS
-    let jwks_response = match reqwest::get(jwks_uri).await {
-        Ok(response) => response,
-        Err(e) => {
-            error!("Failed to fetch JWKS from {}: {}", jwks_uri, e);
-            return Err(format!("Failed to fetch JWKS: {}", e));
-        }
-    };
-    
-    let jwks_json: JwkSet = match jwks_response.json().await {
-        Ok(json) => json,
-        Err(e) => {
-            error!("Failed to parse JWKS JSON from {}: {}", jwks_uri, e);
-            return Err(format!("Failed to parse JWKS: {}", e));
-        }
-    };
-    info!("Successfully fetched and parsed JWKS.");
-    
-    // 3. Find the correct JWK using the `kid`
-    let jwk = match jwks_json.keys.iter().find(|key| key.common.key_id.as_ref() == Some(&kid)) {
-        Some(key) => key,
-        None => {
-            warn!("No JWK found with kid '{}' in the JWKS.", kid);
-            return Err(format!("No matching JWK found for kid: {}", kid));
-        }
-    };
+
+    // 2. Find the correct JWK using the `kid`, refetching the JWKS if the key is unknown
+    let jwk = jwks_cache.get_key(&kid).await.map_err(TokenRejection::UnknownKey)?;
     info!("Found matching JWK for kid: {}", kid);
-    
+
+    verify_with_key(token_string, &header, &jwk, policy)
+}
+
+/// Verifies a token against the JWK its `kid` points at.
+///
+/// # Parameters
+/// * token_string (&str): The JWT to be validated.
+/// * header (&Header): The token's decoded header.
+/// * jwk (&Jwk): The signing key from the JWKS.
+/// * policy (&TokenValidationPolicy): The validation rules built at startup.
+///
+/// # Returns
+/// * (Result<Claims, TokenRejection>): Claims if the token is valid, the reason it was rejected if not.
+pub fn verify_with_key(token_string: &str, header: &Header, jwk: &Jwk, policy: &TokenValidationPolicy) -> Result<Claims, TokenRejection> {
+    // 3. Derive the algorithm from the key, then check it against the allow-list
+    let algorithm = algorithm_for_key(header.alg, jwk)?;
+    let validation = policy.validation_for(algorithm)?;
+
     // 4. Create a DecodingKey from the JWK
-    let decoding_key = match DecodingKey::from_jwk(jwk) {
-        Ok(key) => key,
-        Err(e) => {
-            error!("Failed to create DecodingKey from JWK: {}", e);
-            return Err(format!("Failed to create decoding key: {}", e));
-        }
+    let decoding_key = DecodingKey::from_jwk(jwk).map_err(|e| {
+        error!("Failed to create DecodingKey from JWK: {}", e);
+        TokenRejection::Malformed(format!("Failed to create decoding key: {}", e))
+    })?;
+
+    // 5. Validate the signature and the registered claims, then the policy's extra rules
+    let token_data = decode::<Map<String, Value>>(token_string, &decoding_key, &validation)?;
+    policy.check_claims(&token_data.claims)?;
+
+    serde_json::from_value::<Claims>(Value::Object(token_data.claims))
+        .map_err(|e| TokenRejection::Malformed(format!("Unexpected claims: {}", e)))
+}
+
+/// Reads the `iss` claim of a JWT without verifying it, to pick the provider that will verify it.
+///
+/// # Parameters
+/// * token_string (&str): The token from the request.
+///
+/// # Returns
+/// * (Option<String>): The claimed issuer, or `None` for opaque tokens and tokens without `iss`.
+pub fn unverified_issuer(token_string: &str) -> Option<String> {
+    let mut segments = token_string.split('.');
+    let payload = match (segments.next(), segments.next(), segments.next(), segments.next()) {
+        (Some(_), Some(payload), Some(_), None) => payload,
+        _ => return None,
     };
-    info!("Successfully created DecodingKey from JWK.");
-    
-    // 5. Validate the token
-    let mut validation = Validation::new(Algorithm::RS256); // Keycloak typically uses RS256
-    // You might need to set `validation.validate_aud = false;` or specify expected audiences if `aud` claim is not a single string or if there are multiple valid audiences.
-    // validation.set_issuer(&["http://localhost:8080/realms/myrealm"]); // Optional: Validate issuer
-    
-    validation.required_spec_claims.retain(|claim| claim != "exp"); // Remove "exp" from required claims if not needed
-    validation.validate_aud = false; // Temporarily disable audience validation for debugging
-    let issuer = env::var("OIDC_ISSUER")
-    .unwrap_or_else(|_| "http://localhost:8080/realms/myrealm".to_string());
-    
-    validation.set_issuer(&[&issuer]);
-    match decode::<Claims>(&token_string, &decoding_key, &validation) {
-        Ok(token_data) => {
-            info!("Token validation successful. Claims: {:?}", token_data.claims);
-            Ok(token_data.claims) // Return the Claims struct
-        },
-        Err(e) => {
-            warn!("Token validation failed: {}", e);
-            Err(format!("Token validation failed: {}", e))
-        },
-    }
+    URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Map<String, Value>>(&bytes).ok())
+        .and_then(|claims| claims.get("iss").and_then(Value::as_str).map(str::to_string))
+}
+
+/// Decodes the token header, reporting `alg: none` and other unknown algorithms as disallowed
+/// rather than as a malformed header.
+pub fn decode_token_header(token_string: &str) -> Result<Header, TokenRejection> {
+    decode_header(token_string).map_err(|e| {
+        let raw_alg = token_string
+            .split('.')
+            .next()
+            .and_then(|segment| URL_SAFE_NO_PAD.decode(segment.trim_end_matches('=')).ok())
+            .and_then(|bytes| serde_json::from_slice::<Map<String, Value>>(&bytes).ok())
+            .and_then(|raw| raw.get("alg").and_then(Value::as_str).map(str::to_string));
+        match raw_alg {
+            Some(alg) if Algorithm::from_str(&alg).is_err() => TokenRejection::DisallowedAlgorithm(alg),
+            _ => TokenRejection::MalformedHeader(e.to_string()),
+        }
+    })
 }
 
 /// Extracts the header from the request.
@@ -184,3 +402,151 @@ pub fn extract_header_token(request: &HttpRequest) -> Result<String, &'static st
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use jsonwebtoken::{encode, EncodingKey};
+    use serde_json::json;
+
+    // Keys generated locally with `openssl genpkey` for these tests only.
+    const RSA_PRIVATE_KEY: &str = include_str!("test_keys/rsa.pem");
+    const EC_PRIVATE_KEY: &str = include_str!("test_keys/ec.pem");
+    const ED_PRIVATE_KEY: &str = include_str!("test_keys/ed.pem");
+
+    fn rsa_jwk(alg: Option<&str>) -> Jwk {
+        let mut jwk = json!({
+            "kty": "RSA",
+            "kid": "rsa-key",
+            "use": "sig",
+            "n": "xeQtrdGFJdRDbXZ0MOxTRM1iZ7_aTN5kVhypSzHj5uBoc44EjWF3_8nwalD7brGbvpmXYefPKa6fmAEXlHnOX57mQG_zrh4UNd8vbUHB9tdCQ55iRdYv9fnMd5VnlAkUqKkVmYt3_kv_kTx1GI7md3TdxNndwC0z1v_cG7Ozt-U3-lZGYYR7v-bbu05QJtGwfkZPGW9Ohen2O_U974IefElEugRzqryzxQuu5gTBLLy8PL9OLV400ecFVtJ1__RAa7sdn2eV1cYHJYqJhXCHA7pGOohbTle4lIKR1AykEyggLw--01-XxTS4LYe4rlepNIfong1YUJYREVL2B2kCTw",
+            "e": "AQAB",
+        });
+        if let Some(alg) = alg {
+            jwk["alg"] = json!(alg);
+        }
+        serde_json::from_value(jwk).unwrap()
+    }
+
+    fn ec_jwk() -> Jwk {
+        serde_json::from_value(json!({
+            "kty": "EC",
+            "kid": "ec-key",
+            "alg": "ES256",
+            "crv": "P-256",
+            "x": "Y7LfygUGZmHanyoXrNtZ6HzQ6CeBfhUwhcuPVnWYktg",
+            "y": "pDgmIL3SNuzk9WuiyCjJISQBBFgGur2l1tnEyKPlkHU",
+        }))
+        .unwrap()
+    }
+
+    fn ed_jwk() -> Jwk {
+        serde_json::from_value(json!({
+            "kty": "OKP",
+            "kid": "ed-key",
+            "crv": "Ed25519",
+            "x": "pnD1cvKFC-uEMGg0jh-015IZ-a54WOHwupD-j7i37mw",
+        }))
+        .unwrap()
+    }
+
+    fn policy(algorithms: Vec<Algorithm>) -> TokenValidationPolicy {
+        TokenValidationPolicy {
+            issuer: "https://keycloak.test/realms/myrealm".to_string(),
+            required_claims: vec!["exp".to_string(), "iss".to_string(), "sub".to_string()],
+            audiences: vec!["todo-app".to_string()],
+            allowed_azp: vec!["todo-app".to_string()],
+            leeway: 0,
+            algorithms,
+        }
+    }
+
+    fn default_policy() -> TokenValidationPolicy {
+        policy(vec![Algorithm::RS256, Algorithm::PS256, Algorithm::ES256, Algorithm::EdDSA])
+    }
+
+    fn sign(alg: Algorithm, kid: &str, key: &EncodingKey) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let claims = json!({
+            "iss": "https://keycloak.test/realms/myrealm",
+            "sub": "user-1",
+            "aud": "todo-app",
+            "azp": "todo-app",
+            "iat": now,
+            "exp": now + 300,
+        });
+        let mut header = Header::new(alg);
+        header.kid = Some(kid.to_string());
+        encode(&header, &claims, key).unwrap()
+    }
+
+    fn verify(token: &str, jwk: &Jwk, policy: &TokenValidationPolicy) -> Result<Claims, TokenRejection> {
+        let header = decode_token_header(token)?;
+        verify_with_key(token, &header, jwk, policy)
+    }
+
+    #[test]
+    fn rsa_key_verifies_rs256_and_ps256() {
+        let key = EncodingKey::from_rsa_pem(RSA_PRIVATE_KEY.as_bytes()).unwrap();
+        for alg in [Algorithm::RS256, Algorithm::PS256] {
+            let claims = verify(&sign(alg, "rsa-key", &key), &rsa_jwk(None), &default_policy()).unwrap();
+            assert_eq!(claims.sub, "user-1");
+        }
+    }
+
+    #[test]
+    fn rsa_key_with_alg_rejects_other_rsa_algorithms() {
+        let key = EncodingKey::from_rsa_pem(RSA_PRIVATE_KEY.as_bytes()).unwrap();
+        let token = sign(Algorithm::PS256, "rsa-key", &key);
+        let rejection = verify(&token, &rsa_jwk(Some("RS256")), &default_policy()).unwrap_err();
+        assert_eq!(rejection.reason(), "algorithm_mismatch");
+    }
+
+    #[test]
+    fn ec_key_verifies_es256() {
+        let key = EncodingKey::from_ec_pem(EC_PRIVATE_KEY.as_bytes()).unwrap();
+        let claims = verify(&sign(Algorithm::ES256, "ec-key", &key), &ec_jwk(), &default_policy()).unwrap();
+        assert_eq!(claims.sub, "user-1");
+    }
+
+    #[test]
+    fn ec_key_rejects_rsa_signed_token() {
+        let key = EncodingKey::from_rsa_pem(RSA_PRIVATE_KEY.as_bytes()).unwrap();
+        let token = sign(Algorithm::RS256, "ec-key", &key);
+        let rejection = verify(&token, &ec_jwk(), &default_policy()).unwrap_err();
+        assert_eq!(rejection.reason(), "algorithm_mismatch");
+    }
+
+    #[test]
+    fn ed25519_key_verifies_eddsa() {
+        let key = EncodingKey::from_ed_pem(ED_PRIVATE_KEY.as_bytes()).unwrap();
+        let claims = verify(&sign(Algorithm::EdDSA, "ed-key", &key), &ed_jwk(), &default_policy()).unwrap();
+        assert_eq!(claims.sub, "user-1");
+    }
+
+    #[test]
+    fn hmac_token_is_rejected_for_public_key() {
+        // The classic confusion attack: sign with HS256 using the public key bytes as the secret.
+        let key = EncodingKey::from_secret(b"xeQtrdGFJdRDbXZ0MOxTRM1iZ7_aTN5kVhypSzHj5uBoc44EjWF3");
+        let token = sign(Algorithm::HS256, "rsa-key", &key);
+        let rejection = verify(&token, &rsa_jwk(None), &default_policy()).unwrap_err();
+        assert_eq!(rejection.reason(), "algorithm_mismatch");
+    }
+
+    #[test]
+    fn algorithm_outside_allow_list_is_rejected() {
+        let key = EncodingKey::from_ec_pem(EC_PRIVATE_KEY.as_bytes()).unwrap();
+        let token = sign(Algorithm::ES256, "ec-key", &key);
+        let rejection = verify(&token, &ec_jwk(), &policy(vec![Algorithm::RS256])).unwrap_err();
+        assert_eq!(rejection.reason(), "disallowed_algorithm");
+    }
+
+    #[test]
+    fn alg_none_is_rejected() {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"none","kid":"rsa-key"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(br#"{"sub":"user-1"}"#);
+        let rejection = decode_token_header(&format!("{}.{}.", header, payload)).unwrap_err();
+        assert_eq!(rejection.reason(), "disallowed_algorithm");
+    }
+}
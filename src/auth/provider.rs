@@ -0,0 +1,285 @@
+use std::env;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::ServerHandle;
+use actix_web::web;
+use tracing::{info, warn, error};
+
+use crate::auth::introspection::{IntrospectionClient, IntrospectionSettings, TokenMode};
+use crate::auth::issuers::IssuerConfig;
+use crate::auth::jwks_cache::{JwksCache, JwksCacheConfig};
+use crate::auth::keycloak_config::{fetch_keycloak_openid_config, OpenIdConfig};
+use crate::auth::validation_policy::TokenValidationPolicy;
+
+/// Everything token validation needs from Keycloak, available once discovery has succeeded.
+///
+/// # Attributes
+/// * openid_config (OpenIdConfig): the realm's discovery document
+/// * jwks_cache (Arc<JwksCache>): the realm's cached signing keys
+/// * policy (TokenValidationPolicy): the validation rules, bound to the discovered issuer
+/// * mode (TokenMode): whether tokens are checked against the JWKS, introspected, or both
+/// * introspection (Option<IntrospectionClient>): the introspection client, unless the mode is `Jwks`
+pub struct ProviderState {
+    pub openid_config: OpenIdConfig,
+    pub jwks_cache: Arc<JwksCache>,
+    pub policy: TokenValidationPolicy,
+    pub mode: TokenMode,
+    pub introspection: Option<IntrospectionClient>,
+}
+
+/// Settings parsed at startup for one issuer and applied once its discovery has succeeded.
+///
+/// # Attributes
+/// * policy (TokenValidationPolicy): the validation rules, without an issuer yet
+/// * introspection (IntrospectionSettings): token mode and client credentials
+/// * retry (DiscoveryRetry): backoff and deadline for discovery
+#[derive(Clone, Debug)]
+pub struct ProviderSettings {
+    pub policy: TokenValidationPolicy,
+    pub introspection: IntrospectionSettings,
+    pub retry: DiscoveryRetry,
+}
+
+/// Backoff settings for the startup discovery, read from the environment.
+///
+/// # Attributes
+/// * initial_delay (Duration): wait before the first retry
+/// * max_delay (Duration): upper bound for the exponential backoff
+/// * deadline (Option<Duration>): stop the server after this long if no issuer is up; `None` retries forever
+#[derive(Clone, Debug)]
+pub struct DiscoveryRetry {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub deadline: Option<Duration>,
+}
+
+impl DiscoveryRetry {
+
+    /// Builds the retry settings from `OIDC_DISCOVERY_INITIAL_DELAY_MS`, `OIDC_DISCOVERY_MAX_DELAY_SECS`
+    /// and `OIDC_DISCOVERY_DEADLINE_SECS` (`0` disables the deadline).
+    ///
+    /// # Returns
+    /// (DiscoveryRetry): the retry settings
+    pub fn from_env() -> DiscoveryRetry {
+        let env_u64 = |name: &str, default: u64| {
+            env::var(name).ok().and_then(|value| value.parse::<u64>().ok()).unwrap_or(default)
+        };
+        let deadline = env_u64("OIDC_DISCOVERY_DEADLINE_SECS", 300);
+        DiscoveryRetry {
+            initial_delay: Duration::from_millis(env_u64("OIDC_DISCOVERY_INITIAL_DELAY_MS", 500)),
+            max_delay: Duration::from_secs(env_u64("OIDC_DISCOVERY_MAX_DELAY_SECS", 30)),
+            deadline: if deadline == 0 { None } else { Some(Duration::from_secs(deadline)) },
+        }
+    }
+}
+
+/// One trusted issuer and, once its discovery has succeeded, everything needed to validate its tokens.
+///
+/// The HTTP server starts before Keycloak has answered; until discovery succeeds the provider is
+/// "degraded", tokens from this issuer answer 503 and the readiness endpoint reports why.
+pub struct OidcProvider {
+    pub issuer: IssuerConfig,
+    state: OnceLock<ProviderState>,
+    last_error: RwLock<Option<String>>,
+}
+
+impl OidcProvider {
+
+    /// Creates a provider that has not been discovered yet.
+    ///
+    /// # Arguments
+    /// * issuer (IssuerConfig): the issuer this provider trusts
+    ///
+    /// # Returns
+    /// (OidcProvider): a degraded provider
+    pub fn new(issuer: IssuerConfig) -> OidcProvider {
+        OidcProvider {
+            issuer,
+            state: OnceLock::new(),
+            last_error: RwLock::new(None),
+        }
+    }
+
+    /// Creates a provider that needs no discovery, such as the one for tokens this application signs.
+    ///
+    /// # Arguments
+    /// * issuer (IssuerConfig): the issuer this provider trusts
+    /// * state (ProviderState): everything needed to validate its tokens
+    ///
+    /// # Returns
+    /// (OidcProvider): a ready provider
+    pub fn with_state(issuer: IssuerConfig, state: ProviderState) -> OidcProvider {
+        let provider = OidcProvider::new(issuer);
+        let _ = provider.state.set(state);
+        provider
+    }
+
+    /// Returns the discovered realm, or `None` while the provider is degraded.
+    pub fn get(&self) -> Option<&ProviderState> {
+        self.state.get()
+    }
+
+    /// Returns the error of the last failed discovery attempt, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Whether a token's `iss` names this issuer. Before discovery the configured URL is used, after it
+    /// the issuer advertised by the discovery document.
+    pub fn accepts_issuer(&self, iss: &str) -> bool {
+        match self.get() {
+            Some(state) => state.openid_config.issuer == iss,
+            None => self.issuer.issuer_url == iss.trim_end_matches('/'),
+        }
+    }
+
+    fn initialise(&self, openid_config: OpenIdConfig, settings: &ProviderSettings) {
+        let jwks_cache = Arc::new(JwksCache::new(openid_config.jwks_uri.clone(), JwksCacheConfig::from_env()));
+        if settings.introspection.mode != TokenMode::Introspection {
+            JwksCache::spawn_refresh_task(jwks_cache.clone());
+        }
+        let introspection = match (&settings.introspection.client_secret, &openid_config.introspection_endpoint) {
+            (Some(secret), Some(endpoint)) if settings.introspection.mode != TokenMode::Jwks => Some(IntrospectionClient::new(
+                endpoint.clone(),
+                settings.introspection.client_id.clone(),
+                secret.clone(),
+            )),
+            _ => None,
+        };
+        if settings.introspection.mode != TokenMode::Jwks && introspection.is_none() {
+            error!("Token introspection is enabled for issuer '{}' but the realm advertises no introspection endpoint.", self.issuer.name);
+        }
+        let state = ProviderState {
+            policy: settings.policy.for_issuer(&openid_config),
+            openid_config,
+            jwks_cache,
+            mode: settings.introspection.mode,
+            introspection,
+        };
+        if self.state.set(state).is_err() {
+            warn!("OIDC provider '{}' was already initialised; ignoring the new discovery result.", self.issuer.name);
+        }
+        *self.last_error.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+}
+
+/// Every trusted issuer, stored in the application data and shared by all workers.
+///
+/// Tokens are routed to a provider by their `iss` claim, so each realm keeps its own discovery
+/// document, JWKS cache and audience rules. Tokens signed by this application's own login are
+/// routed to the local provider.
+pub struct ProviderRegistry {
+    providers: Vec<OidcProvider>,
+    local: Option<OidcProvider>,
+}
+
+impl ProviderRegistry {
+
+    /// Creates a registry of degraded providers, one per issuer.
+    ///
+    /// # Arguments
+    /// * issuers (Vec<IssuerConfig>): the trusted issuers
+    /// * local (Option<OidcProvider>): the provider for tokens issued by local login, if it is enabled
+    ///
+    /// # Returns
+    /// (ProviderRegistry): the registry
+    pub fn new(issuers: Vec<IssuerConfig>, local: Option<OidcProvider>) -> ProviderRegistry {
+        ProviderRegistry {
+            providers: issuers.into_iter().map(OidcProvider::new).collect(),
+            local,
+        }
+    }
+
+    /// Returns every OIDC provider, in configuration order.
+    pub fn providers(&self) -> &[OidcProvider] {
+        &self.providers
+    }
+
+    /// Returns the provider for tokens issued by local login, if it is enabled.
+    pub fn local(&self) -> Option<&OidcProvider> {
+        self.local.as_ref()
+    }
+
+    /// Finds the provider for a token's `iss` claim.
+    ///
+    /// # Arguments
+    /// * iss (&str): the issuer named by the token
+    ///
+    /// # Returns
+    /// (Option<&OidcProvider>): the provider, or `None` if the issuer is not trusted
+    pub fn find_by_issuer(&self, iss: &str) -> Option<&OidcProvider> {
+        self.providers.iter().chain(self.local.iter()).find(|provider| provider.accepts_issuer(iss))
+    }
+
+    /// Finds a provider by its configured name.
+    ///
+    /// # Arguments
+    /// * name (&str): the issuer name from `OIDC_ISSUERS`
+    ///
+    /// # Returns
+    /// (Option<&OidcProvider>): the provider, or `None` if no issuer has that name
+    pub fn find_by_name(&self, name: &str) -> Option<&OidcProvider> {
+        self.providers.iter().find(|provider| provider.issuer.name == name)
+    }
+
+    /// Returns the provider used when a login does not name an issuer, or `None` if only local
+    /// login is configured.
+    pub fn default_provider(&self) -> Option<&OidcProvider> {
+        self.providers.first()
+    }
+
+    /// Whether at least one issuer can validate tokens.
+    pub fn any_ready(&self) -> bool {
+        self.local.is_some() || self.providers.iter().any(|provider| provider.get().is_some())
+    }
+
+    /// Runs discovery for every issuer in the background, retrying each with exponential backoff
+    /// until it succeeds.
+    ///
+    /// Once the deadline has passed the server is stopped if no issuer could be discovered at all, so
+    /// the orchestrator can restart the container. If some issuers are up, the others keep retrying
+    /// so one unreachable realm does not take the other tenants down.
+    ///
+    /// # Arguments
+    /// * registry (web::Data<ProviderRegistry>): the shared registry to initialise
+    /// * settings (Vec<ProviderSettings>): policy, introspection and retry settings, one per issuer
+    /// * server (ServerHandle): handle used to stop the server when the deadline is exceeded
+    pub fn spawn_discovery(registry: web::Data<ProviderRegistry>, settings: Vec<ProviderSettings>, server: ServerHandle) {
+        for (index, settings) in settings.into_iter().enumerate() {
+            let registry = registry.clone();
+            let server = server.clone();
+            actix_rt::spawn(async move {
+                let provider = &registry.providers[index];
+                let retry = &settings.retry;
+                let started = Instant::now();
+                let mut delay = retry.initial_delay;
+                let mut attempt: u32 = 1;
+                loop {
+                    match fetch_keycloak_openid_config(&provider.issuer.issuer_url).await {
+                        Ok(openid_config) => {
+                            provider.initialise(openid_config, &settings);
+                            info!("OIDC discovery for issuer '{}' succeeded after {} attempt(s).", provider.issuer.name, attempt);
+                            return;
+                        },
+                        Err(e) => {
+                            warn!("OIDC discovery attempt {} for issuer '{}' failed, its tokens get 503: {}", attempt, provider.issuer.name, e);
+                            *provider.last_error.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(e);
+                        }
+                    }
+
+                    if let Some(deadline) = retry.deadline {
+                        if started.elapsed() + delay > deadline && !registry.any_ready() {
+                            error!("OIDC discovery did not succeed for any issuer within {:?}. Stopping the server.", deadline);
+                            server.stop(true).await;
+                            return;
+                        }
+                    }
+                    actix_rt::time::sleep(delay).await;
+                    delay = (delay * 2).min(retry.max_delay);
+                    attempt += 1;
+                }
+            });
+        }
+    }
+}
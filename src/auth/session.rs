@@ -0,0 +1,69 @@
+use std::time::{Duration, SystemTime};
+
+use tracing::{info, warn};
+
+use crate::auth::oidc_login::{hash_secret, refresh_tokens};
+use crate::auth::processes::Claims;
+use crate::auth::provider::ProviderRegistry;
+use crate::auth::validation_policy::TokenRejection;
+use crate::models::session::session_utils;
+
+/// Access tokens this close to expiry are refreshed before the request is served.
+const REFRESH_AHEAD: Duration = Duration::from_secs(30);
+
+/// Authenticates a browser request from its session cookie.
+///
+/// The session's access token is refreshed with its refresh token when it is about to expire, and
+/// then validated exactly like a Bearer token, so sessions and tokens get the same claims.
+///
+/// # Arguments
+/// * session_id (&str): the value of the session cookie
+/// * registry (&ProviderRegistry): the trusted issuers
+///
+/// # Returns
+/// * (Result<Claims, TokenRejection>): the caller's claims, or the reason the session was rejected
+pub async fn authenticate_session(session_id: &str, registry: &ProviderRegistry) -> Result<Claims, TokenRejection> {
+    let id_hash = hash_secret(session_id);
+    let session = session_utils::find_session(&id_hash)
+        .map_err(TokenRejection::Malformed)?
+        .ok_or_else(|| TokenRejection::InvalidSession("unknown session".to_string()))?;
+    if session.expires_at <= SystemTime::now() {
+        session_utils::delete_session(&id_hash).map_err(TokenRejection::Malformed)?;
+        return Err(TokenRejection::InvalidSession("session has expired".to_string()));
+    }
+
+    let provider = registry
+        .find_by_name(&session.issuer_name)
+        .ok_or_else(|| TokenRejection::UnknownIssuer(session.issuer_name.clone()))?;
+    let state = provider
+        .get()
+        .ok_or_else(|| TokenRejection::IssuerUnavailable(provider.issuer.name.clone()))?;
+
+    let mut access_token = session.access_token.clone();
+    if session.access_token_expires_at <= SystemTime::now() + REFRESH_AHEAD {
+        let refresh_token = session
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| TokenRejection::InvalidSession("access token expired and no refresh token".to_string()))?;
+        info!("Refreshing the access token of a session for user {}", session.user_id);
+        let tokens = match refresh_tokens(provider, state, refresh_token).await {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                // The identity provider no longer honours the refresh token, so the session is over
+                warn!("Session refresh failed for user {}: {}", session.user_id, e);
+                session_utils::delete_session(&id_hash).map_err(TokenRejection::Malformed)?;
+                return Err(TokenRejection::InvalidSession("session could not be refreshed".to_string()));
+            }
+        };
+        session_utils::update_session_tokens(
+            &id_hash,
+            &tokens.access_token,
+            tokens.access_token_expires_at(),
+            tokens.refresh_token.as_deref(),
+        )
+        .map_err(TokenRejection::Malformed)?;
+        access_token = tokens.access_token;
+    }
+
+    super::verify_token(&access_token, state).await
+}
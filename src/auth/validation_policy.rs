@@ -0,0 +1,460 @@
+use std::fmt;
+use std::str::FromStr;
+
+use jsonwebtoken::{Algorithm, Validation};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, PublicKeyUse};
+use tracing::{info, warn};
+use serde_json::{Map, Value};
+
+use crate::auth::issuers::IssuerConfig;
+use crate::auth::keycloak_config::OpenIdConfig;
+
+/// Rules every access token must satisfy, built once at startup.
+///
+/// # Attributes
+/// * issuer (String): the expected `iss`, taken from the OIDC discovery document
+/// * required_claims (Vec<String>): claims that must be present in the token
+/// * audiences (Vec<String>): accepted `aud` values; empty disables the audience check
+/// * allowed_azp (Vec<String>): accepted `azp` values; empty accepts any authorized party
+/// * leeway (u64): clock-skew tolerance in seconds for `exp` and `nbf`
+/// * algorithms (Vec<Algorithm>): asymmetric signing algorithms that are accepted
+#[derive(Clone, Debug)]
+pub struct TokenValidationPolicy {
+    pub issuer: String,
+    pub required_claims: Vec<String>,
+    pub audiences: Vec<String>,
+    pub allowed_azp: Vec<String>,
+    pub leeway: u64,
+    pub algorithms: Vec<Algorithm>,
+}
+
+impl TokenValidationPolicy {
+
+    /// Builds the policy for one issuer from the `TOKEN_*` environment variables, each of which can
+    /// be overridden per issuer (see `IssuerConfig::var`).
+    ///
+    /// The issuer is left empty; it is filled in by `for_issuer` once discovery has succeeded, so
+    /// malformed settings are still reported at startup even while Keycloak is unreachable.
+    ///
+    /// # Arguments
+    /// * issuer (&IssuerConfig): the issuer; its client ID is the default audience and `azp`
+    ///
+    /// # Returns
+    /// * (Result<TokenValidationPolicy, String>): the policy, or an error if a variable is malformed
+    pub fn from_env(issuer: &IssuerConfig) -> Result<TokenValidationPolicy, String> {
+        let env_list = |name: &str| issuer.var(name).map(|value| split_list(&value));
+        let required_claims = env_list("TOKEN_REQUIRED_CLAIMS")
+            .unwrap_or_else(|| vec!["exp".to_string(), "iat".to_string(), "iss".to_string(), "sub".to_string()]);
+        let audiences = env_list("TOKEN_AUDIENCES").unwrap_or_else(|| vec![issuer.client_id.clone()]);
+        let allowed_azp = env_list("TOKEN_ALLOWED_AZP").unwrap_or_else(|| vec![issuer.client_id.clone()]);
+        let leeway = match issuer.var("TOKEN_LEEWAY_SECS") {
+            Some(value) => value
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid TOKEN_LEEWAY_SECS '{}': {}", value, e))?,
+            None => 30,
+        };
+        let algorithms = env_list("TOKEN_ALGORITHMS")
+            .unwrap_or_else(|| ["RS256", "PS256", "ES256", "EdDSA"].iter().map(|name| name.to_string()).collect())
+            .iter()
+            .map(|name| {
+                Algorithm::from_str(name).map_err(|_| format!("Unsupported algorithm in TOKEN_ALGORITHMS: {}", name))
+            })
+            .collect::<Result<Vec<Algorithm>, String>>()?;
+        if algorithms.is_empty() {
+            return Err("TOKEN_ALGORITHMS must list at least one algorithm".to_string());
+        }
+        // Keys come from a public JWKS, so a shared-secret algorithm could only ever be forged.
+        if let Some(symmetric) = algorithms.iter().find(|alg| matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)) {
+            return Err(format!("TOKEN_ALGORITHMS may not contain the symmetric algorithm {:?}", symmetric));
+        }
+
+        Ok(TokenValidationPolicy {
+            issuer: String::new(),
+            required_claims,
+            audiences,
+            allowed_azp,
+            leeway,
+            algorithms,
+        })
+    }
+
+    /// Binds the policy to the issuer advertised by the discovery document.
+    ///
+    /// # Arguments
+    /// * openid_config (&OpenIdConfig): the discovery document, which provides the issuer
+    ///
+    /// # Returns
+    /// (TokenValidationPolicy): a copy of the policy that expects tokens from that issuer
+    pub fn for_issuer(&self, openid_config: &OpenIdConfig) -> TokenValidationPolicy {
+        for algorithm in &self.algorithms {
+            let name = format!("{:?}", algorithm);
+            if !openid_config.id_token_signing_alg_values_supported.is_empty() && !openid_config.supports_signing_alg(&name) {
+                warn!("TOKEN_ALGORITHMS allows {} but the realm does not advertise it.", name);
+            }
+        }
+        let policy = TokenValidationPolicy {
+            issuer: openid_config.issuer.clone(),
+            ..self.clone()
+        };
+        info!("Token validation policy: {:?}", policy);
+        policy
+    }
+
+    /// Creates the `jsonwebtoken` validation settings for a token signed with `algorithm`.
+    ///
+    /// # Arguments
+    /// * algorithm (Algorithm): the algorithm from the token header
+    ///
+    /// # Returns
+    /// * (Result<Validation, TokenRejection>): the settings, or a rejection if the algorithm is not allowed
+    pub fn validation_for(&self, algorithm: Algorithm) -> Result<Validation, TokenRejection> {
+        if !self.algorithms.contains(&algorithm) {
+            return Err(TokenRejection::DisallowedAlgorithm(format!("{:?}", algorithm)));
+        }
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = self.leeway;
+        validation.validate_nbf = true;
+        validation.set_issuer(&[&self.issuer]);
+        // Only the claims jsonwebtoken knows about are enforced here; the rest go through `check_claims`.
+        let spec_claims: Vec<&String> = self
+            .required_claims
+            .iter()
+            .filter(|claim| ["exp", "nbf", "aud", "iss", "sub"].contains(&claim.as_str()))
+            .collect();
+        validation.set_required_spec_claims(&spec_claims);
+        if self.audiences.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.audiences);
+        }
+        Ok(validation)
+    }
+
+    /// Checks the rules `jsonwebtoken` cannot express: arbitrary required claims and the `azp` allow-list.
+    ///
+    /// # Arguments
+    /// * claims (&Map<String, Value>): the verified token payload
+    ///
+    /// # Returns
+    /// * (Result<(), TokenRejection>): a rejection describing the first rule that failed
+    pub fn check_claims(&self, claims: &Map<String, Value>) -> Result<(), TokenRejection> {
+        if let Some(missing) = self.required_claims.iter().find(|claim| !claims.contains_key(claim.as_str())) {
+            return Err(TokenRejection::MissingClaim(missing.clone()));
+        }
+        if !self.allowed_azp.is_empty() {
+            match claims.get("azp").and_then(Value::as_str) {
+                Some(azp) if self.allowed_azp.iter().any(|allowed| allowed == azp) => {},
+                Some(azp) => return Err(TokenRejection::DisallowedAzp(azp.to_string())),
+                None => return Err(TokenRejection::MissingClaim("azp".to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the policy to an introspection response, which carries no signature to verify.
+    ///
+    /// # Arguments
+    /// * claims (&Map<String, Value>): the claims returned by the introspection endpoint
+    /// * now (u64): the current time in seconds since the epoch
+    ///
+    /// # Returns
+    /// * (Result<(), TokenRejection>): a rejection describing the first rule that failed
+    pub fn check_introspected(&self, claims: &Map<String, Value>, now: u64) -> Result<(), TokenRejection> {
+        if claims.get("iss").and_then(Value::as_str) != Some(self.issuer.as_str()) {
+            return Err(TokenRejection::InvalidIssuer);
+        }
+        match claims.get("exp").and_then(Value::as_u64) {
+            Some(exp) if exp + self.leeway < now => return Err(TokenRejection::Expired),
+            Some(_) => {},
+            None => return Err(TokenRejection::MissingClaim("exp".to_string())),
+        }
+        if let Some(nbf) = claims.get("nbf").and_then(Value::as_u64) {
+            if nbf > now + self.leeway {
+                return Err(TokenRejection::NotYetValid);
+            }
+        }
+        if !self.audiences.is_empty() {
+            let audiences: Vec<&str> = match claims.get("aud") {
+                Some(Value::String(aud)) => vec![aud.as_str()],
+                Some(Value::Array(auds)) => auds.iter().filter_map(Value::as_str).collect(),
+                _ => vec![],
+            };
+            if !audiences.iter().any(|aud| self.audiences.iter().any(|accepted| accepted == aud)) {
+                return Err(TokenRejection::InvalidAudience);
+            }
+        }
+        self.check_claims(claims)
+    }
+}
+
+/// Works out which algorithm a token must be verified with, given the JWK its `kid` points at.
+///
+/// The key decides, not the token: a key that publishes an `alg` only verifies that algorithm,
+/// and otherwise the header's algorithm must belong to the key's type and curve (RSA keys take
+/// `RS*`/`PS*`, P-256 takes `ES256`, P-384 takes `ES384`, Ed25519 takes `EdDSA`). Symmetric keys
+/// and keys published for encryption are never used to verify tokens.
+///
+/// # Arguments
+/// * header_alg (Algorithm): the algorithm named in the token header
+/// * jwk (&Jwk): the signing key from the JWKS
+///
+/// # Returns
+/// * (Result<Algorithm, TokenRejection>): the algorithm to verify with, or `AlgorithmMismatch`
+pub fn algorithm_for_key(header_alg: Algorithm, jwk: &Jwk) -> Result<Algorithm, TokenRejection> {
+    let mismatch = |detail: String| Err(TokenRejection::AlgorithmMismatch(format!("{:?} {}", header_alg, detail)));
+
+    if matches!(jwk.common.public_key_use, Some(PublicKeyUse::Encryption)) {
+        return mismatch("cannot be verified with an encryption key".to_string());
+    }
+    if let Some(key_alg) = &jwk.common.key_algorithm {
+        match Algorithm::from_str(&key_alg.to_string()) {
+            Ok(alg) if alg == header_alg => {},
+            Ok(alg) => return mismatch(format!("does not match the key's alg {:?}", alg)),
+            Err(_) => return mismatch(format!("cannot be verified with a {} key", key_alg)),
+        }
+    }
+
+    let matches_key = match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => matches!(
+            header_alg,
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512
+        ),
+        AlgorithmParameters::EllipticCurve(params) => matches!(
+            (&params.curve, header_alg),
+            (EllipticCurve::P256, Algorithm::ES256) | (EllipticCurve::P384, Algorithm::ES384)
+        ),
+        AlgorithmParameters::OctetKeyPair(params) => params.curve == EllipticCurve::Ed25519 && header_alg == Algorithm::EdDSA,
+        AlgorithmParameters::OctetKey(_) => false,
+    };
+    if !matches_key {
+        return mismatch("does not match the key type".to_string());
+    }
+    Ok(header_alg)
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// The reason a token was rejected, logged as a stable `reason` code.
+#[derive(Debug)]
+pub enum TokenRejection {
+    MissingToken(String),
+    InvalidSession(String),
+    UnknownIssuer(String),
+    IssuerUnavailable(String),
+    MalformedHeader(String),
+    MissingKid,
+    UnknownKey(String),
+    DisallowedAlgorithm(String),
+    AlgorithmMismatch(String),
+    InvalidSignature,
+    Expired,
+    NotYetValid,
+    InvalidIssuer,
+    InvalidAudience,
+    MissingClaim(String),
+    DisallowedAzp(String),
+    Inactive,
+    IntrospectionFailed(String),
+    InvalidLogoutToken(String),
+    LoggedOut,
+    AccountDisabled,
+    AccountDeleted,
+    InvalidPersonalAccessToken,
+    Malformed(String),
+}
+
+impl TokenRejection {
+
+    /// Returns a short, stable code for the rejection, suitable for log filtering.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            TokenRejection::MissingToken(_) => "missing_token",
+            TokenRejection::InvalidSession(_) => "invalid_session",
+            TokenRejection::UnknownIssuer(_) => "unknown_issuer",
+            TokenRejection::IssuerUnavailable(_) => "issuer_unavailable",
+            TokenRejection::MalformedHeader(_) => "malformed_header",
+            TokenRejection::MissingKid => "missing_kid",
+            TokenRejection::UnknownKey(_) => "unknown_key",
+            TokenRejection::DisallowedAlgorithm(_) => "disallowed_algorithm",
+            TokenRejection::AlgorithmMismatch(_) => "algorithm_mismatch",
+            TokenRejection::InvalidSignature => "invalid_signature",
+            TokenRejection::Expired => "expired",
+            TokenRejection::NotYetValid => "not_yet_valid",
+            TokenRejection::InvalidIssuer => "invalid_issuer",
+            TokenRejection::InvalidAudience => "invalid_audience",
+            TokenRejection::MissingClaim(_) => "missing_claim",
+            TokenRejection::DisallowedAzp(_) => "disallowed_azp",
+            TokenRejection::Inactive => "inactive",
+            TokenRejection::IntrospectionFailed(_) => "introspection_failed",
+            TokenRejection::InvalidLogoutToken(_) => "invalid_logout_token",
+            TokenRejection::LoggedOut => "logged_out",
+            TokenRejection::AccountDisabled => "account_disabled",
+            TokenRejection::AccountDeleted => "account_deleted",
+            TokenRejection::InvalidPersonalAccessToken => "invalid_personal_access_token",
+            TokenRejection::Malformed(_) => "malformed",
+        }
+    }
+
+    /// Whether the token simply cannot be checked against the JWKS (an opaque token, or a key
+    /// the realm does not publish), so introspection may still be able to vouch for it.
+    pub fn allows_introspection_fallback(&self) -> bool {
+        matches!(
+            self,
+            TokenRejection::MalformedHeader(_) | TokenRejection::MissingKid | TokenRejection::UnknownKey(_)
+        )
+    }
+}
+
+impl fmt::Display for TokenRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenRejection::MissingToken(detail) => write!(f, "{}", detail),
+            TokenRejection::InvalidSession(detail) => write!(f, "Invalid session: {}", detail),
+            TokenRejection::UnknownIssuer(iss) => write!(f, "Token issuer '{}' is not trusted", iss),
+            TokenRejection::IssuerUnavailable(name) => write!(f, "Issuer '{}' is not available yet", name),
+            TokenRejection::MalformedHeader(detail) => write!(f, "Invalid JWT header: {}", detail),
+            TokenRejection::MissingKid => write!(f, "JWT header missing 'kid'"),
+            TokenRejection::UnknownKey(detail) => write!(f, "{}", detail),
+            TokenRejection::DisallowedAlgorithm(alg) => write!(f, "Algorithm not allowed: {}", alg),
+            TokenRejection::AlgorithmMismatch(detail) => write!(f, "Algorithm does not match the signing key: {}", detail),
+            TokenRejection::InvalidSignature => write!(f, "Invalid token signature"),
+            TokenRejection::Expired => write!(f, "Token has expired"),
+            TokenRejection::NotYetValid => write!(f, "Token is not valid yet"),
+            TokenRejection::InvalidIssuer => write!(f, "Token issuer is not accepted"),
+            TokenRejection::InvalidAudience => write!(f, "Token audience is not accepted"),
+            TokenRejection::MissingClaim(claim) => write!(f, "Token is missing required claim '{}'", claim),
+            TokenRejection::DisallowedAzp(azp) => write!(f, "Authorized party '{}' is not accepted", azp),
+            TokenRejection::Inactive => write!(f, "Token is not active"),
+            TokenRejection::IntrospectionFailed(detail) => write!(f, "Token introspection failed: {}", detail),
+            TokenRejection::InvalidLogoutToken(detail) => write!(f, "Invalid logout token: {}", detail),
+            TokenRejection::LoggedOut => write!(f, "The session of this token has been logged out"),
+            TokenRejection::AccountDisabled => write!(f, "The account of this token has been disabled"),
+            TokenRejection::AccountDeleted => write!(f, "The account of this token has been deleted"),
+            TokenRejection::InvalidPersonalAccessToken => write!(f, "Personal access token is unknown, revoked or expired"),
+            TokenRejection::Malformed(detail) => write!(f, "Token validation failed: {}", detail),
+        }
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for TokenRejection {
+    fn from(error: jsonwebtoken::errors::Error) -> TokenRejection {
+        match error.kind() {
+            ErrorKind::ExpiredSignature => TokenRejection::Expired,
+            ErrorKind::ImmatureSignature => TokenRejection::NotYetValid,
+            ErrorKind::InvalidIssuer => TokenRejection::InvalidIssuer,
+            ErrorKind::InvalidAudience => TokenRejection::InvalidAudience,
+            ErrorKind::InvalidSignature => TokenRejection::InvalidSignature,
+            ErrorKind::InvalidAlgorithm => TokenRejection::AlgorithmMismatch("key/algorithm mismatch".to_string()),
+            ErrorKind::MissingRequiredClaim(claim) => TokenRejection::MissingClaim(claim.clone()),
+            _ => TokenRejection::Malformed(error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn policy(leeway: u64) -> TokenValidationPolicy {
+        TokenValidationPolicy {
+            issuer: "https://keycloak.test/realms/myrealm".to_string(),
+            required_claims: vec!["exp".to_string(), "iss".to_string(), "sub".to_string()],
+            audiences: vec!["todo-app".to_string(), "todo-api".to_string()],
+            allowed_azp: vec!["todo-app".to_string()],
+            leeway,
+            algorithms: vec![Algorithm::RS256],
+        }
+    }
+
+    /// Introspected claims that satisfy `policy`, with `change` applied.
+    fn claims(change: impl FnOnce(&mut Map<String, Value>)) -> Map<String, Value> {
+        let mut claims = json!({
+            "iss": "https://keycloak.test/realms/myrealm",
+            "sub": "user-1",
+            "aud": "todo-app",
+            "azp": "todo-app",
+            "exp": NOW + 300,
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+        change(&mut claims);
+        claims
+    }
+
+    #[test]
+    fn introspected_claims_that_satisfy_the_policy_are_accepted() {
+        assert!(policy(0).check_introspected(&claims(|_| {}), NOW).is_ok());
+        assert!(policy(0)
+            .check_introspected(&claims(|claims| { claims.insert("aud".to_string(), json!(["account", "todo-api"])); }), NOW)
+            .is_ok());
+    }
+
+    #[test]
+    fn introspected_claims_from_another_issuer_are_rejected() {
+        let wrong = claims(|claims| { claims.insert("iss".to_string(), json!("https://evil.test/realms/myrealm")); });
+        assert!(matches!(policy(0).check_introspected(&wrong, NOW), Err(TokenRejection::InvalidIssuer)));
+
+        let missing = claims(|claims| { claims.remove("iss"); });
+        assert!(matches!(policy(0).check_introspected(&missing, NOW), Err(TokenRejection::InvalidIssuer)));
+    }
+
+    #[test]
+    fn expired_introspected_claims_are_rejected_after_the_leeway() {
+        let expired = claims(|claims| { claims.insert("exp".to_string(), json!(NOW - 30)); });
+
+        assert!(matches!(policy(0).check_introspected(&expired, NOW), Err(TokenRejection::Expired)));
+        assert!(policy(60).check_introspected(&expired, NOW).is_ok());
+
+        let missing = claims(|claims| { claims.remove("exp"); });
+        assert!(matches!(policy(0).check_introspected(&missing, NOW), Err(TokenRejection::MissingClaim(_))));
+    }
+
+    #[test]
+    fn introspected_claims_not_valid_yet_are_rejected_outside_the_leeway() {
+        let early = claims(|claims| { claims.insert("nbf".to_string(), json!(NOW + 30)); });
+
+        assert!(matches!(policy(0).check_introspected(&early, NOW), Err(TokenRejection::NotYetValid)));
+        assert!(policy(60).check_introspected(&early, NOW).is_ok());
+    }
+
+    #[test]
+    fn introspected_claims_for_another_audience_are_rejected() {
+        let other = claims(|claims| { claims.insert("aud".to_string(), json!("other-app")); });
+        assert!(matches!(policy(0).check_introspected(&other, NOW), Err(TokenRejection::InvalidAudience)));
+
+        let others = claims(|claims| { claims.insert("aud".to_string(), json!(["account", "other-app"])); });
+        assert!(matches!(policy(0).check_introspected(&others, NOW), Err(TokenRejection::InvalidAudience)));
+
+        let missing = claims(|claims| { claims.remove("aud"); });
+        assert!(matches!(policy(0).check_introspected(&missing, NOW), Err(TokenRejection::InvalidAudience)));
+    }
+
+    #[test]
+    fn authorized_parties_must_be_on_the_allow_list() {
+        let other = claims(|claims| { claims.insert("azp".to_string(), json!("other-app")); });
+        assert!(matches!(policy(0).check_claims(&other), Err(TokenRejection::DisallowedAzp(azp)) if azp == "other-app"));
+
+        let missing = claims(|claims| { claims.remove("azp"); });
+        assert!(matches!(policy(0).check_claims(&missing), Err(TokenRejection::MissingClaim(claim)) if claim == "azp"));
+
+        let any_azp = TokenValidationPolicy { allowed_azp: Vec::new(), ..policy(0) };
+        assert!(any_azp.check_claims(&other).is_ok());
+    }
+
+    #[test]
+    fn required_claims_must_be_present() {
+        let missing = claims(|claims| { claims.remove("sub"); });
+        assert!(matches!(policy(0).check_claims(&missing), Err(TokenRejection::MissingClaim(claim)) if claim == "sub"));
+    }
+}
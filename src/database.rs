@@ -1,10 +1,53 @@
+use diesel::connection::{Instrumentation, InstrumentationEvent};
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use dotenv::dotenv;
 use std::env;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{debug, info_span, warn, Span};
 pub fn establish_connection() -> PgConnection {
+    try_establish_connection().unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Connects to the database, returning an error instead of panicking when it is unreachable.
+///
+/// # Returns
+/// * (Result<PgConnection, String>): the connection, or an error message
+pub fn try_establish_connection() -> Result<PgConnection, String> {
     dotenv().ok();
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    PgConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+    let mut connection = PgConnection::establish(&database_url)
+        .map_err(|e| format!("Error connecting to {}: {}", database_url, e))?;
+    connection.set_instrumentation(trace_queries());
+    Ok(connection)
+}
+
+/// Runs each query in a `db_query` span with its SQL and latency, inside the span of the request or
+/// token check that ran it, and logs it under the `diesel` target. The bound values are left out, as
+/// they include password hashes and token hashes.
+fn trace_queries() -> impl Instrumentation {
+    let mut running: Option<(Span, Instant)> = None;
+    move |event: InstrumentationEvent<'_>| match event {
+        InstrumentationEvent::StartQuery { query, .. } => {
+            let span = info_span!("db_query", sql = %without_binds(&query.to_string()), latency_ms = Empty);
+            running = Some((span, Instant::now()));
+        },
+        InstrumentationEvent::FinishQuery { query, error, .. } => {
+            if let Some((span, started)) = running.take() {
+                span.record("latency_ms", started.elapsed().as_micros() as f64 / 1000.0);
+                let _entered = span.enter();
+                let query = query.to_string();
+                match error {
+                    Some(e) => warn!(target: "diesel", "Query failed: {}: {}", without_binds(&query), e),
+                    None => debug!(target: "diesel", "{}", without_binds(&query)),
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+fn without_binds(query: &str) -> &str {
+    query.split(" -- binds:").next().unwrap_or_default()
 }
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct EmailSchema {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct AccountTokenSchema {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetSchema {
+    pub token: String,
+    pub password: String,
+}
@@ -0,0 +1,122 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::json_serialization::personal_access_token::PersonalAccessTokenView;
+use crate::models::user::account_data::AccountData;
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// The user's row. Times are Unix seconds, as everywhere in the export.
+#[derive(Serialize)]
+pub struct ExportedUser {
+    pub id: String,
+    pub username: String,
+    pub email: Option<String>,
+    pub issuer: String,
+    pub subject: String,
+    pub email_verified_at: Option<u64>,
+    pub display_name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub disabled_at: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedItem {
+    pub id: i32,
+    pub title: String,
+    pub status: String,
+}
+
+/// A way the user can sign in. Password hashes are not exported.
+#[derive(Serialize)]
+pub struct ExportedCredential {
+    pub kind: String,
+    pub issuer: Option<String>,
+    pub subject: Option<String>,
+    pub created_at: u64,
+}
+
+/// A browser session, without the tokens it holds.
+#[derive(Serialize)]
+pub struct ExportedSession {
+    pub issuer_name: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+/// A refresh token of a local login, without the token.
+#[derive(Serialize)]
+pub struct ExportedRefreshToken {
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub used_at: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ExportedLoginFailures {
+    pub failures: i32,
+    pub last_failure_at: u64,
+    pub locked_until: Option<u64>,
+}
+
+/// Everything stored about a user, one entry per file of the export archive.
+pub struct AccountExport {
+    pub user: ExportedUser,
+    pub items: Vec<ExportedItem>,
+    pub credentials: Vec<ExportedCredential>,
+    pub personal_access_tokens: Vec<PersonalAccessTokenView>,
+    pub sessions: Vec<ExportedSession>,
+    pub refresh_tokens: Vec<ExportedRefreshToken>,
+    pub login_failures: Option<ExportedLoginFailures>,
+}
+
+impl AccountExport {
+    pub fn new(data: &AccountData) -> AccountExport {
+        let user = &data.user;
+        AccountExport {
+            user: ExportedUser {
+                id: user.id.clone(),
+                username: user.username.clone(),
+                email: user.email.clone(),
+                issuer: user.issuer.clone(),
+                subject: user.subject.clone(),
+                email_verified_at: user.email_verified_at.map(seconds),
+                display_name: user.display_name.clone(),
+                given_name: user.given_name.clone(),
+                family_name: user.family_name.clone(),
+                disabled_at: user.disabled_at.map(seconds),
+            },
+            items: data.items.iter().map(|item| ExportedItem {
+                id: item.id,
+                title: item.title.clone(),
+                status: item.status.clone(),
+            }).collect(),
+            credentials: data.credentials.iter().map(|credential| ExportedCredential {
+                kind: credential.kind.clone(),
+                issuer: credential.issuer.clone(),
+                subject: credential.subject.clone(),
+                created_at: seconds(credential.created_at),
+            }).collect(),
+            personal_access_tokens: data.personal_access_tokens.iter().map(PersonalAccessTokenView::new).collect(),
+            sessions: data.sessions.iter().map(|session| ExportedSession {
+                issuer_name: session.issuer_name.clone(),
+                created_at: seconds(session.created_at),
+                expires_at: seconds(session.expires_at),
+            }).collect(),
+            refresh_tokens: data.refresh_tokens.iter().map(|token| ExportedRefreshToken {
+                created_at: seconds(token.created_at),
+                expires_at: seconds(token.expires_at),
+                used_at: token.used_at.map(seconds),
+            }).collect(),
+            login_failures: data.login_failures.as_ref().map(|failures| ExportedLoginFailures {
+                failures: failures.failures,
+                last_failure_at: seconds(failures.last_failure_at),
+                locked_until: failures.locked_until.map(seconds),
+            }),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::user::user::User;
+
+/// The query string of the user list: a search term and the page, counted from 1.
+#[derive(Deserialize)]
+pub struct UserListQuery {
+    pub q: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+/// The query string of a user deletion: `items=delete`, or `items=reassign` with `reassign_to`.
+#[derive(Deserialize)]
+pub struct UserDeleteQuery {
+    pub items: Option<String>,
+    pub reassign_to: Option<String>,
+}
+
+/// A user as shown to administrators. Times are Unix seconds.
+#[derive(Serialize)]
+pub struct AdminUserView {
+    pub id: String,
+    pub username: String,
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub issuer: String,
+    pub email_verified: bool,
+    pub disabled: bool,
+    pub disabled_at: Option<u64>,
+    pub item_count: i64,
+}
+
+impl AdminUserView {
+    pub fn new(user: &User, item_count: i64) -> AdminUserView {
+        let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        AdminUserView {
+            id: user.id.clone(),
+            username: user.username.clone(),
+            email: user.email.clone(),
+            display_name: user.display_name.clone(),
+            issuer: user.issuer.clone(),
+            email_verified: user.email_verified_at.is_some(),
+            disabled: user.disabled_at.is_some(),
+            disabled_at: user.disabled_at.map(seconds),
+            item_count,
+        }
+    }
+}
+
+/// One page of the user list.
+#[derive(Serialize)]
+pub struct AdminUserPage {
+    pub users: Vec<AdminUserView>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
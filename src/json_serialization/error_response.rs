@@ -0,0 +1,39 @@
+use serde::Serialize;
+
+use crate::middleware::request_id;
+
+/// One problem with one field of a request body.
+#[derive(Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &str, message: &str) -> FieldError {
+        FieldError { field: field.to_string(), code: code.to_string(), message: message.to_string() }
+    }
+}
+
+/// The JSON body of an error response: a machine-readable code, a message, the field-level
+/// problems if the request body was invalid, and the ID of the request to find its log lines.
+#[derive(Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ErrorResponse {
+    pub fn new(error: &str, message: &str) -> ErrorResponse {
+        ErrorResponse { error: error.to_string(), message: message.to_string(), fields: Vec::new(), request_id: request_id::current() }
+    }
+
+    pub fn with_fields(error: &str, message: &str, fields: Vec<FieldError>) -> ErrorResponse {
+        ErrorResponse { error: error.to_string(), message: message.to_string(), fields, request_id: request_id::current() }
+    }
+}
@@ -1,7 +1,21 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
 pub struct Login {
     pub username: String,
     pub password: String,
 }
+
+#[derive(Deserialize)]
+pub struct Refresh {
+    pub refresh_token: String,
+}
+
+/// The tokens returned by a successful login or refresh.
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    pub refresh_token: String,
+}
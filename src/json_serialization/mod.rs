@@ -1,4 +1,9 @@
+pub mod account;
+pub mod account_export;
+pub mod admin_user;
+pub mod error_response;
 pub mod login;
 pub mod new_user;
+pub mod personal_access_token;
 pub mod to_do_item;
 pub mod to_do_items;
@@ -1,7 +1,192 @@
 use serde::Deserialize;
+
+use crate::auth::password_policy::PasswordPolicy;
+use crate::json_serialization::error_response::FieldError;
+
+/// Usernames are 3 to 32 characters long.
+const USERNAME_LENGTH: std::ops::RangeInclusive<usize> = 3..=32;
+
+/// The longest email address SMTP can deliver to.
+const MAX_EMAIL_LENGTH: usize = 254;
+
 #[derive(Deserialize)]
 pub struct NewUserSchema {
     pub name: String,
     pub email: String,
     pub password: String,
 }
+
+impl NewUserSchema {
+
+    /// Checks the username, email address and password of a registration.
+    ///
+    /// # Arguments
+    /// * policy (&PasswordPolicy): the password rules
+    ///
+    /// # Returns
+    /// * (Vec<FieldError>): every problem found; empty if the registration is valid
+    pub fn validate(&self, policy: &PasswordPolicy) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        let name_length = self.name.chars().count();
+        if !USERNAME_LENGTH.contains(&name_length) {
+            errors.push(FieldError::new("name", "invalid_length", &format!(
+                "must be between {} and {} characters long", USERNAME_LENGTH.start(), USERNAME_LENGTH.end()
+            )));
+        }
+        if !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-') {
+            errors.push(FieldError::new("name", "invalid_characters", "may only contain letters, digits, '.', '_' and '-'"));
+        } else if !self.name.starts_with(|c: char| c.is_ascii_alphanumeric()) && name_length > 0 {
+            errors.push(FieldError::new("name", "invalid_characters", "must start with a letter or a digit"));
+        }
+
+        if !is_valid_email(&self.email) {
+            errors.push(FieldError::new("email", "invalid_email", "is not a valid email address"));
+        }
+
+        for problem in policy.check(&self.password, &self.name) {
+            errors.push(FieldError::new("password", "weak_password", &problem));
+        }
+        errors
+    }
+}
+
+/// A syntax check for addresses people actually use: one `@`, a local part without spaces, and a
+/// domain of at least two dot-separated labels. Quoted local parts and IP literals are refused.
+fn is_valid_email(email: &str) -> bool {
+    if email.len() > MAX_EMAIL_LENGTH || email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    let (local, domain) = match email.split_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    if local.is_empty() || local.len() > 64 || local.contains('"') || domain.contains('@') {
+        return false;
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return false;
+    }
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() >= 2 && labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy { min_length: 10, min_character_classes: 2 }
+    }
+
+    fn registration(name: &str, email: &str) -> NewUserSchema {
+        NewUserSchema {
+            name: name.to_string(),
+            email: email.to_string(),
+            password: "correct-horse-battery".to_string(),
+        }
+    }
+
+    /// The `(field, code)` of every problem found in a registration.
+    fn problems(name: &str, email: &str) -> Vec<(String, String)> {
+        registration(name, email)
+            .validate(&policy())
+            .into_iter()
+            .map(|error| (error.field, error.code))
+            .collect()
+    }
+
+    fn name_problem(code: &str) -> Vec<(String, String)> {
+        vec![("name".to_string(), code.to_string())]
+    }
+
+    #[test]
+    fn a_valid_registration_has_no_problems() {
+        assert!(problems("alice", "alice@example.com").is_empty());
+        assert!(problems("a.b_c-9", "alice@example.com").is_empty());
+    }
+
+    #[test]
+    fn usernames_must_be_3_to_32_characters_long() {
+        assert_eq!(problems("al", "alice@example.com"), name_problem("invalid_length"));
+        assert!(problems("ali", "alice@example.com").is_empty());
+        assert!(problems(&"a".repeat(32), "alice@example.com").is_empty());
+        assert_eq!(problems(&"a".repeat(33), "alice@example.com"), name_problem("invalid_length"));
+    }
+
+    #[test]
+    fn usernames_are_limited_to_letters_digits_and_a_few_symbols() {
+        assert_eq!(problems("alice smith", "alice@example.com"), name_problem("invalid_characters"));
+        assert_eq!(problems("alice@home", "alice@example.com"), name_problem("invalid_characters"));
+        assert_eq!(problems("alicé", "alice@example.com"), name_problem("invalid_characters"));
+    }
+
+    #[test]
+    fn usernames_must_start_with_a_letter_or_a_digit() {
+        assert_eq!(problems(".alice", "alice@example.com"), name_problem("invalid_characters"));
+        assert_eq!(problems("_alice", "alice@example.com"), name_problem("invalid_characters"));
+        assert_eq!(problems("-alice", "alice@example.com"), name_problem("invalid_characters"));
+        assert!(problems("9alice", "alice@example.com").is_empty());
+    }
+
+    #[test]
+    fn an_empty_username_is_reported_once() {
+        assert_eq!(problems("", "alice@example.com"), name_problem("invalid_length"));
+    }
+
+    #[test]
+    fn an_invalid_email_is_reported() {
+        assert_eq!(problems("alice", "alice"), vec![("email".to_string(), "invalid_email".to_string())]);
+    }
+
+    #[test]
+    fn common_email_addresses_are_valid() {
+        assert!(is_valid_email("alice@example.com"));
+        assert!(is_valid_email("alice.smith+todo@mail.example.co.uk"));
+        assert!(is_valid_email("a@b.io"));
+    }
+
+    #[test]
+    fn email_addresses_need_exactly_one_at_sign() {
+        assert!(!is_valid_email("alice.example.com"));
+        assert!(!is_valid_email("alice@home@example.com"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("alice@"));
+    }
+
+    #[test]
+    fn email_local_parts_may_not_misuse_dots_or_quotes() {
+        assert!(!is_valid_email(".alice@example.com"));
+        assert!(!is_valid_email("alice.@example.com"));
+        assert!(!is_valid_email("alice..smith@example.com"));
+        assert!(!is_valid_email("\"alice\"@example.com"));
+        assert!(!is_valid_email("alice smith@example.com"));
+    }
+
+    #[test]
+    fn email_domains_need_two_valid_labels() {
+        assert!(!is_valid_email("alice@localhost"));
+        assert!(!is_valid_email("alice@example..com"));
+        assert!(!is_valid_email("alice@-example.com"));
+        assert!(!is_valid_email("alice@example.com."));
+        assert!(!is_valid_email("alice@[127.0.0.1]"));
+    }
+
+    #[test]
+    fn email_addresses_are_at_most_254_bytes() {
+        let domain = format!("{}.{}.{}.com", "a".repeat(63), "b".repeat(63), "c".repeat(57));
+        let longest = format!("{}@{}", "d".repeat(64), domain);
+        assert_eq!(longest.len(), 254);
+        assert!(is_valid_email(&longest));
+
+        let too_long = format!("{}@{}.{}.{}.com", "d".repeat(64), "a".repeat(63), "b".repeat(63), "c".repeat(58));
+        assert_eq!(too_long.len(), 255);
+        assert!(!is_valid_email(&too_long));
+    }
+}
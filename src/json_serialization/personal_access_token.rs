@@ -0,0 +1,41 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::personal_access_token::personal_access_token::PersonalAccessToken;
+
+#[derive(Deserialize)]
+pub struct NewPersonalAccessTokenSchema {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<u64>,
+}
+
+/// A personal access token as shown to its owner. Times are Unix seconds; the token itself is only
+/// included in the response that created it.
+#[derive(Serialize)]
+pub struct PersonalAccessTokenView {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub last_used_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl PersonalAccessTokenView {
+    pub fn new(token: &PersonalAccessToken) -> PersonalAccessTokenView {
+        let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        PersonalAccessTokenView {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            scopes: token.scopes.split_whitespace().map(str::to_string).collect(),
+            created_at: seconds(token.created_at),
+            expires_at: seconds(token.expires_at),
+            last_used_at: token.last_used_at.map(seconds),
+            token: None,
+        }
+    }
+}
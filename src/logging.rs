@@ -0,0 +1,132 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::Level;
+use tracing_subscriber::filter::{EnvFilter, Targets};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, Layer, Registry};
+
+/// How log lines are written to stdout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    /// `time LEVEL span{fields}: target: message key=value ...`
+    Text,
+    /// One JSON object per line, with the fields of the event as keys and its spans under `spans`.
+    Json,
+}
+
+impl LogFormat {
+
+    /// Reads `LOG_FORMAT` (`text`, the default, or `json`).
+    ///
+    /// # Returns
+    /// (Result<LogFormat, String>): the format, or an error for an unknown value
+    pub fn from_env() -> Result<LogFormat, String> {
+        match env::var("LOG_FORMAT").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "" | "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("LOG_FORMAT must be text or json, not '{}'", other)),
+        }
+    }
+}
+
+/// Where the spans of requests, token checks and database queries go.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceExporter {
+    /// Each span is written to stdout when it closes, with its fields and latency, in the `LOG_FORMAT`.
+    Stdout,
+    /// Spans are sent over OTLP/HTTP to the collector at `OTEL_EXPORTER_OTLP_ENDPOINT`
+    /// (`http://localhost:4318` by default); stdout only gets the log lines.
+    Otlp,
+}
+
+impl TraceExporter {
+
+    /// Reads `TRACE_EXPORTER` (`stdout`, the default, or `otlp`).
+    ///
+    /// # Returns
+    /// (Result<TraceExporter, String>): the exporter, or an error for an unknown value
+    pub fn from_env() -> Result<TraceExporter, String> {
+        match env::var("TRACE_EXPORTER").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "" | "stdout" => Ok(TraceExporter::Stdout),
+            "otlp" => Ok(TraceExporter::Otlp),
+            other => Err(format!("TRACE_EXPORTER must be stdout or otlp, not '{}'", other)),
+        }
+    }
+}
+
+/// Keeps the OTLP exporter alive; `shutdown` sends the spans it still holds.
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+
+    /// Flushes and stops the OTLP exporter, if there is one. Call it once the server has stopped.
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to shut down the OTLP trace exporter: {}", e);
+            }
+        }
+    }
+}
+
+/// Sets up `tracing`: levels come from `RUST_LOG` as before, the line format from `LOG_FORMAT` and
+/// where spans go from `TRACE_EXPORTER`. Lines of the `log` crate, written by the libraries, are
+/// passed on to `tracing`.
+///
+/// # Returns
+/// (Result<Telemetry, String>): the exporter to shut down at exit, or an error if the configuration is invalid
+pub fn init() -> Result<Telemetry, String> {
+    let format = LogFormat::from_env()?;
+    let exporter = TraceExporter::from_env()?;
+
+    // Without RUST_LOG only errors are logged, as with env_logger before
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error"));
+    let span_events = match exporter {
+        TraceExporter::Stdout => FmtSpan::CLOSE,
+        TraceExporter::Otlp => FmtSpan::NONE,
+    };
+    let output: Box<dyn Layer<Registry> + Send + Sync> = match format {
+        // Colours only on a terminal, not in files and log collectors
+        LogFormat::Text => fmt::layer().with_ansi(io::stdout().is_terminal()).with_span_events(span_events).boxed(),
+        LogFormat::Json => fmt::layer().json().flatten_event(true).with_span_events(span_events).boxed(),
+    };
+
+    let provider = match exporter {
+        TraceExporter::Stdout => None,
+        TraceExporter::Otlp => {
+            let span_exporter = SpanExporter::builder()
+                .with_http()
+                .build()
+                .map_err(|e| format!("Failed to create the OTLP trace exporter: {}", e))?;
+            let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "web_application".to_string());
+            Some(
+                SdkTracerProvider::builder()
+                    .with_batch_exporter(span_exporter)
+                    .with_resource(Resource::builder().with_service_name(service_name).build())
+                    .build(),
+            )
+        }
+    };
+    // RUST_LOG only filters the lines; the application's spans are always exported
+    let spans = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("web_application"))
+            .with_filter(Targets::new().with_target("web_application", Level::INFO))
+    });
+
+    tracing_subscriber::registry()
+        .with(output.with_filter(filter))
+        .with(spans)
+        .try_init()
+        .map_err(|e| format!("Failed to set up logging: {}", e))?;
+    Ok(Telemetry { provider })
+}
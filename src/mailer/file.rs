@@ -0,0 +1,47 @@
+use std::env;
+use std::fs;
+
+use lettre::message::Mailbox;
+use lettre::{FileTransport, Transport};
+use tracing::info;
+
+use super::{build_message, Email, Mailer};
+
+/// Writes every email as an `.eml` file into a directory instead of sending it, for development.
+pub struct FileMailer {
+    from: Mailbox,
+    directory: String,
+    transport: FileTransport,
+}
+
+impl FileMailer {
+
+    /// Builds the mailer from `MAIL_DROP_DIR` (default `./mail`), creating the directory if needed.
+    ///
+    /// # Arguments
+    /// * from (Mailbox): the sender of every email
+    ///
+    /// # Returns
+    /// * (Result<FileMailer, String>): the mailer, or an error if the directory cannot be created
+    pub fn from_env(from: Mailbox) -> Result<FileMailer, String> {
+        let directory = env::var("MAIL_DROP_DIR").unwrap_or_else(|_| "./mail".to_string());
+        fs::create_dir_all(&directory).map_err(|e| format!("Cannot create MAIL_DROP_DIR {}: {}", directory, e))?;
+        Ok(FileMailer { from, transport: FileTransport::new(&directory), directory })
+    }
+
+    /// The directory the emails are written to.
+    pub fn directory(&self) -> &str {
+        &self.directory
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: &Email) -> Result<(), String> {
+        let message = build_message(&self.from, email)?;
+        let id = self.transport
+            .send(&message)
+            .map_err(|e| format!("Cannot write email to {}: {}", self.directory, e))?;
+        info!("Wrote email '{}' for {} to {}/{}.eml", email.subject, email.to, self.directory, id);
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::Mutex;
+
+use tracing::info;
+
+use super::{Email, Mailer};
+
+/// Keeps every email in memory instead of sending it, for tests.
+#[derive(Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl MemoryMailer {
+    pub fn new() -> MemoryMailer {
+        MemoryMailer::default()
+    }
+
+    /// Returns the emails sent so far, oldest first.
+    #[cfg(test)]
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+}
+
+impl Mailer for MemoryMailer {
+    fn send(&self, email: &Email) -> Result<(), String> {
+        info!("Captured email '{}' for {} in memory", email.subject, email.to);
+        self.sent.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(email.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_mailer_keeps_sent_emails() {
+        let mailer = MemoryMailer::default();
+        let email = Email { to: "alice@example.com".to_string(), subject: "Hi".to_string(), body: "Hello".to_string() };
+        mailer.send(&email).unwrap();
+        assert_eq!(mailer.sent().len(), 1);
+        assert_eq!(mailer.sent()[0].to, "alice@example.com");
+    }
+}
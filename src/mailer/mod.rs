@@ -0,0 +1,92 @@
+use std::env;
+use std::sync::Arc;
+
+use actix_web::web;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::Message;
+use tracing::{info, warn, error, Instrument, Span};
+
+pub mod file;
+pub mod memory;
+pub mod smtp;
+
+/// An email ready to be sent.
+///
+/// # Attributes
+/// * to (String): the recipient's address
+/// * subject (String): the subject line
+/// * body (String): the plain-text body
+#[derive(Clone, Debug)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers emails. The implementation is chosen at startup with `MAILER`, so development and
+/// tests need no mail server.
+pub trait Mailer: Send + Sync {
+
+    /// Sends an email, blocking until it has been handed over.
+    ///
+    /// # Arguments
+    /// * email (&Email): the email
+    ///
+    /// # Returns
+    /// * (Result<(), String>): an error message if the email could not be sent
+    fn send(&self, email: &Email) -> Result<(), String>;
+}
+
+/// Builds the mailer named by `MAILER`: `smtp`, `file` (the default) or `memory`. The file mailer is
+/// meant for development; as the emails it writes hold verification and password reset links, a
+/// warning is logged whenever it is used.
+///
+/// # Returns
+/// * (Result<Arc<dyn Mailer>, String>): the mailer, or an error if its configuration is invalid
+pub fn from_env() -> Result<Arc<dyn Mailer>, String> {
+    let from = env::var("MAIL_FROM").unwrap_or_else(|_| "To-Do <no-reply@localhost>".to_string());
+    let from = from.parse::<Mailbox>().map_err(|e| format!("MAIL_FROM '{}' is not a valid mailbox: {}", from, e))?;
+    let mailer: Arc<dyn Mailer> = match env::var("MAILER").unwrap_or_else(|_| "file".to_string()).as_str() {
+        "smtp" => Arc::new(smtp::SmtpMailer::from_env(from)?),
+        "file" => {
+            let mailer = file::FileMailer::from_env(from)?;
+            warn!(
+                "Emails, including password reset links, are written to {} instead of being sent (MAILER={}). Set MAILER=smtp in production.",
+                mailer.directory(), env::var("MAILER").map(|_| "file").unwrap_or("file, the default")
+            );
+            Arc::new(mailer)
+        },
+        "memory" => Arc::new(memory::MemoryMailer::new()),
+        other => return Err(format!("Unknown MAILER '{}'; expected smtp, file or memory", other)),
+    };
+    Ok(mailer)
+}
+
+/// Sends an email on the blocking thread pool without making the request wait for it, so the
+/// response does not reveal whether an email was sent.
+///
+/// # Arguments
+/// * mailer (web::Data<dyn Mailer>): the mailer
+/// * email (Email): the email
+pub fn send_in_background(mailer: web::Data<dyn Mailer>, email: Email) {
+    actix_rt::spawn(async move {
+        let to = email.to.clone();
+        match web::block(move || mailer.send(&email)).await {
+            Ok(Ok(())) => info!("Sent email to {}", to),
+            Ok(Err(e)) => error!("Failed to send email to {}: {}", to, e),
+            Err(e) => error!("Failed to send email to {}: {}", to, e),
+        }
+    }.instrument(Span::current()));
+}
+
+fn build_message(from: &Mailbox, email: &Email) -> Result<Message, String> {
+    let to = email.to.parse::<Mailbox>().map_err(|e| format!("Invalid recipient '{}': {}", email.to, e))?;
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(email.subject.clone())
+        .header(ContentType::TEXT_PLAIN)
+        .body(email.body.clone())
+        .map_err(|e| format!("Failed to build email: {}", e))
+}
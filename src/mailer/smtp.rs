@@ -0,0 +1,56 @@
+use std::env;
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{SmtpTransport, Transport};
+
+use super::{build_message, Email, Mailer};
+
+/// Sends emails through an SMTP relay.
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: SmtpTransport,
+}
+
+impl SmtpMailer {
+
+    /// Builds the mailer from `SMTP_HOST`, `SMTP_PORT`, `SMTP_TLS` (`starttls`, `tls` or `none`),
+    /// `SMTP_USERNAME` and `SMTP_PASSWORD`.
+    ///
+    /// # Arguments
+    /// * from (Mailbox): the sender of every email
+    ///
+    /// # Returns
+    /// * (Result<SmtpMailer, String>): the mailer, or an error if the configuration is invalid
+    pub fn from_env(from: Mailbox) -> Result<SmtpMailer, String> {
+        let host = env::var("SMTP_HOST").map_err(|_| "SMTP_HOST must be set when MAILER=smtp".to_string())?;
+        let tls = env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string());
+        let builder = match tls.as_str() {
+            "starttls" => SmtpTransport::starttls_relay(&host),
+            "tls" => SmtpTransport::relay(&host),
+            "none" => Ok(SmtpTransport::builder_dangerous(&host)),
+            other => return Err(format!("Unknown SMTP_TLS '{}'; expected starttls, tls or none", other)),
+        }
+        .map_err(|e| format!("Cannot configure SMTP relay {}: {}", host, e))?;
+
+        let mut builder = match env::var("SMTP_PORT") {
+            Ok(port) => builder.port(port.parse::<u16>().map_err(|e| format!("Invalid SMTP_PORT '{}': {}", port, e))?),
+            Err(_) => builder,
+        };
+        if let Ok(username) = env::var("SMTP_USERNAME") {
+            let password = env::var("SMTP_PASSWORD").unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(SmtpMailer { from, transport: builder.build() })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<(), String> {
+        let message = build_message(&self.from, email)?;
+        self.transport
+            .send(&message)
+            .map(|_| ())
+            .map_err(|e| format!("SMTP delivery failed: {}", e))
+    }
+}
@@ -5,13 +5,19 @@ extern crate dotenv;
 use actix_web::{App, HttpServer, web, HttpResponse, Error};
 use actix_service::Service;
 use futures::future::{ok, Either, Ready};
-use log::{info, warn, error}; // Added for logging
-use actix_files as fs; // Import actix_files
+use tracing::{info, warn, error};
 
-use env_logger;
 mod auth;
-use crate::auth::keycloak_config::fetch_keycloak_openid_config; // Import the function to fetch OIDC config
 use crate::auth::KeycloakClientConfig; // Import the new struct
+use crate::auth::account_token::AccountTokenSigner;
+use crate::auth::introspection::IntrospectionSettings;
+use crate::auth::issuers::IssuerConfig;
+use crate::auth::local_issuer::LocalTokenIssuer;
+use crate::auth::login_throttle::LoginThrottlePolicy;
+use crate::auth::password_policy::PasswordPolicy;
+use crate::auth::oidc_login::OidcLoginSettings;
+use crate::auth::provider::{DiscoveryRetry, ProviderRegistry, ProviderSettings};
+use crate::auth::validation_policy::TokenValidationPolicy;
 mod schema;
 mod database;
 mod processes;
@@ -21,11 +27,18 @@ mod to_do;
 mod json_serialization;
 mod views;
 mod middleware; 
-use crate::middleware::request_logger::RequestLogger; // Import our custom RequestLogger middleware explicitly
+mod mailer;
+mod logging;
+use crate::middleware::request_id::AssignRequestId;
+use crate::middleware::request_logger::{RequestLogger, RequestLogSettings}; // Import our custom RequestLogger middleware explicitly
+use crate::models::user::user_utils;
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init();
+    let telemetry = match logging::init() {
+        Ok(telemetry) => telemetry,
+        Err(e) => panic!("Critical error: Could not configure logging: {}", e),
+    };
     info!("Starting Actix Web application...");
 
     // Load Keycloak configuration from environment variables with default values
@@ -40,18 +53,67 @@ async fn main() -> std::io::Result<()> {
     info!("Using Keycloak Realm: {}", keycloak_realm);
     info!("Using Keycloak Client ID: {}", keycloak_client_id);
 
-    // Construct the Keycloak base URL for OpenID Connect discovery
-    let keycloak_openid_base_url = format!("{}/realms/{}", keycloak_auth_server_url.trim_end_matches('/'), keycloak_realm);
-    info!("Constructed Keycloak OpenID Base URL: {}", keycloak_openid_base_url);
+    // Every trusted issuer (one Keycloak realm per customer); defaults to the single realm above
+    let issuers = match IssuerConfig::from_env(&keycloak_auth_server_url, &keycloak_realm, &keycloak_client_id) {
+        Ok(issuers) => issuers,
+        Err(e) => {
+            error!("Invalid issuer configuration: {}", e);
+            panic!("Critical error: Could not read the trusted issuers.")
+        }
+    };
+
+    // Users stored before issuers were recorded belong to the realm configured by KEYCLOAK_* back then
+    let legacy_user_issuer = std::env::var("LEGACY_USER_ISSUER")
+        .unwrap_or_else(|_| format!("{}/realms/{}", keycloak_auth_server_url.trim_end_matches('/'), keycloak_realm));
+    match user_utils::assign_legacy_users(&legacy_user_issuer) {
+        Ok(0) => {},
+        Ok(assigned) => info!("Assigned {} pre-existing user(s) to their issuer.", assigned),
+        Err(e) => error!("Could not assign pre-existing users to their issuer: {}", e),
+    }
 
-    let jwks_uri: String = match fetch_keycloak_openid_config(&keycloak_openid_base_url).await {
-        Ok(uri) => uri,
+    // Token validation rules are parsed now so bad settings fail fast; each issuer is bound after its discovery
+    let mut provider_settings = Vec::new();
+    for issuer in &issuers {
+        let token_policy = match TokenValidationPolicy::from_env(issuer) {
+            Ok(policy) => policy,
+            Err(e) => {
+                error!("Invalid token validation policy for issuer '{}': {}", issuer.name, e);
+                panic!("Critical error: Could not build the token validation policy.")
+            }
+        };
+
+        let introspection_settings = match IntrospectionSettings::from_env(issuer) {
+            Ok(settings) => settings,
+            Err(e) => {
+                error!("Invalid token introspection settings: {}", e);
+                panic!("Critical error: Could not configure token introspection.")
+            }
+        };
+
+        provider_settings.push(ProviderSettings {
+            policy: token_policy,
+            introspection: introspection_settings,
+            retry: DiscoveryRetry::from_env(),
+        });
+    }
+
+    // Local login signs its own tokens; its public keys are published at /auth/jwks
+    let local_token_issuer = match LocalTokenIssuer::from_env() {
+        Ok(issuer) => issuer,
         Err(e) => {
-            error!("Failed to fetch JWKS URI from Keycloak: {}", e);
-            panic!("Critical error: Could not obtain JWKS URI from Keycloak.")
+            error!("Invalid local signing key configuration: {}", e);
+            panic!("Critical error: Could not load the local signing keys.")
         }
     };
-    let jwks_uri_data = web::Data::new(jwks_uri); // Store JWKS URI in app state
+    if issuers.is_empty() && !local_token_issuer.is_enabled() {
+        error!("OIDC_ISSUERS is empty and LOCAL_SIGNING_KEYS is not set, so no token could ever be accepted.");
+        panic!("Critical error: No token issuer is configured.")
+    }
+
+    // Discovery runs in the background so the server can start before Keycloak is ready
+    let provider_registry = web::Data::new(ProviderRegistry::new(issuers, local_token_issuer.provider()));
+    let local_token_issuer = web::Data::new(local_token_issuer);
+    let provider_registry_data = provider_registry.clone();
 
     // Create KeycloakClientConfig data for frontend and other parts of the backend
     let keycloak_client_config = web::Data::new(KeycloakClientConfig {
@@ -60,17 +122,48 @@ async fn main() -> std::io::Result<()> {
         client_id: keycloak_client_id.clone(),
     });
 
-    HttpServer::new(move || {
-        let jwks_uri_data = jwks_uri_data.clone(); // Clone for each worker
+    // Settings for the server-side login; tokens stay on the server and the browser gets a session cookie
+    let oidc_login_settings = web::Data::new(OidcLoginSettings::from_env());
+
+    // Limits on failed local logins; the counts live in the database so all workers share them
+    let login_throttle_policy = web::Data::new(LoginThrottlePolicy::from_env());
+    let password_policy = web::Data::new(PasswordPolicy::from_env());
+
+    // Email verification and password reset for local accounts
+    let account_token_signer = web::Data::new(AccountTokenSigner::from_env());
+    let mailer: web::Data<dyn mailer::Mailer> = match mailer::from_env() {
+        Ok(mailer) => web::Data::from(mailer),
+        Err(e) => {
+            error!("Invalid mailer configuration: {}", e);
+            panic!("Critical error: Could not configure the mailer.")
+        }
+    };
+
+    // Headers and fields kept out of the request log
+    let request_log_settings = RequestLogSettings::from_env();
+
+    let server = HttpServer::new(move || {
+        let provider_registry = provider_registry_data.clone(); // Clone for each worker
         let keycloak_client_config = keycloak_client_config.clone(); // Clone for each worker
+        let oidc_login_settings = oidc_login_settings.clone(); // Clone for each worker
+        let local_token_issuer = local_token_issuer.clone(); // Clone for each worker
+        let login_throttle_policy = login_throttle_policy.clone(); // Clone for each worker
+        let password_policy = password_policy.clone(); // Clone for each worker
+        let account_token_signer = account_token_signer.clone(); // Clone for each worker
+        let mailer = mailer.clone(); // Clone for each worker
+        let request_log_settings = request_log_settings.clone(); // Clone for each worker
         info!("Setting up application routes and middleware.");
         let app = App::new()
-            .app_data(jwks_uri_data.clone()) // Add JWKS URI to app data
+            .app_data(provider_registry.clone()) // Add the trusted issuers (discovery, JWKS, policy) to app data
             .app_data(keycloak_client_config.clone()) // Add Keycloak client config to app data
-            .service(fs::Files::new("/javascript", "./javascript").show_files_listing()) // Serve static files
-            .service(fs::Files::new("/css", "./css").show_files_listing()) // Serve CSS files
-            .service(fs::Files::new("/templates", "./templates").show_files_listing()) // Serve templates (including header.html)
-            .wrap(RequestLogger) // Use our custom RequestLogger middleware
+            .app_data(oidc_login_settings.clone()) // Add the server-side login settings to app data
+            .app_data(local_token_issuer.clone()) // Add the local token issuer to app data
+            .app_data(login_throttle_policy.clone()) // Add the failed login limits to app data
+            .app_data(password_policy.clone()) // Add the password rules for registration to app data
+            .app_data(account_token_signer.clone()) // Add the email verification and reset token signer to app data
+            .app_data(mailer.clone()) // Add the mailer to app data
+            .wrap(RequestLogger::new(request_log_settings)) // Log each request with its secrets redacted
+            .wrap(AssignRequestId) // Outermost, so every log line of the request has its ID
 
             .configure(move |cfg| {
                 views::views_factory(cfg)
@@ -78,6 +171,17 @@ async fn main() -> std::io::Result<()> {
         return app
     })
     .bind(std::env::var("BIND_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8000".to_string()))?
-    .run()
-    .await
+    .run();
+
+    ProviderRegistry::spawn_discovery(provider_registry.clone(), provider_settings, server.handle());
+    // Spans still held by the exporter are sent even if the server stopped with an error
+    let result = server.await;
+    telemetry.shutdown();
+    result?;
+
+    if !provider_registry.any_ready() {
+        error!("Server stopped before OIDC discovery succeeded.");
+        return Err(std::io::Error::other("OIDC discovery did not succeed"));
+    }
+    Ok(())
 }
@@ -0,0 +1,96 @@
+use std::rc::Rc;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, Error, HttpResponse};
+use futures_util::future::{self, LocalBoxFuture, Ready};
+use tracing::{info, warn, error};
+
+use crate::auth;
+use crate::auth::provider::ProviderRegistry;
+use crate::auth::validation_policy::TokenRejection;
+use crate::json_serialization::error_response::ErrorResponse;
+
+/// Requires a valid token, personal access token or session on every request to the scope it wraps,
+/// including paths no route of the scope matches.
+///
+/// ```rust
+/// web::scope("/api").wrap(Authenticate)
+/// ```
+pub struct Authenticate;
+
+impl<S, B> Transform<S, ServiceRequest> for Authenticate
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuthenticateService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(AuthenticateService { service: Rc::new(service) })
+    }
+}
+
+/// The service built by `Authenticate`.
+///
+/// Authenticated requests carry their `Claims` in the request extensions. Requests without valid
+/// credentials get a 401, and a 503 while the discovery of their token's issuer has not succeeded.
+pub struct AuthenticateService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticateService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            // Retrieve the trusted issuers from app data; each is degraded until its discovery succeeds
+            let registry = match req.app_data::<web::Data<ProviderRegistry>>() {
+                Some(data) => data.clone(),
+                None => {
+                    error!("OIDC provider registry not found in application data.");
+                    return Ok(req.into_response(HttpResponse::InternalServerError().finish()));
+                }
+            };
+
+            // process_token stores the claims in the request extensions for the guards and extractors
+            match auth::process_token(req.request(), &registry).await {
+                Ok(claims) => {
+                    info!("Token processed successfully for: {}. User ID: {}", req.path(), claims.sub);
+                    Ok(service.call(req).await?.map_into_boxed_body())
+                },
+                Err(TokenRejection::IssuerUnavailable(issuer)) => {
+                    warn!("Rejecting {} with 503: OIDC discovery for issuer '{}' has not succeeded yet.", req.path(), issuer);
+                    Ok(req.into_response(
+                        HttpResponse::ServiceUnavailable()
+                            .insert_header((RETRY_AFTER, "5"))
+                            .json(ErrorResponse::new("issuer_unavailable", "Authentication is not available yet")),
+                    ))
+                },
+                Err(rejection) => {
+                    warn!("Unauthorized access attempt to {}: {}", req.path(), rejection);
+                    Ok(req.into_response(
+                        HttpResponse::Unauthorized().json(ErrorResponse::new("unauthorized", "A valid token or session is required")),
+                    ))
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,177 @@
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::PayloadError;
+use actix_web::http::header::CONTENT_LENGTH;
+use actix_web::{Error, HttpMessage, HttpResponse};
+use futures_util::future::{self, LocalBoxFuture, Ready};
+use futures_util::{Stream, StreamExt};
+use bytes::Bytes;
+use tracing::warn;
+
+use crate::json_serialization::error_response::ErrorResponse;
+
+/// Caps the size of request bodies on the scope or route it wraps.
+///
+/// A request whose `Content-Length` is over the limit gets a 413 before its body is read. A body
+/// without a length, or with a wrong one, is cut off with `PayloadError::Overflow` once it passes the
+/// limit, which the extractors also answer with 413.
+///
+/// Limits nest: a route can wrap itself in a smaller limit than its scope's, but not a larger one.
+///
+/// ```rust
+/// web::scope("/api").wrap(BodyLimit::new(64 * 1024))
+/// web::post().to(handler).wrap(BodyLimit::new(4 * 1024))
+/// ```
+pub struct BodyLimit {
+    limit: usize,
+}
+
+impl BodyLimit {
+    pub fn new(limit: usize) -> BodyLimit {
+        BodyLimit { limit }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for BodyLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = BodyLimitService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(BodyLimitService { service: Rc::new(service), limit: self.limit })
+    }
+}
+
+/// The service built by `BodyLimit`.
+pub struct BodyLimitService<S> {
+    service: Rc<S>,
+    limit: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for BodyLimitService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let limit = self.limit;
+
+        Box::pin(async move {
+            let declared = req
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok());
+            if let Some(length) = declared.filter(|length| *length > limit as u64) {
+                warn!("Refusing a {} byte body for {}: the limit is {} bytes.", length, req.path(), limit);
+                return Ok(req.into_response(HttpResponse::PayloadTooLarge().json(ErrorResponse::new(
+                    "payload_too_large",
+                    &format!("The request body must not be larger than {} bytes", limit),
+                ))));
+            }
+
+            // Count what is actually received, for chunked bodies and lengths that do not match
+            let limited = req.take_payload().scan(0usize, move |received, chunk| {
+                let chunk = chunk.and_then(|bytes| {
+                    *received += bytes.len();
+                    if *received > limit { Err(PayloadError::Overflow) } else { Ok(bytes) }
+                });
+                future::ready(Some(chunk))
+            });
+            let limited: Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>> = Box::pin(limited);
+            req.set_payload(Payload::from(limited));
+
+            Ok(service.call(req).await?.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{web, App};
+
+    async fn echo(body: web::Bytes) -> HttpResponse {
+        HttpResponse::Ok().body(body)
+    }
+
+    #[actix_web::test]
+    async fn bodies_within_the_limit_pass() {
+        let app = init_service(App::new().wrap(BodyLimit::new(8)).route("/", web::post().to(echo))).await;
+
+        let res = call_service(&app, TestRequest::post().uri("/").set_payload("12345678").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(read_body(res).await, "12345678");
+    }
+
+    #[actix_web::test]
+    async fn a_content_length_over_the_limit_is_refused_before_reading() {
+        let app = init_service(App::new().wrap(BodyLimit::new(8)).route("/", web::post().to(echo))).await;
+
+        let req = TestRequest::post().uri("/").insert_header((CONTENT_LENGTH, "9")).set_payload("123456789").to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        // The JSON error comes from the middleware; the extractor answers an overflow in plain text
+        let body: serde_json::Value = serde_json::from_slice(&read_body(res).await).unwrap();
+        assert_eq!(body["error"], "payload_too_large");
+    }
+
+    #[actix_web::test]
+    async fn a_body_without_a_length_is_cut_off_at_the_limit() {
+        let app = init_service(App::new().wrap(BodyLimit::new(8)).route("/", web::post().to(echo))).await;
+
+        // As with chunked transfer encoding, only the received bytes show the size
+        let mut req = TestRequest::post().uri("/").set_payload("123456789").to_request();
+        req.headers_mut().remove(CONTENT_LENGTH);
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn a_content_length_below_the_body_size_does_not_lift_the_limit() {
+        let app = init_service(App::new().wrap(BodyLimit::new(8)).route("/", web::post().to(echo))).await;
+
+        let mut req = TestRequest::post().uri("/").set_payload("123456789").to_request();
+        req.headers_mut().insert(CONTENT_LENGTH, "4".parse().unwrap());
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn a_route_can_tighten_the_limit_of_its_scope() {
+        let app = init_service(
+            App::new().service(
+                web::scope("")
+                    .wrap(BodyLimit::new(16))
+                    .route("/small", web::post().to(echo).wrap(BodyLimit::new(4)))
+                    .route("/large", web::post().to(echo)),
+            ),
+        )
+        .await;
+
+        let res = call_service(&app, TestRequest::post().uri("/small").set_payload("12345").to_request()).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let res = call_service(&app, TestRequest::post().uri("/large").set_payload("12345").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
@@ -1 +1,4 @@
-pub mod request_logger;
\ No newline at end of file
+pub mod authentication;
+pub mod body_limit;
+pub mod request_id;
+pub mod request_logger;
@@ -0,0 +1,195 @@
+use std::rc::Rc;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{Error, HttpMessage};
+use futures_util::future::{self, LocalBoxFuture, Ready};
+use uuid::Uuid;
+
+/// The header a request ID is accepted from and returned in.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest request ID accepted from a client; longer ones are replaced by a new ID.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    /// The ID of the request whose future is being polled, for its error bodies and outgoing calls.
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// The ID of a request, stored in its extensions by `AssignRequestId`.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The ID of the request being handled, if any.
+///
+/// # Returns
+/// (Option<String>): the ID, or None outside of a request, e.g. in background refreshes
+pub fn current() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Adds the ID of the current request to a call to the identity provider, so both logs can be matched.
+///
+/// # Arguments
+/// * request (reqwest::RequestBuilder): the outgoing request
+///
+/// # Returns
+/// (reqwest::RequestBuilder): the request, with `X-Request-Id` if a request is being handled
+pub fn propagate(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match current() {
+        Some(id) => request.header(REQUEST_ID_HEADER.as_str(), id),
+        None => request,
+    }
+}
+
+/// Only IDs that are safe to copy into log lines and headers are accepted from clients.
+fn accepted(value: &HeaderValue) -> Option<String> {
+    let id = value.to_str().ok()?;
+    let safe = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/'));
+    if safe { Some(id.to_string()) } else { None }
+}
+
+/// Gives every request an ID: the client's `X-Request-Id` if it is acceptable, a new UUID otherwise.
+///
+/// The ID is stored in the request extensions as `RequestId`, added to every log line written while
+/// the request is handled and returned in the `X-Request-Id` response header. Wrap it outermost so the
+/// request log has it too.
+pub struct AssignRequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for AssignRequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AssignRequestIdService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(AssignRequestIdService { service: Rc::new(service) })
+    }
+}
+
+/// The service built by `AssignRequestId`.
+pub struct AssignRequestIdService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AssignRequestIdService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let id = req
+            .headers()
+            .get(&REQUEST_ID_HEADER)
+            .and_then(accepted)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        req.extensions_mut().insert(RequestId(id.clone()));
+
+        Box::pin(CURRENT_REQUEST_ID.scope(id.clone(), async move {
+            let mut res = service.call(req).await?.map_into_boxed_body();
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                res.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            Ok(res)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use crate::json_serialization::error_response::ErrorResponse;
+
+    fn accepts(id: &str) -> bool {
+        accepted(&HeaderValue::from_str(id).unwrap()).is_some()
+    }
+
+    /// The `X-Request-Id` a request with `id` is answered with.
+    async fn assigned_id(id: Option<&str>) -> String {
+        let app = init_service(
+            App::new()
+                .wrap(AssignRequestId)
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let mut req = TestRequest::get().uri("/");
+        if let Some(id) = id {
+            req = req.insert_header((REQUEST_ID_HEADER, id));
+        }
+        let res = call_service(&app, req.to_request()).await;
+        res.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn client_ids_within_the_allow_list_are_accepted() {
+        assert!(accepts("f47ac10b-58cc-4372-a567-0e02b2c3d479"));
+        assert!(accepts("gateway/req_42.7:a"));
+        assert!(accepts(&"a".repeat(MAX_REQUEST_ID_LENGTH)));
+    }
+
+    #[test]
+    fn empty_long_or_unsafe_client_ids_are_refused() {
+        assert!(!accepts(""));
+        assert!(!accepts(&"a".repeat(MAX_REQUEST_ID_LENGTH + 1)));
+        assert!(!accepts("id with spaces"));
+        assert!(!accepts("id\"quoted\""));
+        assert!(!accepts("id;other=1"));
+        assert!(!accepts("%0d%0aInjected: header"));
+    }
+
+    #[actix_web::test]
+    async fn an_acceptable_client_id_is_echoed() {
+        assert_eq!(assigned_id(Some("gateway-42")).await, "gateway-42");
+    }
+
+    #[actix_web::test]
+    async fn requests_without_an_acceptable_id_get_a_new_uuid() {
+        let missing = assigned_id(None).await;
+        let refused = assigned_id(Some("id with spaces")).await;
+
+        assert!(Uuid::parse_str(&missing).is_ok());
+        assert!(Uuid::parse_str(&refused).is_ok());
+        assert_ne!(missing, refused);
+    }
+
+    #[actix_web::test]
+    async fn error_bodies_carry_the_request_id() {
+        let app = init_service(
+            App::new()
+                .wrap(AssignRequestId)
+                .route("/", web::get().to(|| async { HttpResponse::NotFound().json(ErrorResponse::new("not_found", "No such item")) })),
+        )
+        .await;
+        let req = TestRequest::get().uri("/").insert_header((REQUEST_ID_HEADER, "gateway-42")).to_request();
+        let body: serde_json::Value = read_body_json(call_service(&app, req).await).await;
+
+        assert_eq!(body["request_id"], "gateway-42");
+        assert_eq!(current(), None);
+    }
+}
@@ -1,17 +1,148 @@
 use actix_web::{
     dev::{Service, ServiceRequest, ServiceResponse, Payload},
-    Error, web::Bytes, HttpResponse,
+    error::PayloadError,
+    http::header::{HeaderMap, CONTENT_TYPE},
+    Error, HttpMessage,
 };
 use futures_util::{
     future::{self, LocalBoxFuture, Ready},
-    StreamExt,
+    stream, Stream, StreamExt,
 };
-use std::{rc::Rc, cell::RefCell};
-use log::{info, warn, error};
-use bytes::{BytesMut, BufMut};
-use crate::auth; // Import the auth module for token processing
+use std::{env, pin::Pin, rc::Rc, cell::RefCell, time::Instant};
+use tracing::field::Empty;
+use tracing::{debug, info, info_span, warn, Instrument};
+use bytes::{Bytes, BytesMut, BufMut};
 use actix_web::body::{MessageBody, BoxBody}; // To ensure B can be BoxBody
-use actix_web::HttpMessage; // For extensions_mut()
+use serde_json::Value;
+
+use crate::middleware::request_id::RequestId;
+
+/// Replaces every redacted header, query parameter and JSON field in the log.
+const REDACTED: &str = "[REDACTED]";
+
+/// Headers left out of the log unless `LOG_REDACT_HEADERS` says otherwise.
+const DEFAULT_REDACT_HEADERS: &str = "authorization,proxy-authorization,cookie,set-cookie,x-api-key";
+
+/// Query parameters and JSON body fields left out of the log unless `LOG_REDACT_FIELDS` says otherwise.
+const DEFAULT_REDACT_FIELDS: &str =
+    "password,current_password,new_password,token,access_token,refresh_token,id_token,client_secret,secret,code,code_verifier,logout_token";
+
+/// How much of a JSON body is read for the log unless `LOG_BODY_CAPTURE_BYTES` says otherwise.
+const DEFAULT_BODY_CAPTURE_BYTES: u64 = 4096;
+
+/// What the request log records and leaves out, read from the environment.
+///
+/// # Attributes
+/// * redact_headers (Vec<String>): lowercase names of the headers whose values are redacted
+/// * redact_fields (Vec<String>): lowercase names of the query parameters and JSON fields, at any depth, whose values are redacted
+/// * capture_bodies (bool): whether JSON request bodies are logged at all
+/// * body_capture_bytes (usize): how much of a JSON body is held back for the log; longer bodies are not logged
+#[derive(Clone, Debug)]
+pub struct RequestLogSettings {
+    pub redact_headers: Vec<String>,
+    pub redact_fields: Vec<String>,
+    pub capture_bodies: bool,
+    pub body_capture_bytes: usize,
+}
+
+impl RequestLogSettings {
+
+    /// Builds the settings from the comma-separated `LOG_REDACT_HEADERS` and `LOG_REDACT_FIELDS`, and
+    /// from `LOG_REQUEST_BODIES` (`true` to log JSON bodies, off by default) and `LOG_BODY_CAPTURE_BYTES`.
+    ///
+    /// # Returns
+    /// (RequestLogSettings): the settings
+    pub fn from_env() -> RequestLogSettings {
+        let names = |name: &str, default: &str| -> Vec<String> {
+            env::var(name)
+                .unwrap_or_else(|_| default.to_string())
+                .split(',')
+                .map(|value| value.trim().to_ascii_lowercase())
+                .filter(|value| !value.is_empty())
+                .collect()
+        };
+        let env_u64 = |name: &str, default: u64| -> u64 {
+            env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+        };
+        RequestLogSettings {
+            redact_headers: names("LOG_REDACT_HEADERS", DEFAULT_REDACT_HEADERS),
+            redact_fields: names("LOG_REDACT_FIELDS", DEFAULT_REDACT_FIELDS),
+            capture_bodies: env::var("LOG_REQUEST_BODIES").map(|value| value.trim() == "true").unwrap_or(false),
+            body_capture_bytes: env_u64("LOG_BODY_CAPTURE_BYTES", DEFAULT_BODY_CAPTURE_BYTES) as usize,
+        }
+    }
+
+    fn redacts_header(&self, name: &str) -> bool {
+        self.redact_headers.iter().any(|header| header.eq_ignore_ascii_case(name))
+    }
+
+    fn redacts_field(&self, name: &str) -> bool {
+        self.redact_fields.iter().any(|field| field.eq_ignore_ascii_case(name))
+    }
+
+    fn headers(&self, headers: &HeaderMap) -> String {
+        let mut header_info = String::new();
+        for (name, value) in headers.iter() {
+            if self.redacts_header(name.as_str()) {
+                header_info.push_str(&format!("{}: {}, ", name, REDACTED));
+            } else {
+                header_info.push_str(&format!("{}: {:?}, ", name, value));
+            }
+        }
+        header_info
+    }
+
+    fn query(&self, query: &str) -> String {
+        query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if self.redacts_field(name) => format!("{}={}", name, REDACTED),
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Only JSON bodies are captured, as other types cannot be redacted field by field.
+    fn captures(&self, content_type: &str) -> bool {
+        self.capture_bodies
+            && (content_type.starts_with("application/json") || content_type.split(';').next().unwrap_or("").ends_with("+json"))
+    }
+
+    /// A captured body is logged with its secret fields redacted. A body longer than the capture limit
+    /// is only logged by size: a cut-off JSON document cannot be parsed, so it cannot be redacted either.
+    fn body(&self, body: &[u8], complete: bool) -> String {
+        if !complete {
+            return format!("<more than {} bytes of JSON>", self.body_capture_bytes);
+        }
+        if body.is_empty() {
+            return String::new();
+        }
+        match serde_json::from_slice::<Value>(body) {
+            Ok(mut value) => {
+                self.redact(&mut value);
+                value.to_string()
+            },
+            Err(_) => format!("<{} bytes of invalid JSON>", body.len()),
+        }
+    }
+
+    fn redact(&self, value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (name, field) in fields.iter_mut() {
+                    if self.redacts_field(name) {
+                        *field = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact(field);
+                    }
+                }
+            },
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact(value)),
+            _ => {},
+        }
+    }
+}
 
 // There are two types of middleware in actix-web.
 // 1. Middleware for the Service: actix_web::dev::Transform
@@ -19,7 +150,18 @@ use actix_web::HttpMessage; // For extensions_mut()
 
 // This is the "factory" for our middleware. It's responsible for creating
 // a new instance of RequestLoggerService for each incoming connection.
-pub struct RequestLogger;
+// It only logs; authentication is done by `Authenticate` on the scopes that need it.
+// Each request runs in a `request` span with its ID, method, path, matched route, the `sub` of its
+// caller once `Authenticate` has checked the token, its status and its latency.
+pub struct RequestLogger {
+    settings: Rc<RequestLogSettings>,
+}
+
+impl RequestLogger {
+    pub fn new(settings: RequestLogSettings) -> RequestLogger {
+        RequestLogger { settings: Rc::new(settings) }
+    }
+}
 
 impl<S, B> actix_web::dev::Transform<S, ServiceRequest> for RequestLogger
 where
@@ -36,12 +178,14 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         future::ok(RequestLoggerService {
             service: Rc::new(RefCell::new(service)),
+            settings: self.settings.clone(),
         })
     }
 }
 
 pub struct RequestLoggerService<S> {
     service: Rc<RefCell<S>>,
+    settings: Rc<RequestLogSettings>,
 }
 
 impl<S, B> Service<ServiceRequest> for RequestLoggerService<S>
@@ -60,78 +204,149 @@ where
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let service = self.service.clone();
+        let settings = self.settings.clone();
+        let request_id = req.extensions().get::<RequestId>().map(|id| id.to_string()).unwrap_or_default();
+        let span = info_span!(
+            "request",
+            request_id = %request_id, method = %req.method(), path = %req.path(),
+            route = Empty, sub = Empty, status = Empty, latency_ms = Empty,
+        );
 
         Box::pin(async move {
-            let (mut http_req, mut payload) = req.into_parts(); // Ensure payload is mutable
+            let started = Instant::now();
+            let (http_req, mut payload) = req.into_parts(); // Ensure payload is mutable
 
-            let request_url = http_req.uri().path().to_string();
             let request_method = http_req.method().to_string();
-            let mut header_info = String::new();
-            for (name, value) in http_req.headers().iter() {
-                header_info.push_str(&format!("{}: {:?}, ", name, value));
-            }
+            let request_url = http_req.uri().path().to_string();
+            let query = settings.query(http_req.query_string());
+            let header_info = settings.headers(http_req.headers());
+            let content_type = http_req
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("")
+                .to_ascii_lowercase();
+            debug!("Incoming request: Method={}, URI={}", request_method, request_url);
 
-            // Read the entire request body
-            let mut body_bytes = BytesMut::new();
-            while let Some(chunk) = payload.next().await {
-                body_bytes.put(chunk?);
-            }
-            let body = body_bytes.freeze();
-
-            let body_str = String::from_utf8_lossy(&body);
-            info!(
-                "Incoming request: Method={}, URI={}, Headers=[{}], Body='{}'",
-                request_method, request_url, header_info, body_str
-            );
-
-            let passed: bool;
-            if request_url.contains("/api/v1/item/") {
-                info!("API item path detected: {}", request_url);
-                // Retrieve jwks_uri from app data
-                let jwks_uri_data = match http_req.app_data::<actix_web::web::Data<String>>() {
-                    Some(data) => data.clone(),
-                    None => {
-                        error!("JWKS URI not found in application data.");
-                        // Handle the error: return InternalServerError immediately
-                        return Ok(ServiceResponse::new(
-                            http_req,
-                            HttpResponse::InternalServerError().finish().map_into_boxed_body(),
-                        ));
-                    }
-                };
-
-                // Pass http_req directly to process_token
-                match auth::process_token(&http_req, jwks_uri_data).await {
-                    Ok(claims) => {
-                        info!("Token processed successfully for: {}. User ID: {}", request_url, claims.sub);
-                        // Store Claims in request extensions
-                        http_req.extensions_mut().insert(claims);
-                        passed = true;
-                    },
-                    Err(message) => {
-                        warn!("Token processing failed for {}: {}", request_url, message);
-                        passed = false;
+            // Hold back at most the capture limit of a JSON body; the rest streams on to the handler
+            let mut body_info = String::new();
+            if settings.captures(&content_type) {
+                let mut prefix: Vec<Bytes> = Vec::new();
+                let mut captured = BytesMut::new();
+                while captured.len() <= settings.body_capture_bytes {
+                    match payload.next().await {
+                        Some(chunk) => {
+                            let chunk = chunk?;
+                            captured.put(chunk.clone());
+                            prefix.push(chunk);
+                        },
+                        None => break,
                     }
                 }
-            } else {
-                passed = true;
+                body_info = settings.body(&captured, captured.len() <= settings.body_capture_bytes);
+
+                // Hand the handler the chunks read so far, followed by the rest of the body
+                let rest: Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>> = Box::pin(stream::iter(prefix.into_iter().map(Ok)).chain(payload));
+                payload = Payload::from(rest);
             }
 
-            let res = if passed {
-                // Reconstruct the payload from the consumed body bytes
-                let new_payload = Payload::from(actix_web::web::Bytes::from(body.clone()));
-                let new_req = ServiceRequest::from_parts(http_req, new_payload);
-                service.call(new_req).await?.map_into_boxed_body()
-            } else {
-                error!("Unauthorized access attempt to: {}", request_url);
-                ServiceResponse::new(http_req, HttpResponse::Unauthorized().finish().map_into_boxed_body())
+            let new_req = ServiceRequest::from_parts(http_req, payload);
+            let result = service.call(new_req).await;
+
+            let duration_ms = started.elapsed().as_micros() as f64 / 1000.0;
+            let status = match &result {
+                Ok(res) => res.status(),
+                Err(e) => e.as_response_error().status_code(),
             };
+            let span = tracing::Span::current();
+            if let Some(route) = result.as_ref().ok().and_then(|res| res.request().match_pattern()) {
+                span.record("route", route.as_str());
+            }
+            span.record("status", status.as_u16());
+            span.record("latency_ms", duration_ms);
+            let message = format!("{} {} -> {} in {:.1} ms", request_method, request_url, status.as_u16(), duration_ms);
+            if status.is_server_error() {
+                warn!(
+                    method = request_method.as_str(), path = request_url.as_str(), query = query.as_str(),
+                    status = status.as_u16(), duration_ms = duration_ms,
+                    headers = header_info.as_str(), body = body_info.as_str(),
+                    "{}", message
+                );
+            } else {
+                info!(
+                    method = request_method.as_str(), path = request_url.as_str(), query = query.as_str(),
+                    status = status.as_u16(), duration_ms = duration_ms,
+                    headers = header_info.as_str(), body = body_info.as_str(),
+                    "{}", message
+                );
+            }
+            Ok(result?.map_into_boxed_body())
+        }.instrument(span))
+    }
+}
 
-            log::info!(
-                "{} {} -> {} (Body: '{}')",
-                request_method, request_url, &res.status(), body_str
-            );
-            Ok(res)
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    fn settings(capture_bodies: bool) -> RequestLogSettings {
+        RequestLogSettings {
+            redact_headers: vec!["authorization".to_string()],
+            redact_fields: vec!["password".to_string()],
+            capture_bodies,
+            body_capture_bytes: 16,
+        }
+    }
+
+    #[test]
+    fn only_json_bodies_are_captured() {
+        let settings = settings(true);
+
+        assert!(settings.captures("application/json"));
+        assert!(settings.captures("application/json; charset=utf-8"));
+        assert!(settings.captures("application/merge-patch+json"));
+        assert!(!settings.captures("application/x-www-form-urlencoded"));
+        assert!(!settings.captures("multipart/form-data; boundary=x"));
+        assert!(!settings.captures("text/plain"));
+        assert!(!settings.captures(""));
+    }
+
+    #[test]
+    fn nothing_is_captured_unless_enabled() {
+        assert!(!settings(false).captures("application/json"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn captured_bodies_are_redacted_or_summarised() {
+        let settings = settings(true);
+
+        assert_eq!(settings.body(br#"{"password":"x"}"#, true), r#"{"password":"[REDACTED]"}"#);
+        assert_eq!(settings.body(b"{", true), "<1 bytes of invalid JSON>");
+        assert_eq!(settings.body(b"{}", false), "<more than 16 bytes of JSON>");
+    }
+
+    #[actix_web::test]
+    async fn handlers_get_the_whole_body_whether_captured_or_not() {
+        let app = init_service(
+            App::new()
+                .wrap(RequestLogger::new(settings(true)))
+                .route("/", web::post().to(|body: web::Bytes| async move { HttpResponse::Ok().body(body) })),
+        )
+        .await;
+
+        for content_type in ["application/json", "text/plain"] {
+            let body = format!(r#"{{"password":"{}"}}"#, "x".repeat(64));
+            let req = TestRequest::post()
+                .uri("/")
+                .insert_header((CONTENT_TYPE, content_type))
+                .set_payload(body.clone())
+                .to_request();
+            let res = call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(read_body(res).await, body);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::time::SystemTime;
+
+use diesel::prelude::*;
+use tracing::error;
+
+use crate::auth::oidc_login::hash_secret;
+use crate::database::establish_connection;
+use crate::schema::account_tombstones;
+
+/// Whether the account a token names was deleted after the token was issued.
+///
+/// # Arguments
+/// * issuer (&str): the token's `iss`
+/// * subject (&str): the token's `sub`
+/// * issued_at (SystemTime): the token's `iat`
+///
+/// # Returns
+/// * (Result<bool, String>): whether the account was deleted since, or an error message if the database call failed
+pub fn was_deleted_since(issuer: &str, subject: &str, issued_at: SystemTime) -> Result<bool, String> {
+    let mut connection = establish_connection();
+    account_tombstones::table
+        .filter(account_tombstones::columns::issuer.eq(issuer))
+        .filter(account_tombstones::columns::subject_hash.eq(hash_secret(subject)))
+        .filter(account_tombstones::columns::deleted_at.ge(issued_at))
+        .count()
+        .get_result::<i64>(&mut connection)
+        .map(|count| count > 0)
+        .map_err(|e| {
+            error!("Error checking account deletions: {}", e);
+            format!("Database error: {}", e)
+        })
+}
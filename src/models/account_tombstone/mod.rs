@@ -0,0 +1,2 @@
+pub mod account_tombstone_utils;
+pub mod new_account_tombstone;
@@ -0,0 +1,17 @@
+use std::time::SystemTime;
+
+use crate::schema::account_tombstones;
+use diesel::Insertable;
+
+/// The record of a deleted account. The subject is hashed so the tombstone holds no personal data.
+#[derive(Insertable)]
+#[diesel(table_name = account_tombstones)]
+pub struct NewAccountTombstone {
+    pub user_id: String,
+    pub issuer: String,
+    pub subject_hash: String,
+    pub requested_by: String,
+    pub items_deleted: i32,
+    pub items_reassigned: i32,
+    pub deleted_at: SystemTime,
+}
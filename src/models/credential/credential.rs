@@ -0,0 +1,46 @@
+extern crate bcrypt;
+use std::time::SystemTime;
+
+use super::super::user::user::User;
+use crate::schema::credentials;
+use bcrypt::verify;
+use diesel::{Identifiable, Queryable};
+
+/// Kind of a credential holding a local password.
+pub const PASSWORD_CREDENTIAL: &str = "password";
+
+/// Kind of a credential linking the user to an identity at an OIDC issuer.
+pub const OIDC_CREDENTIAL: &str = "oidc";
+
+/// One way a user can authenticate.
+#[derive(Queryable, Identifiable, Associations, Clone)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = credentials)]
+pub struct Credential {
+    pub id: i32,
+    pub user_id: String,
+    pub kind: String,
+    pub password_hash: Option<String>,
+    pub issuer: Option<String>,
+    pub subject: Option<String>,
+    pub created_at: SystemTime,
+}
+
+impl Credential {
+
+    /// Checks a password against the stored bcrypt hash.
+    ///
+    /// # Arguments
+    /// * password (&str): the password to check
+    ///
+    /// # Returns
+    /// * (Result<bool, String>): whether the password matches, or an error if this is not a password
+    ///   credential or its hash is malformed
+    pub fn verify_password(&self, password: &str) -> Result<bool, String> {
+        let password_hash = self.password_hash
+            .as_deref()
+            .ok_or_else(|| format!("Credential {} of user {} holds no password", self.id, self.user_id))?;
+        verify(password, password_hash)
+            .map_err(|e| format!("Password hash of user {} cannot be checked: {}", self.user_id, e))
+    }
+}
@@ -0,0 +1,72 @@
+use bcrypt::{hash, DEFAULT_COST};
+use diesel::prelude::*;
+use tracing::{info, error};
+
+use crate::database::establish_connection;
+use crate::models::credential::credential::{Credential, PASSWORD_CREDENTIAL};
+use crate::schema::{credentials, refresh_tokens};
+
+/// Hashes a password for a password credential.
+///
+/// # Arguments
+/// * password (&str): the password, in clear text
+///
+/// # Returns
+/// * (Result<String, String>): the bcrypt hash, or an error message
+pub fn hash_password(password: &str) -> Result<String, String> {
+    hash(password, DEFAULT_COST).map_err(|e| format!("Failed to hash password: {}", e))
+}
+
+/// Loads a user's password credential.
+///
+/// # Arguments
+/// * user_id (&str): the local user ID
+///
+/// # Returns
+/// * (Result<Option<Credential>, String>): the credential, or `None` if the user has no password
+pub fn find_password_credential(user_id: &str) -> Result<Option<Credential>, String> {
+    let mut connection = establish_connection();
+    credentials::table
+        .filter(credentials::columns::user_id.eq(user_id))
+        .filter(credentials::columns::kind.eq(PASSWORD_CREDENTIAL))
+        .first::<Credential>(&mut connection)
+        .optional()
+        .map_err(|e| {
+            error!("Error loading password credential of user {}: {}", user_id, e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Replaces a user's password and revokes their refresh tokens, so sessions started with the old
+/// password end when their access tokens expire.
+///
+/// # Arguments
+/// * user_id (&str): the local user ID
+/// * password (&str): the new password, in clear text
+///
+/// # Returns
+/// * (Result<(), String>): an error message if the user has no password or it could not be stored
+pub fn reset_password(user_id: &str, password: &str) -> Result<(), String> {
+    let password_hash = hash_password(password)?;
+    let mut connection = establish_connection();
+    connection
+        .transaction::<_, diesel::result::Error, _>(|connection| {
+            let updated = diesel::update(
+                credentials::table
+                    .filter(credentials::columns::user_id.eq(user_id))
+                    .filter(credentials::columns::kind.eq(PASSWORD_CREDENTIAL)),
+            )
+            .set(credentials::columns::password_hash.eq(password_hash))
+            .execute(connection)?;
+            if updated == 0 {
+                return Err(diesel::result::Error::NotFound);
+            }
+            diesel::delete(refresh_tokens::table.filter(refresh_tokens::columns::user_id.eq(user_id)))
+                .execute(connection)
+        })
+        .map(|revoked| info!("Reset the password of user {} and revoked {} refresh token(s)", user_id, revoked))
+        .map_err(|e| {
+            error!("Error resetting password of user {}: {}", user_id, e);
+            format!("Database error: {}", e)
+        })
+}
@@ -0,0 +1,3 @@
+pub mod credential;
+pub mod credential_utils;
+pub mod new_credential;
@@ -0,0 +1,38 @@
+use crate::models::credential::credential::{OIDC_CREDENTIAL, PASSWORD_CREDENTIAL};
+use crate::schema::credentials;
+use diesel::Insertable;
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = credentials)]
+pub struct NewCredential {
+    pub user_id: String,
+    pub kind: String,
+    pub password_hash: Option<String>,
+    pub issuer: Option<String>,
+    pub subject: Option<String>,
+}
+
+impl NewCredential {
+
+    /// A local password, already hashed with bcrypt.
+    pub fn password(user_id: &str, password_hash: String) -> NewCredential {
+        NewCredential {
+            user_id: user_id.to_string(),
+            kind: PASSWORD_CREDENTIAL.to_string(),
+            password_hash: Some(password_hash),
+            issuer: None,
+            subject: None,
+        }
+    }
+
+    /// An identity at an OIDC issuer.
+    pub fn oidc(user_id: &str, issuer: &str, subject: &str) -> NewCredential {
+        NewCredential {
+            user_id: user_id.to_string(),
+            kind: OIDC_CREDENTIAL.to_string(),
+            password_hash: None,
+            issuer: Some(issuer.to_string()),
+            subject: Some(subject.to_string()),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use std::time::SystemTime;
+
+use crate::schema::login_failures;
+use diesel::{Identifiable, Queryable};
+
+/// The failed logins recorded for one username or one client IP.
+#[derive(Queryable, Identifiable, Clone)]
+#[diesel(table_name = login_failures)]
+#[diesel(primary_key(throttle_key))]
+pub struct LoginFailure {
+    pub throttle_key: String,
+    pub failures: i32,
+    pub last_failure_at: SystemTime,
+    pub locked_until: Option<SystemTime>,
+}
@@ -0,0 +1,104 @@
+use std::time::{Duration, SystemTime};
+
+use diesel::prelude::*;
+use tracing::{warn, error};
+
+use crate::database::establish_connection;
+use crate::models::login_failure::login_failure::LoginFailure;
+use crate::schema::login_failures;
+
+/// Loads the failures recorded for the given keys.
+///
+/// # Arguments
+/// * keys (&[String]): the throttle keys of the login
+///
+/// # Returns
+/// * (Result<Vec<LoginFailure>, String>): the recorded failures, or an error message if the database call failed
+pub fn find_failures(keys: &[String]) -> Result<Vec<LoginFailure>, String> {
+    let mut connection = establish_connection();
+    login_failures::table
+        .filter(login_failures::columns::throttle_key.eq_any(keys))
+        .load::<LoginFailure>(&mut connection)
+        .map_err(|e| {
+            error!("Error loading login failures: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Counts one more failed login for a key, locking it once it reaches `max_failures`.
+///
+/// Failures older than `window` are forgotten, so the count starts again after a quiet period; keys
+/// that have been quiet that long are dropped.
+///
+/// # Arguments
+/// * key (&str): the throttle key
+/// * max_failures (i32): the failures that lock the key
+/// * window (Duration): how long a failure is remembered
+/// * lockout (Duration): how long the key stays locked
+///
+/// # Returns
+/// * (Result<LoginFailure, String>): the updated failures, or an error message if the database call failed
+pub fn record_failure(key: &str, max_failures: i32, window: Duration, lockout: Duration) -> Result<LoginFailure, String> {
+    let mut connection = establish_connection();
+    let now = SystemTime::now();
+    diesel::delete(
+        login_failures::table
+            .filter(login_failures::columns::last_failure_at.lt(now - window))
+            .filter(login_failures::columns::locked_until.is_null().or(login_failures::columns::locked_until.lt(now))),
+    )
+        .execute(&mut connection)
+        .map_err(|e| format!("Database error: {}", e))?;
+    connection
+        .transaction::<LoginFailure, diesel::result::Error, _>(|connection| {
+            // Make sure the row exists so concurrent failures serialise on its lock
+            diesel::insert_into(login_failures::table)
+                .values(login_failures::columns::throttle_key.eq(key))
+                .on_conflict_do_nothing()
+                .execute(connection)?;
+            let current = login_failures::table
+                .find(key)
+                .for_update()
+                .first::<LoginFailure>(connection)?;
+
+            let failures = if current.failures > 0 && current.last_failure_at + window > now {
+                current.failures + 1
+            } else {
+                1
+            };
+            let locked_until = if failures >= max_failures {
+                warn!("Login locked for {} after {} failed attempts.", key, failures);
+                Some(now + lockout)
+            } else {
+                current.locked_until.filter(|locked_until| *locked_until > now)
+            };
+            diesel::update(login_failures::table.find(key))
+                .set((
+                    login_failures::columns::failures.eq(failures),
+                    login_failures::columns::last_failure_at.eq(now),
+                    login_failures::columns::locked_until.eq(locked_until),
+                ))
+                .get_result::<LoginFailure>(connection)
+        })
+        .map_err(|e| {
+            error!("Error recording login failure: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Forgets the failures of a key after a successful login.
+///
+/// # Arguments
+/// * key (&str): the throttle key
+///
+/// # Returns
+/// * (Result<(), String>): an error message if the database call failed
+pub fn clear_failures(key: &str) -> Result<(), String> {
+    let mut connection = establish_connection();
+    diesel::delete(login_failures::table.find(key))
+        .execute(&mut connection)
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Error clearing login failures: {}", e);
+            format!("Database error: {}", e)
+        })
+}
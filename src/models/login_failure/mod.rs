@@ -0,0 +1,2 @@
+pub mod login_failure;
+pub mod login_failure_utils;
@@ -1,2 +1,8 @@
+pub mod account_tombstone;
+pub mod credential;
 pub mod item;
+pub mod login_failure;
+pub mod personal_access_token;
+pub mod refresh_token;
+pub mod session;
 pub mod user;
@@ -0,0 +1,29 @@
+use std::time::SystemTime;
+
+use crate::schema::personal_access_tokens;
+use diesel::Insertable;
+use uuid::Uuid;
+
+#[derive(Insertable)]
+#[diesel(table_name = personal_access_tokens)]
+pub struct NewPersonalAccessToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: String,
+    pub expires_at: SystemTime,
+}
+
+impl NewPersonalAccessToken {
+    pub fn new(user_id: String, name: String, token_hash: String, scopes: String, expires_at: SystemTime) -> NewPersonalAccessToken {
+        NewPersonalAccessToken {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            name,
+            token_hash,
+            scopes,
+            expires_at,
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use std::time::SystemTime;
+
+use super::super::user::user::User;
+use crate::schema::personal_access_tokens;
+use diesel::{Identifiable, Queryable};
+
+/// A personal access token; only the hash of the token is stored.
+#[derive(Queryable, Identifiable, Associations, Clone)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = personal_access_tokens)]
+pub struct PersonalAccessToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: String,
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+    pub last_used_at: Option<SystemTime>,
+}
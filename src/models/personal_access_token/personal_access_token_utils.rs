@@ -0,0 +1,178 @@
+use std::time::SystemTime;
+
+use diesel::prelude::*;
+use tracing::error;
+
+use crate::database::establish_connection;
+use crate::models::personal_access_token::new_personal_access_token::NewPersonalAccessToken;
+use crate::models::personal_access_token::personal_access_token::PersonalAccessToken;
+use crate::models::user::user::User;
+use crate::schema::{personal_access_tokens, users};
+
+/// Stores a new personal access token.
+///
+/// # Arguments
+/// * new_token (&NewPersonalAccessToken): the token to store, with the hash of its secret
+///
+/// # Returns
+/// * (Result<Option<PersonalAccessToken>, String>): the stored token, or `None` if the user already has a token with that name
+pub fn create_token(new_token: &NewPersonalAccessToken) -> Result<Option<PersonalAccessToken>, String> {
+    let mut connection = establish_connection();
+    match diesel::insert_into(personal_access_tokens::table)
+        .values(new_token)
+        .get_result::<PersonalAccessToken>(&mut connection)
+    {
+        Ok(token) => Ok(Some(token)),
+        Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)) => Ok(None),
+        Err(e) => {
+            error!("Error creating personal access token: {}", e);
+            Err(format!("Database error: {}", e))
+        }
+    }
+}
+
+/// Lists a user's personal access tokens, newest first.
+///
+/// # Arguments
+/// * user_id (&str): the owner of the tokens
+///
+/// # Returns
+/// * (Result<Vec<PersonalAccessToken>, String>): the tokens, or an error message
+pub fn list_tokens(user_id: &str) -> Result<Vec<PersonalAccessToken>, String> {
+    let mut connection = establish_connection();
+    personal_access_tokens::table
+        .filter(personal_access_tokens::columns::user_id.eq(user_id))
+        .order(personal_access_tokens::columns::created_at.desc())
+        .load::<PersonalAccessToken>(&mut connection)
+        .map_err(|e| {
+            error!("Error loading personal access tokens: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Revokes one of a user's personal access tokens.
+///
+/// # Arguments
+/// * user_id (&str): the owner of the token; other users' tokens are never touched
+/// * id (&str): the ID of the token
+///
+/// # Returns
+/// * (Result<bool, String>): whether a token was revoked
+pub fn delete_token(user_id: &str, id: &str) -> Result<bool, String> {
+    let mut connection = establish_connection();
+    delete_user_token(&mut connection, user_id, id).map_err(|e| {
+        error!("Error revoking personal access token: {}", e);
+        format!("Database error: {}", e)
+    })
+}
+
+fn delete_user_token(connection: &mut PgConnection, user_id: &str, id: &str) -> QueryResult<bool> {
+    diesel::delete(
+        personal_access_tokens::table
+            .filter(personal_access_tokens::columns::id.eq(id))
+            .filter(personal_access_tokens::columns::user_id.eq(user_id)),
+    )
+    .execute(connection)
+    .map(|deleted| deleted > 0)
+}
+
+/// Finds an unexpired token by the hash of its secret, with its owner, and records that it was used.
+///
+/// # Arguments
+/// * token_hash (&str): the hash of the presented token
+///
+/// # Returns
+/// * (Result<Option<(PersonalAccessToken, User)>, String>): the token and its owner, if the token is valid
+pub fn use_token(token_hash: &str) -> Result<Option<(PersonalAccessToken, User)>, String> {
+    let mut connection = establish_connection();
+    find_usable_token(&mut connection, token_hash, SystemTime::now()).map_err(|e| {
+        error!("Error loading personal access token: {}", e);
+        format!("Database error: {}", e)
+    })
+}
+
+fn find_usable_token(
+    connection: &mut PgConnection,
+    token_hash: &str,
+    now: SystemTime,
+) -> QueryResult<Option<(PersonalAccessToken, User)>> {
+    diesel::update(
+        personal_access_tokens::table
+            .filter(personal_access_tokens::columns::token_hash.eq(token_hash))
+            .filter(personal_access_tokens::columns::expires_at.gt(now)),
+    )
+    .set(personal_access_tokens::columns::last_used_at.eq(now))
+    .get_result::<PersonalAccessToken>(connection)
+    .optional()
+    .and_then(|token| match token {
+        Some(token) => users::table
+            .find(&token.user_id)
+            .first::<User>(connection)
+            .map(|user| Some((token, user))),
+        None => Ok(None),
+    })
+}
+
+// These run against the database at DATABASE_URL, inside a transaction that is rolled back:
+// cargo test -- --ignored
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::database::try_establish_connection;
+    use crate::models::user::new_user::NewUser;
+
+    fn with_token(expires_at: SystemTime, test: impl FnOnce(&mut PgConnection, &NewPersonalAccessToken)) {
+        let mut connection = try_establish_connection().unwrap();
+        connection.test_transaction::<_, diesel::result::Error, _>(|connection| {
+            let user = NewUser::new("pat-test-user".to_string(), "pat-test@example.com".to_string());
+            diesel::insert_into(users::table).values(&user).execute(connection)?;
+            let token = NewPersonalAccessToken::new(
+                user.id.clone(), "ci".to_string(), "pat-test-hash".to_string(), "items:read".to_string(), expires_at,
+            );
+            diesel::insert_into(personal_access_tokens::table).values(&token).execute(connection)?;
+            test(connection, &token);
+            Ok(())
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn unexpired_tokens_are_found_and_marked_used() {
+        with_token(SystemTime::now() + Duration::from_secs(60), |connection, token| {
+            let (found, user) = find_usable_token(connection, "pat-test-hash", SystemTime::now()).unwrap().unwrap();
+
+            assert_eq!(found.id, token.id);
+            assert_eq!(user.id, token.user_id);
+            assert!(found.last_used_at.is_some());
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn expired_tokens_are_not_found() {
+        with_token(SystemTime::now() - Duration::from_secs(1), |connection, _| {
+            assert!(find_usable_token(connection, "pat-test-hash", SystemTime::now()).unwrap().is_none());
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn revoked_tokens_are_not_found() {
+        with_token(SystemTime::now() + Duration::from_secs(60), |connection, token| {
+            assert!(delete_user_token(connection, &token.user_id, &token.id).unwrap());
+
+            assert!(find_usable_token(connection, "pat-test-hash", SystemTime::now()).unwrap().is_none());
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn tokens_are_only_revoked_by_their_owner() {
+        with_token(SystemTime::now() + Duration::from_secs(60), |connection, token| {
+            assert!(!delete_user_token(connection, "someone-else", &token.id).unwrap());
+
+            assert!(find_usable_token(connection, "pat-test-hash", SystemTime::now()).unwrap().is_some());
+        });
+    }
+}
@@ -0,0 +1,3 @@
+pub mod new_refresh_token;
+pub mod refresh_token;
+pub mod refresh_token_utils;
@@ -0,0 +1,13 @@
+use std::time::SystemTime;
+
+use crate::schema::refresh_tokens;
+use diesel::Insertable;
+
+#[derive(Insertable)]
+#[diesel(table_name = refresh_tokens)]
+pub struct NewRefreshToken {
+    pub token_hash: String,
+    pub family_id: String,
+    pub user_id: String,
+    pub expires_at: SystemTime,
+}
@@ -0,0 +1,19 @@
+use std::time::SystemTime;
+
+use super::super::user::user::User;
+use crate::schema::refresh_tokens;
+use diesel::{Identifiable, Queryable};
+
+/// A refresh token issued by local login; only its hash is stored.
+#[derive(Queryable, Identifiable, Associations, Clone)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = refresh_tokens)]
+#[diesel(primary_key(token_hash))]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub family_id: String,
+    pub user_id: String,
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+    pub used_at: Option<SystemTime>,
+}
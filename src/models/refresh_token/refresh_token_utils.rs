@@ -0,0 +1,211 @@
+use std::time::SystemTime;
+
+use diesel::prelude::*;
+use tracing::{info, warn, error};
+
+use crate::database::establish_connection;
+use crate::models::refresh_token::new_refresh_token::NewRefreshToken;
+use crate::models::refresh_token::refresh_token::RefreshToken;
+use crate::schema::refresh_tokens;
+
+/// What happened to a refresh token presented for rotation.
+pub enum RotationOutcome {
+    /// The token was valid; it is now used up and the new token replaces it.
+    Rotated(RefreshToken),
+    /// The token is unknown, or its family has been revoked.
+    Unknown,
+    /// The token has expired.
+    Expired,
+    /// The token had already been used, so its whole family was revoked.
+    Reused,
+}
+
+/// Stores a refresh token, dropping tokens that have expired.
+///
+/// # Arguments
+/// * new_token (&NewRefreshToken): the token to store
+///
+/// # Returns
+/// * (Result<(), String>): an error message if the database call failed
+pub fn create_refresh_token(new_token: &NewRefreshToken) -> Result<(), String> {
+    let mut connection = establish_connection();
+    diesel::delete(refresh_tokens::table.filter(refresh_tokens::columns::expires_at.lt(SystemTime::now())))
+        .execute(&mut connection)
+        .map_err(|e| format!("Database error: {}", e))?;
+    diesel::insert_into(refresh_tokens::table)
+        .values(new_token)
+        .execute(&mut connection)
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Error creating refresh token: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Uses up a refresh token and stores its replacement in the same family.
+///
+/// # Arguments
+/// * token_hash (&str): the hash of the presented refresh token
+/// * new_token_hash (&str): the hash of the replacement
+/// * expires_at (SystemTime): when the replacement expires
+///
+/// # Returns
+/// * (Result<RotationOutcome, String>): the outcome, or an error message if the database call failed
+pub fn rotate_refresh_token(token_hash: &str, new_token_hash: &str, expires_at: SystemTime) -> Result<RotationOutcome, String> {
+    let mut connection = establish_connection();
+    connection
+        .transaction::<RotationOutcome, diesel::result::Error, _>(|connection| rotate(connection, token_hash, new_token_hash, expires_at))
+        .map_err(|e| {
+            error!("Error rotating refresh token: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+fn rotate(connection: &mut PgConnection, token_hash: &str, new_token_hash: &str, expires_at: SystemTime) -> QueryResult<RotationOutcome> {
+    let token = refresh_tokens::table
+        .find(token_hash)
+        .for_update()
+        .first::<RefreshToken>(connection)
+        .optional()?;
+    let token = match token {
+        Some(token) => token,
+        None => return Ok(RotationOutcome::Unknown),
+    };
+    if token.used_at.is_some() {
+        let revoked = delete_family(connection, &token.family_id)?;
+        warn!("Refresh token reused for user {}; revoked its family of {} token(s).", token.user_id, revoked);
+        return Ok(RotationOutcome::Reused);
+    }
+    if token.expires_at <= SystemTime::now() {
+        return Ok(RotationOutcome::Expired);
+    }
+
+    diesel::update(refresh_tokens::table.find(token_hash))
+        .set(refresh_tokens::columns::used_at.eq(SystemTime::now()))
+        .execute(connection)?;
+    diesel::insert_into(refresh_tokens::table)
+        .values(&NewRefreshToken {
+            token_hash: new_token_hash.to_string(),
+            family_id: token.family_id.clone(),
+            user_id: token.user_id.clone(),
+            expires_at,
+        })
+        .execute(connection)?;
+    info!("Rotated a refresh token of user {}.", token.user_id);
+    Ok(RotationOutcome::Rotated(token))
+}
+
+/// Revokes the refresh token and every other token descended from the same login, e.g. on logout.
+///
+/// # Arguments
+/// * token_hash (&str): the hash of a refresh token of the family, used or not
+///
+/// # Returns
+/// * (Result<usize, String>): the number of tokens revoked, 0 if the token is unknown, or an error message
+pub fn revoke_refresh_family(token_hash: &str) -> Result<usize, String> {
+    let mut connection = establish_connection();
+    revoke_family(&mut connection, token_hash).map_err(|e| {
+        error!("Error revoking refresh tokens: {}", e);
+        format!("Database error: {}", e)
+    })
+}
+
+fn revoke_family(connection: &mut PgConnection, token_hash: &str) -> QueryResult<usize> {
+    let token = refresh_tokens::table.find(token_hash).first::<RefreshToken>(connection).optional()?;
+    match token {
+        Some(token) => {
+            let revoked = delete_family(connection, &token.family_id)?;
+            info!("Revoked {} refresh token(s) of user {}.", revoked, token.user_id);
+            Ok(revoked)
+        },
+        None => Ok(0),
+    }
+}
+
+fn delete_family(connection: &mut PgConnection, family_id: &str) -> QueryResult<usize> {
+    diesel::delete(refresh_tokens::table.filter(refresh_tokens::columns::family_id.eq(family_id))).execute(connection)
+}
+
+// These run against the database at DATABASE_URL, inside a transaction that is rolled back:
+// cargo test -- --ignored
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::database::try_establish_connection;
+    use crate::models::user::new_user::NewUser;
+    use crate::schema::users;
+
+    fn later() -> SystemTime {
+        SystemTime::now() + Duration::from_secs(60)
+    }
+
+    fn with_login(test: impl FnOnce(&mut PgConnection)) {
+        let mut connection = try_establish_connection().unwrap();
+        connection.test_transaction::<_, diesel::result::Error, _>(|connection| {
+            let user = NewUser::new("refresh-test-user".to_string(), "refresh-test@example.com".to_string());
+            diesel::insert_into(users::table).values(&user).execute(connection)?;
+            diesel::insert_into(refresh_tokens::table)
+                .values(&NewRefreshToken {
+                    token_hash: "first".to_string(),
+                    family_id: "family-1".to_string(),
+                    user_id: user.id.clone(),
+                    expires_at: later(),
+                })
+                .execute(connection)?;
+            test(connection);
+            Ok(())
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn refresh_tokens_rotate_within_their_family() {
+        with_login(|connection| {
+            assert!(matches!(rotate(connection, "first", "second", later()).unwrap(), RotationOutcome::Rotated(_)));
+            assert!(matches!(rotate(connection, "second", "third", later()).unwrap(), RotationOutcome::Rotated(_)));
+
+            let family: Vec<RefreshToken> = refresh_tokens::table
+                .filter(refresh_tokens::columns::family_id.eq("family-1"))
+                .load(connection)
+                .unwrap();
+            assert_eq!(family.len(), 3);
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn reusing_a_refresh_token_is_rejected_and_revokes_its_family() {
+        with_login(|connection| {
+            assert!(matches!(rotate(connection, "first", "second", later()).unwrap(), RotationOutcome::Rotated(_)));
+
+            assert!(matches!(rotate(connection, "first", "stolen", later()).unwrap(), RotationOutcome::Reused));
+            assert!(matches!(rotate(connection, "second", "third", later()).unwrap(), RotationOutcome::Unknown));
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn expired_refresh_tokens_are_rejected() {
+        with_login(|connection| {
+            diesel::update(refresh_tokens::table.find("first"))
+                .set(refresh_tokens::columns::expires_at.eq(SystemTime::now() - Duration::from_secs(1)))
+                .execute(connection)
+                .unwrap();
+
+            assert!(matches!(rotate(connection, "first", "second", later()).unwrap(), RotationOutcome::Expired));
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the database at DATABASE_URL"]
+    fn logging_out_revokes_the_whole_family() {
+        with_login(|connection| {
+            assert!(matches!(rotate(connection, "first", "second", later()).unwrap(), RotationOutcome::Rotated(_)));
+
+            assert_eq!(revoke_family(connection, "second").unwrap(), 2);
+            assert!(matches!(rotate(connection, "second", "third", later()).unwrap(), RotationOutcome::Unknown));
+            assert_eq!(revoke_family(connection, "second").unwrap(), 0);
+        });
+    }
+}
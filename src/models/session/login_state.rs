@@ -0,0 +1,16 @@
+use std::time::SystemTime;
+
+use crate::schema::oidc_login_states;
+use diesel::{Insertable, Queryable};
+
+/// A login in progress, stored between the redirect to the identity provider and the callback.
+#[derive(Queryable, Insertable, Clone)]
+#[diesel(table_name = oidc_login_states)]
+pub struct LoginState {
+    pub state_hash: String,
+    pub issuer_name: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub return_to: String,
+    pub expires_at: SystemTime,
+}
@@ -0,0 +1,5 @@
+pub mod login_state;
+pub mod new_logout_revocation;
+pub mod new_session;
+pub mod session;
+pub mod session_utils;
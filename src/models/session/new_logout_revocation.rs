@@ -0,0 +1,15 @@
+use std::time::SystemTime;
+
+use crate::schema::logout_revocations;
+use diesel::Insertable;
+
+/// A logout announced by an identity provider, for a whole user (`subject`) or one of its sessions (`sid`).
+#[derive(Insertable)]
+#[diesel(table_name = logout_revocations)]
+pub struct NewLogoutRevocation {
+    pub issuer: String,
+    pub sid: Option<String>,
+    pub subject: Option<String>,
+    pub revoked_at: SystemTime,
+    pub expires_at: SystemTime,
+}
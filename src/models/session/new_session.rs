@@ -0,0 +1,18 @@
+use std::time::SystemTime;
+
+use crate::schema::sessions;
+use diesel::Insertable;
+
+#[derive(Insertable)]
+#[diesel(table_name = sessions)]
+pub struct NewSession {
+    pub id_hash: String,
+    pub user_id: String,
+    pub issuer_name: String,
+    pub access_token: String,
+    pub access_token_expires_at: SystemTime,
+    pub refresh_token: Option<String>,
+    pub id_token: Option<String>,
+    pub expires_at: SystemTime,
+    pub sid: Option<String>,
+}
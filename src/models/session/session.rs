@@ -0,0 +1,23 @@
+use std::time::SystemTime;
+
+use super::super::user::user::User;
+use crate::schema::sessions;
+use diesel::{Identifiable, Queryable};
+
+/// A browser session; the tokens never leave the server.
+#[derive(Queryable, Identifiable, Associations, Clone)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = sessions)]
+#[diesel(primary_key(id_hash))]
+pub struct Session {
+    pub id_hash: String,
+    pub user_id: String,
+    pub issuer_name: String,
+    pub access_token: String,
+    pub access_token_expires_at: SystemTime,
+    pub refresh_token: Option<String>,
+    pub id_token: Option<String>,
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+    pub sid: Option<String>,
+}
@@ -0,0 +1,254 @@
+use std::time::SystemTime;
+
+use diesel::prelude::*;
+use tracing::{info, error};
+
+use crate::database::establish_connection;
+use crate::models::session::login_state::LoginState;
+use crate::models::session::new_logout_revocation::NewLogoutRevocation;
+use crate::models::session::new_session::NewSession;
+use crate::models::session::session::Session;
+use crate::schema::{logout_revocations, oidc_login_states, sessions, users};
+
+/// Stores a login in progress, dropping logins that were never completed.
+///
+/// # Arguments
+/// * login_state (&LoginState): the state, PKCE verifier and nonce of the login
+///
+/// # Returns
+/// * (Result<(), String>): an error message if the database call failed
+pub fn save_login_state(login_state: &LoginState) -> Result<(), String> {
+    let mut connection = establish_connection();
+    diesel::delete(oidc_login_states::table.filter(oidc_login_states::columns::expires_at.lt(SystemTime::now())))
+        .execute(&mut connection)
+        .map_err(|e| format!("Database error: {}", e))?;
+    diesel::insert_into(oidc_login_states::table)
+        .values(login_state)
+        .execute(&mut connection)
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Error saving login state: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Removes and returns a login in progress, so each `state` can only be redeemed once.
+///
+/// # Arguments
+/// * state_hash (&str): the hash of the `state` parameter
+///
+/// # Returns
+/// * (Result<Option<LoginState>, String>): the login if it exists and has not expired
+pub fn take_login_state(state_hash: &str) -> Result<Option<LoginState>, String> {
+    let mut connection = establish_connection();
+    diesel::delete(oidc_login_states::table.find(state_hash))
+        .get_result::<LoginState>(&mut connection)
+        .optional()
+        .map(|login_state| login_state.filter(|login_state| login_state.expires_at > SystemTime::now()))
+        .map_err(|e| {
+            error!("Error loading login state: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Stores a new session, dropping sessions that have expired.
+///
+/// # Arguments
+/// * new_session (&NewSession): the session to store
+///
+/// # Returns
+/// * (Result<(), String>): an error message if the database call failed
+pub fn create_session(new_session: &NewSession) -> Result<(), String> {
+    let mut connection = establish_connection();
+    let purged = diesel::delete(sessions::table.filter(sessions::columns::expires_at.lt(SystemTime::now())))
+        .execute(&mut connection)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if purged > 0 {
+        info!("Purged {} expired sessions.", purged);
+    }
+    diesel::insert_into(sessions::table)
+        .values(new_session)
+        .execute(&mut connection)
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Error creating session: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Looks up a session by the hash of its cookie value.
+///
+/// # Arguments
+/// * id_hash (&str): the hash of the session cookie
+///
+/// # Returns
+/// * (Result<Option<Session>, String>): the session, if it exists
+pub fn find_session(id_hash: &str) -> Result<Option<Session>, String> {
+    let mut connection = establish_connection();
+    sessions::table
+        .find(id_hash)
+        .first::<Session>(&mut connection)
+        .optional()
+        .map_err(|e| {
+            error!("Error loading session: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Replaces a session's tokens after a refresh.
+///
+/// # Arguments
+/// * id_hash (&str): the hash of the session cookie
+/// * access_token (&str): the new access token
+/// * access_token_expires_at (SystemTime): when the new access token expires
+/// * refresh_token (Option<&str>): the new refresh token, if the provider rotated it
+///
+/// # Returns
+/// * (Result<(), String>): an error message if the database call failed
+pub fn update_session_tokens(
+    id_hash: &str,
+    access_token: &str,
+    access_token_expires_at: SystemTime,
+    refresh_token: Option<&str>,
+) -> Result<(), String> {
+    let mut connection = establish_connection();
+    let session = sessions::table.find(id_hash);
+    let result = match refresh_token {
+        Some(refresh_token) => diesel::update(session)
+            .set((
+                sessions::columns::access_token.eq(access_token),
+                sessions::columns::access_token_expires_at.eq(access_token_expires_at),
+                sessions::columns::refresh_token.eq(refresh_token),
+            ))
+            .execute(&mut connection),
+        None => diesel::update(session)
+            .set((
+                sessions::columns::access_token.eq(access_token),
+                sessions::columns::access_token_expires_at.eq(access_token_expires_at),
+            ))
+            .execute(&mut connection),
+    };
+    result.map(|_| ()).map_err(|e| {
+        error!("Error updating session tokens: {}", e);
+        format!("Database error: {}", e)
+    })
+}
+
+/// Deletes a session.
+///
+/// # Arguments
+/// * id_hash (&str): the hash of the session cookie
+///
+/// # Returns
+/// * (Result<Option<Session>, String>): the deleted session, if it existed
+pub fn delete_session(id_hash: &str) -> Result<Option<Session>, String> {
+    let mut connection = establish_connection();
+    diesel::delete(sessions::table.find(id_hash))
+        .get_result::<Session>(&mut connection)
+        .optional()
+        .map_err(|e| {
+            error!("Error deleting session: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Deletes the browser sessions that belong to one identity provider session.
+///
+/// # Arguments
+/// * issuer_name (&str): the name of the issuer the sessions were started with
+/// * sid (&str): the identity provider's session ID
+///
+/// # Returns
+/// * (Result<usize, String>): the number of sessions deleted
+pub fn delete_sessions_by_sid(issuer_name: &str, sid: &str) -> Result<usize, String> {
+    let mut connection = establish_connection();
+    diesel::delete(
+        sessions::table
+            .filter(sessions::columns::issuer_name.eq(issuer_name))
+            .filter(sessions::columns::sid.eq(sid)),
+    )
+    .execute(&mut connection)
+    .map_err(|e| {
+        error!("Error deleting sessions: {}", e);
+        format!("Database error: {}", e)
+    })
+}
+
+/// Records a logout announced by an identity provider and ends the browser sessions it covers.
+///
+/// # Arguments
+/// * revocation (&NewLogoutRevocation): the revoked `sid` and/or `subject`
+/// * issuer_name (&str): the name of the issuer, as stored on sessions
+///
+/// # Returns
+/// * (Result<usize, String>): the number of browser sessions ended
+pub fn record_logout(revocation: &NewLogoutRevocation, issuer_name: &str) -> Result<usize, String> {
+    let mut connection = establish_connection();
+    connection
+        .transaction::<usize, diesel::result::Error, _>(|connection| {
+            diesel::delete(logout_revocations::table.filter(logout_revocations::columns::expires_at.lt(SystemTime::now())))
+                .execute(connection)?;
+            diesel::insert_into(logout_revocations::table)
+                .values(revocation)
+                .execute(connection)?;
+
+            let mut ended = 0;
+            if let Some(sid) = &revocation.sid {
+                ended += diesel::delete(
+                    sessions::table
+                        .filter(sessions::columns::issuer_name.eq(issuer_name))
+                        .filter(sessions::columns::sid.eq(sid)),
+                )
+                .execute(connection)?;
+            }
+            if let Some(subject) = &revocation.subject {
+                let user_ids = users::table
+                    .filter(users::columns::issuer.eq(&revocation.issuer))
+                    .filter(users::columns::subject.eq(subject))
+                    .select(users::columns::id);
+                ended += diesel::delete(sessions::table.filter(sessions::columns::user_id.eq_any(user_ids)))
+                    .execute(connection)?;
+            }
+            Ok(ended)
+        })
+        .map_err(|e| {
+            error!("Error recording logout: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Whether a token has been ended by a logout: its `sid` or its subject was logged out after the
+/// token was issued.
+///
+/// # Arguments
+/// * connection (&mut PgConnection): the database connection
+/// * issuer (&str): the token's `iss`
+/// * sid (Option<&str>): the token's `sid`, if it carries one
+/// * subject (&str): the token's `sub`
+/// * issued_at (SystemTime): the token's `iat`
+///
+/// # Returns
+/// * (Result<bool, String>): whether the token is logged out
+pub fn is_logged_out(
+    connection: &mut PgConnection,
+    issuer: &str,
+    sid: Option<&str>,
+    subject: &str,
+    issued_at: SystemTime,
+) -> Result<bool, String> {
+    let query = logout_revocations::table
+        .filter(logout_revocations::columns::issuer.eq(issuer))
+        .filter(logout_revocations::columns::expires_at.gt(SystemTime::now()))
+        .filter(logout_revocations::columns::revoked_at.ge(issued_at));
+    let count = match sid {
+        Some(sid) => query
+            .filter(logout_revocations::columns::sid.eq(sid).or(logout_revocations::columns::subject.eq(subject)))
+            .count()
+            .get_result::<i64>(connection),
+        None => query.filter(logout_revocations::columns::subject.eq(subject)).count().get_result::<i64>(connection),
+    };
+    count.map(|count| count > 0).map_err(|e| {
+        error!("Error checking logouts: {}", e);
+        format!("Database error: {}", e)
+    })
+}
@@ -0,0 +1,27 @@
+use crate::models::credential::credential::Credential;
+use crate::models::item::item::Item;
+use crate::models::login_failure::login_failure::LoginFailure;
+use crate::models::personal_access_token::personal_access_token::PersonalAccessToken;
+use crate::models::refresh_token::refresh_token::RefreshToken;
+use crate::models::session::session::Session;
+use crate::models::user::user::User;
+
+/// Everything stored about one user, as handed out by a data export.
+///
+/// # Attributes
+/// * user (User): the user's row
+/// * items (Vec<Item>): the user's to-do items
+/// * credentials (Vec<Credential>): how the user can sign in
+/// * personal_access_tokens (Vec<PersonalAccessToken>): the user's personal access tokens
+/// * sessions (Vec<Session>): the user's browser sessions
+/// * refresh_tokens (Vec<RefreshToken>): the refresh tokens of the user's local logins
+/// * login_failures (Option<LoginFailure>): the failed local logins counted against the username
+pub struct AccountData {
+    pub user: User,
+    pub items: Vec<Item>,
+    pub credentials: Vec<Credential>,
+    pub personal_access_tokens: Vec<PersonalAccessToken>,
+    pub sessions: Vec<Session>,
+    pub refresh_tokens: Vec<RefreshToken>,
+    pub login_failures: Option<LoginFailure>,
+}
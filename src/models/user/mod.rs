@@ -1,3 +1,5 @@
+pub mod account_data;
 pub mod new_user;
 pub mod user;
+pub mod user_profile;
 pub mod user_utils;
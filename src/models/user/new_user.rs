@@ -1,26 +1,34 @@
-extern crate bcrypt;
 use crate::schema::users;
-use bcrypt::{hash, DEFAULT_COST};
 use diesel::Insertable;
 use uuid::Uuid;
 
+/// Issuer recorded for accounts registered directly with this application.
+pub const LOCAL_ISSUER: &str = "local";
+
 #[derive(Insertable, Clone)]
 #[table_name = "users"]
 pub struct NewUser {
-    pub id: String,          // <-- Keycloak sub (TEXT PK)
+    pub id: String,          // <-- local ID (TEXT PK), independent of the issuer's sub
     pub username: String,
-    pub email: String,
-    pub password: String,
+    pub email: Option<String>,
+    pub issuer: String,
+    pub subject: String,
+    pub display_name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
 }
 impl NewUser {
-    pub fn new(username: String, email: String, password: String) -> NewUser {
-        let hashed_password: String = hash(password.as_str(), DEFAULT_COST).unwrap();
+    pub fn new(username: String, email: String) -> NewUser {
         let uuid = Uuid::new_v4().to_string();
         return NewUser {
             username,
-            email,
-            password: hashed_password,
+            email: Some(email),
+            issuer: LOCAL_ISSUER.to_string(),
+            subject: uuid.clone(),
             id: uuid,
+            display_name: None,
+            given_name: None,
+            family_name: None,
         };
     }
 }
@@ -1,6 +1,6 @@
-extern crate bcrypt;
+use std::time::SystemTime;
+
 use crate::schema::users;
-use bcrypt::verify;
 use diesel::{Identifiable, Queryable};
 
 #[derive(Queryable, Clone, Identifiable)]
@@ -8,12 +8,40 @@ use diesel::{Identifiable, Queryable};
 pub struct User {
     pub id: String,
     pub username: String,
-    pub email: String,
-    pub password: String,
+    pub email: Option<String>,
+    pub issuer: String,
+    pub subject: String,
+    pub email_verified_at: Option<SystemTime>,
+    pub display_name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub disabled_at: Option<SystemTime>,
 }
 
+#[cfg(test)]
 impl User {
-    pub fn verify(self, password: String) -> bool {
-        return verify(password.as_str(), &self.password).unwrap();
+
+    /// An enabled user `alice` with an unverified `alice@example.com`, for tests.
+    ///
+    /// # Arguments
+    /// * id (&str): the user's ID
+    /// * issuer (&str): the issuer the user belongs to
+    /// * subject (&str): the user's subject at that issuer
+    ///
+    /// # Returns
+    /// (User): the user
+    pub fn for_test(id: &str, issuer: &str, subject: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: "alice".to_string(),
+            email: Some("alice@example.com".to_string()),
+            issuer: issuer.to_string(),
+            subject: subject.to_string(),
+            email_verified_at: None,
+            display_name: None,
+            given_name: None,
+            family_name: None,
+            disabled_at: None,
+        }
     }
 }
@@ -0,0 +1,49 @@
+use crate::auth::processes::Principal;
+use crate::models::user::user::User;
+use crate::schema::users;
+use diesel::AsChangeset;
+
+/// The profile fields of a user that are copied from the identity provider's token claims.
+#[derive(AsChangeset, Clone, Debug, PartialEq)]
+#[diesel(table_name = users)]
+#[diesel(treat_none_as_null = true)]
+pub struct UserProfile {
+    pub username: String,
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+}
+
+impl UserProfile {
+
+    /// Returns the profile currently stored for a user.
+    pub fn of(user: &User) -> UserProfile {
+        UserProfile {
+            username: user.username.clone(),
+            email: user.email.clone(),
+            display_name: user.display_name.clone(),
+            given_name: user.given_name.clone(),
+            family_name: user.family_name.clone(),
+        }
+    }
+
+    /// Applies the claims of a token to a stored profile. A claim the token does not carry leaves the
+    /// field unchanged, since tokens issued with fewer scopes omit profile claims.
+    ///
+    /// # Arguments
+    /// * principal (&Principal): the caller, from the validated token
+    ///
+    /// # Returns
+    /// (UserProfile): the updated profile
+    pub fn merged_with(&self, principal: &Principal) -> UserProfile {
+        let claim = |value: Option<&str>, current: &Option<String>| value.map(str::to_string).or_else(|| current.clone());
+        UserProfile {
+            username: principal.claimed_username().unwrap_or_else(|| self.username.clone()),
+            email: claim(principal.email(), &self.email),
+            display_name: claim(principal.name(), &self.display_name),
+            given_name: claim(principal.given_name(), &self.given_name),
+            family_name: claim(principal.family_name(), &self.family_name),
+        }
+    }
+}
@@ -1,50 +1,533 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use diesel::dsl::count_star;
+use diesel::pg::Pg;
 use diesel::prelude::*;
-use crate::database::establish_connection;
+use diesel::result::DatabaseErrorKind;
+use diesel::result::Error::DatabaseError;
+use crate::database::{establish_connection, try_establish_connection};
+use crate::models::account_tombstone::new_account_tombstone::NewAccountTombstone;
+use crate::models::credential::credential::{Credential, OIDC_CREDENTIAL, PASSWORD_CREDENTIAL};
+use crate::models::item::item::Item;
+use crate::models::login_failure::login_failure::LoginFailure;
+use crate::models::personal_access_token::personal_access_token::PersonalAccessToken;
+use crate::models::refresh_token::refresh_token::RefreshToken;
+use crate::models::session::session::Session;
+use crate::models::user::account_data::AccountData;
+use crate::models::credential::new_credential::NewCredential;
 use crate::models::user::user::User;
-use crate::models::user::new_user::NewUser;
-use crate::schema::users;
-use log::{info, error};
+use crate::models::user::new_user::{NewUser, LOCAL_ISSUER};
+use crate::models::user::user_profile::UserProfile;
+use crate::schema::{account_tombstones, credentials, login_failures, personal_access_tokens, refresh_tokens, sessions, to_do, users};
+use crate::auth::login_throttle;
+use crate::auth::oidc_login::hash_secret;
+use crate::auth::processes::Principal;
+use tracing::{info, warn, error};
+use uuid::Uuid;
+
+/// The password users of the single Keycloak realm were stored with before issuers were recorded.
+const LEGACY_SSO_PASSWORD: &str = "DUMMY_PASSWORD";
 
-pub fn find_or_create_user(user_id: &str, email: &str, username: &str) -> Result<User, String> {
+/// Finds the local user row for an authenticated caller, creating it on first use and keeping its
+/// profile in sync with the token claims.
+///
+/// Users are looked up by the OIDC credential holding the issuer and subject, so the same `sub` in
+/// two realms maps to two users. Rows created before issuers were recorded are never matched here;
+/// `assign_legacy_users` gives them their issuer at startup.
+///
+/// Usernames and email addresses are unique within an issuer, but the identity provider may have
+/// given them to someone else since their previous owner last logged in. Such a stale row gives
+/// them up: its username becomes `<username>~<subject>` and its email is cleared, until its owner
+/// logs in again.
+///
+/// # Arguments
+/// * principal (&Principal): the human user or service client making the request
+///
+/// # Returns
+/// * (Result<User, String>): the local user, or an error message if the database call failed
+pub fn find_or_create_user(principal: &Principal) -> Result<User, String> {
     let mut connection = establish_connection();
+    let issuer = principal.issuer();
+    let subject = principal.subject();
 
-    // Try to find the user by unique_id (which is claims.sub)
-    let user_result = users::table
-        .filter(users::columns::id.eq(user_id))
-        .first::<User>(&mut connection);
+    let user_result = find_user_by_identity(&mut connection, issuer, subject);
 
     match user_result {
         Ok(user) => {
-            info!("Found existing user with unique_id: {}", user_id);
-            Ok(user)
+            info!("Found existing user {} for subject {} of issuer {}", user.id, subject, issuer);
+            sync_profile(&mut connection, user, principal)
         },
+        // Tokens of local accounts are only issued to existing users, so a missing one has been deleted
+        Err(diesel::NotFound) if issuer == LOCAL_ISSUER => Err(format!("Local user {} no longer exists", subject)),
         Err(diesel::NotFound) => {
-            info!("User with unique_id '{}' not found. Creating new user.", user_id);
-            // Create a new user if not found
-            // For password, we'll use a dummy as Keycloak handles authentication
-            let new_user = NewUser::new(
-                username.to_string(),
-                email.to_string(),
-                "DUMMY_PASSWORD".to_string() // Dummy password
-            );
-
-            // Override the generated UUID with the Keycloak user_id (claims.sub)
+            info!("No user for subject '{}' of issuer '{}'. Creating new user.", subject, issuer);
+            // Create a new user if not found; SSO users get no password, only an OIDC credential
+            let new_user = NewUser::new(principal.username(), String::new());
+
+            // The local ID is generated; the issuer and subject link the row to the identity provider.
+            // Service clients and users with incomplete profiles have no email address
             let new_user_with_keycloak_id = NewUser {
-                id: user_id.to_string(),
+                id: Uuid::new_v4().to_string(),
+                email: principal.email().map(str::to_string),
+                issuer: issuer.to_string(),
+                subject: subject.to_string(),
+                display_name: principal.name().map(str::to_string),
+                given_name: principal.given_name().map(str::to_string),
+                family_name: principal.family_name().map(str::to_string),
                 ..new_user
             };
 
-            diesel::insert_into(users::table)
-                .values(&new_user_with_keycloak_id)
-                .get_result::<User>(&mut connection)
-                .map_err(|e| {
+            let inserted = connection.transaction::<User, diesel::result::Error, _>(|connection| {
+                release_taken_profile_fields(
+                    connection, issuer, subject, &new_user_with_keycloak_id.username, new_user_with_keycloak_id.email.as_deref(),
+                )?;
+                let user = diesel::insert_into(users::table)
+                    .values(&new_user_with_keycloak_id)
+                    .get_result::<User>(connection)?;
+                diesel::insert_into(credentials::table)
+                    .values(&NewCredential::oidc(&user.id, issuer, subject))
+                    .execute(connection)?;
+                Ok(user)
+            });
+            match inserted {
+                Ok(user) => Ok(user),
+                // A concurrent request of the same caller created the row first
+                Err(DatabaseError(DatabaseErrorKind::UniqueViolation, info))
+                    if matches!(info.constraint_name(), Some("uc_user_subject") | Some("uc_credentials_oidc")) =>
+                {
+                    find_user_by_identity(&mut connection, issuer, subject).map_err(|e| format!("Database error: {}", e))
+                },
+                Err(e) => {
                     error!("Error creating new user: {}", e);
-                    format!("Failed to create user: {}", e)
-                })
+                    Err(format!("Failed to create user: {}", e))
+                }
+            }
         },
         Err(e) => {
-            error!("Error querying for user with unique_id {}: {}", user_id, e);
+            error!("Error querying for subject {} of issuer {}: {}", subject, issuer, e);
             Err(format!("Database error: {}", e))
         }
     }
-}
\ No newline at end of file
+}
+
+/// Gives the users created before issuers were recorded, whose issuer is still empty, their issuer.
+///
+/// Before then there was a single Keycloak realm, and its users were stored with the bcrypt hash of
+/// `DUMMY_PASSWORD` in place of a password. A row with that hash, or with no usable hash, belongs to
+/// the former realm: it gets `legacy_issuer` and an OIDC credential for its subject, and the
+/// placeholder hash is dropped. Any other row is a local account and keeps its password.
+///
+/// Each row is moved in its own transaction; one that cannot be moved, e.g. because its username is
+/// taken in the target issuer, is logged and left for the next start.
+///
+/// # Arguments
+/// * legacy_issuer (&str): the issuer URL of the realm configured before `OIDC_ISSUERS`
+///
+/// # Returns
+/// * (Result<usize, String>): the number of rows moved, or an error message if they could not be loaded
+pub fn assign_legacy_users(legacy_issuer: &str) -> Result<usize, String> {
+    let mut connection = try_establish_connection()?;
+    let legacy_users = users::table
+        .filter(users::columns::issuer.eq(""))
+        .load::<User>(&mut connection)
+        .map_err(|e| {
+            error!("Error loading users without an issuer: {}", e);
+            format!("Database error: {}", e)
+        })?;
+
+    let mut assigned = 0;
+    for user in legacy_users {
+        let password = credentials::table
+            .filter(credentials::columns::user_id.eq(&user.id))
+            .filter(credentials::columns::kind.eq(PASSWORD_CREDENTIAL))
+            .first::<Credential>(&mut connection)
+            .optional();
+        let password = match password {
+            Ok(password) => password,
+            Err(e) => {
+                error!("Error loading the password credential of user {}: {}", user.id, e);
+                continue;
+            }
+        };
+        let is_local = password
+            .as_ref()
+            .is_some_and(|credential| matches!(credential.verify_password(LEGACY_SSO_PASSWORD), Ok(false)));
+        let issuer = if is_local { LOCAL_ISSUER } else { legacy_issuer };
+
+        let moved = connection.transaction::<(), diesel::result::Error, _>(|connection| {
+            diesel::update(users::table.find(&user.id).filter(users::columns::issuer.eq("")))
+                .set(users::columns::issuer.eq(issuer))
+                .execute(connection)?;
+            if !is_local {
+                diesel::delete(
+                    credentials::table
+                        .filter(credentials::columns::user_id.eq(&user.id))
+                        .filter(credentials::columns::kind.eq(PASSWORD_CREDENTIAL)),
+                )
+                .execute(connection)?;
+                diesel::insert_into(credentials::table)
+                    .values(&NewCredential::oidc(&user.id, issuer, &user.subject))
+                    .execute(connection)?;
+            }
+            Ok(())
+        });
+        match moved {
+            Ok(()) => {
+                info!("Assigned pre-existing user {} to issuer {}", user.id, issuer);
+                assigned += 1;
+            },
+            Err(e) => error!("Error assigning pre-existing user {} to issuer {}: {}", user.id, issuer, e),
+        }
+    }
+    Ok(assigned)
+}
+
+/// Finds a user by the identity a token names: local accounts by their subject, everyone else
+/// through their OIDC credential.
+fn find_user_by_identity(connection: &mut PgConnection, issuer: &str, subject: &str) -> QueryResult<User> {
+    if issuer == LOCAL_ISSUER {
+        return users::table
+            .filter(users::columns::issuer.eq(LOCAL_ISSUER))
+            .filter(users::columns::subject.eq(subject))
+            .first::<User>(connection);
+    }
+    credentials::table
+        .inner_join(users::table)
+        .filter(credentials::columns::kind.eq(OIDC_CREDENTIAL))
+        .filter(credentials::columns::issuer.eq(issuer))
+        .filter(credentials::columns::subject.eq(subject))
+        .select(users::all_columns)
+        .first::<User>(connection)
+}
+
+/// Copies changed profile claims to the user's row.
+fn sync_profile(connection: &mut PgConnection, user: User, principal: &Principal) -> Result<User, String> {
+    let current = UserProfile::of(&user);
+    let profile = current.merged_with(principal);
+    if profile == current {
+        return Ok(user);
+    }
+    connection
+        .transaction::<User, diesel::result::Error, _>(|connection| {
+            if profile.username != current.username || (profile.email != current.email && profile.email.is_some()) {
+                release_taken_profile_fields(connection, &user.issuer, &user.subject, &profile.username, profile.email.as_deref())?;
+            }
+            diesel::update(users::table.find(&user.id))
+                .set(&profile)
+                .get_result::<User>(connection)
+        })
+        .inspect(|updated| info!("Updated the profile of user {} from the token claims", updated.id))
+        .map_err(|e| {
+            error!("Error updating the profile of user {}: {}", user.id, e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Takes a username and email address away from other rows of the same issuer that still hold them.
+/// Local accounts own their usernames, so they are never renamed.
+fn release_taken_profile_fields(
+    connection: &mut PgConnection,
+    issuer: &str,
+    subject: &str,
+    username: &str,
+    email: Option<&str>,
+) -> QueryResult<()> {
+    if issuer == LOCAL_ISSUER {
+        return Ok(());
+    }
+    let renamed = diesel::update(
+        users::table
+            .filter(users::columns::issuer.eq(issuer))
+            .filter(users::columns::username.eq(username))
+            .filter(users::columns::subject.ne(subject)),
+    )
+    .set(users::columns::username.eq(users::columns::username.concat("~").concat(users::columns::subject)))
+    .execute(connection)?;
+    if renamed > 0 {
+        warn!("Username '{}' of issuer {} was held by a stale user; renamed it.", username, issuer);
+    }
+    if let Some(email) = email {
+        let cleared = diesel::update(
+            users::table
+                .filter(users::columns::issuer.eq(issuer))
+                .filter(users::columns::email.eq(email))
+                .filter(users::columns::subject.ne(subject)),
+        )
+        .set(users::columns::email.eq(None::<String>))
+        .execute(connection)?;
+        if cleared > 0 {
+            warn!("An email address of issuer {} was held by a stale user; cleared it.", issuer);
+        }
+    }
+    Ok(())
+}
+
+/// Loads a user by its local ID.
+///
+/// # Arguments
+/// * user_id (&str): the local user ID
+///
+/// # Returns
+/// * (Result<Option<User>, String>): the user if it exists, or an error message if the database call failed
+pub fn find_user(user_id: &str) -> Result<Option<User>, String> {
+    let mut connection = establish_connection();
+    users::table
+        .find(user_id)
+        .first::<User>(&mut connection)
+        .optional()
+        .map_err(|e| {
+            error!("Error loading user {}: {}", user_id, e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Finds the local account registered with an email address.
+///
+/// # Arguments
+/// * email (&str): the email address
+///
+/// # Returns
+/// * (Result<Option<User>, String>): the user if there is one, or an error message if the database call failed
+pub fn find_local_user_by_email(email: &str) -> Result<Option<User>, String> {
+    let mut connection = establish_connection();
+    users::table
+        .filter(users::columns::issuer.eq(LOCAL_ISSUER))
+        .filter(users::columns::email.eq(email))
+        .first::<User>(&mut connection)
+        .optional()
+        .map_err(|e| {
+            error!("Error looking up user by email: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Records that a user has proved they own their email address.
+///
+/// # Arguments
+/// * user_id (&str): the local user ID
+///
+/// # Returns
+/// * (Result<(), String>): an error message if the database call failed
+pub fn mark_email_verified(user_id: &str) -> Result<(), String> {
+    let mut connection = establish_connection();
+    diesel::update(users::table.find(user_id))
+        .set(users::columns::email_verified_at.eq(SystemTime::now()))
+        .execute(&mut connection)
+        .map(|_| info!("Verified the email address of user {}", user_id))
+        .map_err(|e| {
+            error!("Error verifying email of user {}: {}", user_id, e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Finds the user a token names without creating it.
+///
+/// # Arguments
+/// * issuer (&str): the issuer of the token
+/// * subject (&str): the subject of the token
+///
+/// # Returns
+/// * (Result<Option<User>, String>): the user if there is one yet, or an error message if the database call failed
+pub fn find_user_for_token(issuer: &str, subject: &str) -> Result<Option<User>, String> {
+    let mut connection = establish_connection();
+    find_user_by_identity(&mut connection, issuer, subject).optional().map_err(|e| {
+        error!("Error looking up subject {} of issuer {}: {}", subject, issuer, e);
+        format!("Database error: {}", e)
+    })
+}
+
+/// The users matching an optional search term, which is looked for in the username, email address
+/// and display name regardless of case.
+fn search_users(search: Option<&str>) -> users::BoxedQuery<'static, Pg> {
+    let mut query = users::table.into_boxed();
+    if let Some(search) = search {
+        let pattern = format!("%{}%", search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        query = query.filter(
+            users::columns::username.ilike(pattern.clone())
+                .or(users::columns::email.ilike(pattern.clone()))
+                .or(users::columns::display_name.ilike(pattern)),
+        );
+    }
+    query
+}
+
+/// Loads one page of users, ordered by username.
+///
+/// # Arguments
+/// * search (Option<&str>): only users whose username, email address or display name contain this
+/// * offset (i64): the number of users to skip
+/// * limit (i64): the most users to return
+///
+/// # Returns
+/// * (Result<(Vec<User>, i64), String>): the page and the number of matching users, or an error message if the database call failed
+pub fn list_users(search: Option<&str>, offset: i64, limit: i64) -> Result<(Vec<User>, i64), String> {
+    let mut connection = establish_connection();
+    let total = search_users(search)
+        .count()
+        .get_result::<i64>(&mut connection)
+        .map_err(|e| format!("Database error: {}", e))?;
+    search_users(search)
+        .order((users::columns::username.asc(), users::columns::id.asc()))
+        .offset(offset)
+        .limit(limit)
+        .load::<User>(&mut connection)
+        .map(|users| (users, total))
+        .map_err(|e| {
+            error!("Error listing users: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Counts the to-do items each user owns.
+///
+/// # Arguments
+/// * user_ids (&[String]): the local user IDs
+///
+/// # Returns
+/// * (Result<HashMap<String, i64>, String>): the counts of the users owning any items, or an error message if the database call failed
+pub fn count_items(user_ids: &[String]) -> Result<HashMap<String, i64>, String> {
+    let mut connection = establish_connection();
+    to_do::table
+        .filter(to_do::columns::user_id.eq_any(user_ids))
+        .group_by(to_do::columns::user_id)
+        .select((to_do::columns::user_id, count_star()))
+        .load::<(String, i64)>(&mut connection)
+        .map(|counts| counts.into_iter().collect())
+        .map_err(|e| {
+            error!("Error counting items of users: {}", e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Disables or re-enables a user. Disabling also ends the user's sessions and revokes their refresh
+/// tokens; access tokens already issued are refused while the user stays disabled.
+///
+/// # Arguments
+/// * user_id (&str): the local user ID
+/// * disabled (bool): whether the user should be disabled
+///
+/// # Returns
+/// * (Result<Option<User>, String>): the updated user, `None` if it does not exist, or an error message if the database call failed
+pub fn set_disabled(user_id: &str, disabled: bool) -> Result<Option<User>, String> {
+    let mut connection = establish_connection();
+    connection
+        .transaction::<Option<User>, diesel::result::Error, _>(|connection| {
+            let user = diesel::update(users::table.find(user_id))
+                .set(users::columns::disabled_at.eq(if disabled { Some(SystemTime::now()) } else { None }))
+                .get_result::<User>(connection)
+                .optional()?;
+            if user.is_some() && disabled {
+                diesel::delete(sessions::table.filter(sessions::columns::user_id.eq(user_id))).execute(connection)?;
+                diesel::delete(refresh_tokens::table.filter(refresh_tokens::columns::user_id.eq(user_id))).execute(connection)?;
+            }
+            Ok(user)
+        })
+        .map_err(|e| {
+            error!("Error changing whether user {} is disabled: {}", user_id, e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// Loads everything stored about a user, in one transaction so the parts agree with each other.
+///
+/// # Arguments
+/// * user_id (&str): the local user ID
+///
+/// # Returns
+/// * (Result<Option<AccountData>, String>): the user's data, `None` if the user does not exist, or an error message if the database call failed
+pub fn load_account_data(user_id: &str) -> Result<Option<AccountData>, String> {
+    let mut connection = establish_connection();
+    connection
+        .build_transaction()
+        .repeatable_read()
+        .read_only()
+        .run::<Option<AccountData>, diesel::result::Error, _>(|connection| {
+            let user = match users::table.find(user_id).first::<User>(connection).optional()? {
+                Some(user) => user,
+                None => return Ok(None),
+            };
+            let login_failures = if user.issuer == LOCAL_ISSUER {
+                login_failures::table
+                    .find(login_throttle::user_key(&user.username))
+                    .first::<LoginFailure>(connection)
+                    .optional()?
+            } else {
+                None
+            };
+            Ok(Some(AccountData {
+                items: Item::belonging_to(&user).order(to_do::columns::id.asc()).load::<Item>(connection)?,
+                credentials: Credential::belonging_to(&user).order(credentials::columns::id.asc()).load::<Credential>(connection)?,
+                personal_access_tokens: PersonalAccessToken::belonging_to(&user)
+                    .order(personal_access_tokens::columns::created_at.asc())
+                    .load::<PersonalAccessToken>(connection)?,
+                sessions: Session::belonging_to(&user).order(sessions::columns::created_at.asc()).load::<Session>(connection)?,
+                refresh_tokens: RefreshToken::belonging_to(&user)
+                    .order(refresh_tokens::columns::created_at.asc())
+                    .load::<RefreshToken>(connection)?,
+                login_failures,
+                user,
+            }))
+        })
+        .map_err(|e| {
+            error!("Error loading the data of user {}: {}", user_id, e);
+            format!("Database error: {}", e)
+        })
+}
+
+/// What happens to the to-do items of a deleted user.
+pub enum ItemDisposition {
+    /// The items are deleted with the user.
+    Delete,
+    /// The items are given to another user, by local ID.
+    ReassignTo(String),
+}
+
+/// Deletes a user with their credentials, tokens and sessions, deletes or reassigns their items, and
+/// records a tombstone, all in one transaction.
+///
+/// # Arguments
+/// * user_id (&str): the local user ID
+/// * items (&ItemDisposition): what happens to the user's to-do items
+/// * requested_by (&str): the local ID of the user or administrator asking for the deletion
+///
+/// # Returns
+/// * (Result<Option<usize>, String>): the number of items deleted or reassigned, `None` if the user does not exist, or an error message if the database call failed
+pub fn delete_user(user_id: &str, items: &ItemDisposition, requested_by: &str) -> Result<Option<usize>, String> {
+    let mut connection = establish_connection();
+    connection
+        .transaction::<Option<usize>, diesel::result::Error, _>(|connection| {
+            let user = match users::table.find(user_id).for_update().first::<User>(connection).optional()? {
+                Some(user) => user,
+                None => return Ok(None),
+            };
+            let user_items = to_do::table.filter(to_do::columns::user_id.eq(user_id));
+            let (deleted, reassigned) = match items {
+                ItemDisposition::Delete => (diesel::delete(user_items).execute(connection)?, 0),
+                ItemDisposition::ReassignTo(owner) => (0, diesel::update(user_items)
+                    .set(to_do::columns::user_id.eq(owner))
+                    .execute(connection)?),
+            };
+            if user.issuer == LOCAL_ISSUER {
+                diesel::delete(login_failures::table.find(login_throttle::user_key(&user.username))).execute(connection)?;
+            }
+            // Credentials, tokens and sessions are removed by their foreign keys
+            diesel::delete(users::table.find(user_id)).execute(connection)?;
+            diesel::insert_into(account_tombstones::table)
+                .values(&NewAccountTombstone {
+                    user_id: user.id.clone(),
+                    issuer: user.issuer.clone(),
+                    subject_hash: hash_secret(&user.subject),
+                    requested_by: requested_by.to_string(),
+                    items_deleted: deleted as i32,
+                    items_reassigned: reassigned as i32,
+                    deleted_at: SystemTime::now(),
+                })
+                .execute(connection)?;
+            Ok(Some(deleted + reassigned))
+        })
+        .map_err(|e| {
+            error!("Error deleting user {}: {}", user_id, e);
+            format!("Database error: {}", e)
+        })
+}
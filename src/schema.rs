@@ -1,5 +1,99 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    account_tombstones (user_id) {
+        user_id -> Text,
+        issuer -> Text,
+        subject_hash -> Text,
+        requested_by -> Text,
+        items_deleted -> Int4,
+        items_reassigned -> Int4,
+        deleted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    credentials (id) {
+        id -> Int4,
+        user_id -> Text,
+        kind -> Text,
+        password_hash -> Nullable<Text>,
+        issuer -> Nullable<Text>,
+        subject -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    login_failures (throttle_key) {
+        throttle_key -> Text,
+        failures -> Int4,
+        last_failure_at -> Timestamp,
+        locked_until -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    logout_revocations (id) {
+        id -> Int4,
+        issuer -> Text,
+        sid -> Nullable<Text>,
+        subject -> Nullable<Text>,
+        revoked_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oidc_login_states (state_hash) {
+        state_hash -> Text,
+        issuer_name -> Text,
+        code_verifier -> Text,
+        nonce -> Text,
+        return_to -> Text,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    personal_access_tokens (id) {
+        id -> Text,
+        user_id -> Text,
+        name -> Text,
+        token_hash -> Text,
+        scopes -> Text,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    refresh_tokens (token_hash) {
+        token_hash -> Text,
+        family_id -> Text,
+        user_id -> Text,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    sessions (id_hash) {
+        id_hash -> Text,
+        user_id -> Text,
+        issuer_name -> Text,
+        access_token -> Text,
+        access_token_expires_at -> Timestamp,
+        refresh_token -> Nullable<Text>,
+        id_token -> Nullable<Text>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        sid -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     to_do (id) {
         id -> Int4,
@@ -13,14 +107,32 @@ diesel::table! {
     users (id) {
         id -> Text,
         username -> Varchar,
-        email -> Varchar,
-        password -> Varchar,
+        email -> Nullable<Varchar>,
+        issuer -> Text,
+        subject -> Text,
+        email_verified_at -> Nullable<Timestamp>,
+        display_name -> Nullable<Text>,
+        given_name -> Nullable<Text>,
+        family_name -> Nullable<Text>,
+        disabled_at -> Nullable<Timestamp>,
     }
 }
 
+diesel::joinable!(credentials -> users (user_id));
+diesel::joinable!(personal_access_tokens -> users (user_id));
+diesel::joinable!(refresh_tokens -> users (user_id));
+diesel::joinable!(sessions -> users (user_id));
 diesel::joinable!(to_do -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    account_tombstones,
+    credentials,
+    login_failures,
+    logout_revocations,
+    oidc_login_states,
+    personal_access_tokens,
+    refresh_tokens,
+    sessions,
     to_do,
     users,
 );
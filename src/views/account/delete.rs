@@ -0,0 +1,30 @@
+use actix_web::HttpResponse;
+use tracing::info;
+
+use crate::auth::processes::Claims;
+use crate::json_serialization::error_response::ErrorResponse;
+use crate::models::user::user_utils::{self, ItemDisposition};
+
+/// This view deletes the authenticated user's account with their items, credentials, tokens and
+/// sessions, and records a tombstone without personal data. Tokens issued before the deletion are
+/// refused from then on.
+///
+/// # Arguments
+/// * claims (Claims): the authenticated caller's claims
+///
+/// # Returns
+/// * (HttpResponse): 204 once deleted, 403 for personal access tokens and service accounts
+pub async fn delete(claims: Claims) -> HttpResponse {
+    let user = match super::account_owner(&claims) {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match user_utils::delete_user(&user.id, &ItemDisposition::Delete, &user.id) {
+        Ok(Some(items)) => {
+            info!("User {} deleted their account and {} item(s)", user.id, items);
+            HttpResponse::NoContent().finish()
+        },
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse::new("not_found", "No such user")),
+        Err(e) => HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    }
+}
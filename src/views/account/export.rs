@@ -0,0 +1,72 @@
+use std::io::{Cursor, Write};
+
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_DISPOSITION};
+use actix_web::HttpResponse;
+use tracing::{info, error};
+use serde::Serialize;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::auth::processes::Claims;
+use crate::json_serialization::account_export::AccountExport;
+use crate::json_serialization::error_response::ErrorResponse;
+use crate::models::user::user_utils;
+
+/// This view lets the authenticated user download everything stored about them as one zip archive,
+/// with a JSON file each for their profile, items, credentials, tokens, sessions and failed logins.
+///
+/// # Arguments
+/// * claims (Claims): the authenticated caller's claims
+///
+/// # Returns
+/// * (HttpResponse): 200 with the archive, 403 for personal access tokens and service accounts
+pub async fn export(claims: Claims) -> HttpResponse {
+    let user = match super::account_owner(&claims) {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    let data = match user_utils::load_account_data(&user.id) {
+        Ok(Some(data)) => data,
+        Ok(None) => return HttpResponse::NotFound().json(ErrorResponse::new("not_found", "No such user")),
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    };
+    let export = AccountExport::new(&data);
+
+    let archive = build_archive(&[
+        ("user.json", to_json(&export.user)),
+        ("items.json", to_json(&export.items)),
+        ("credentials.json", to_json(&export.credentials)),
+        ("personal_access_tokens.json", to_json(&export.personal_access_tokens)),
+        ("sessions.json", to_json(&export.sessions)),
+        ("refresh_tokens.json", to_json(&export.refresh_tokens)),
+        ("login_failures.json", to_json(&export.login_failures)),
+    ]);
+    match archive {
+        Ok(archive) => {
+            info!("User {} exported their data", user.id);
+            HttpResponse::Ok()
+                .content_type("application/zip")
+                .insert_header((CONTENT_DISPOSITION, format!("attachment; filename=\"account-{}.zip\"", user.id)))
+                .insert_header((CACHE_CONTROL, "no-store"))
+                .body(archive)
+        },
+        Err(e) => {
+            error!("Could not build the data export of user {}: {}", user.id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", "The export could not be built"))
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Vec<u8> {
+    serde_json::to_vec_pretty(value).unwrap_or_default()
+}
+
+fn build_archive(files: &[(&str, Vec<u8>)]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, contents) in files {
+        archive.start_file(*name, options)?;
+        archive.write_all(contents)?;
+    }
+    Ok(archive.finish()?.into_inner())
+}
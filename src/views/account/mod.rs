@@ -0,0 +1,47 @@
+use actix_web::{web, HttpResponse};
+use tracing::error;
+mod delete;
+mod export;
+use super::path::Path;
+use crate::auth::guards::RequireScope;
+use crate::auth::permissions;
+use crate::auth::processes::{Claims, Principal};
+use crate::json_serialization::error_response::ErrorResponse;
+use crate::models::user::user::User;
+use crate::models::user::user_utils;
+
+
+/// This function adds the views that let users export and delete their own account. Every route needs
+/// the `profile` scope.
+///
+/// # Arguments
+/// * (&mut web::ServiceConfig): reference to the app for configuration
+///
+/// # Returns
+/// None
+pub fn account_factory(app: &mut web::ServiceConfig) {
+    let base_path: Path = Path{prefix: String::from("/account"), backend: true};
+
+    app.route(&base_path.define(String::from("/export")),
+              web::get().to(export::export).wrap(RequireScope(permissions::PROFILE)));
+    app.route(&base_path.define(String::from("")),
+              web::delete().to(delete::delete).wrap(RequireScope(permissions::PROFILE)));
+}
+
+/// Finds the account of the caller. Personal access tokens and service accounts cannot export or
+/// delete an account.
+fn account_owner(claims: &Claims) -> Result<User, HttpResponse> {
+    if claims.personal_access_token.is_some() {
+        return Err(HttpResponse::Forbidden().json(ErrorResponse::new(
+            "forbidden", "Personal access tokens cannot export or delete the account",
+        )));
+    }
+    let principal = Principal::from_claims(claims);
+    if let Principal::ServiceClient { .. } = principal {
+        return Err(HttpResponse::Forbidden().json(ErrorResponse::new("forbidden", "Service accounts have no account to manage")));
+    }
+    user_utils::find_or_create_user(&principal).map_err(|e| {
+        error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
+        HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e))
+    })
+}
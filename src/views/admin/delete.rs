@@ -0,0 +1,64 @@
+use actix_web::{web, HttpResponse};
+use tracing::{info, error};
+
+use crate::auth::processes::Principal;
+use crate::json_serialization::admin_user::UserDeleteQuery;
+use crate::json_serialization::error_response::ErrorResponse;
+use crate::models::user::user_utils::{self, ItemDisposition};
+
+/// This view deletes a user with their credentials, tokens and sessions. The query string says what
+/// happens to their to-do items: `items=delete` deletes them, `items=reassign&reassign_to=<user id>`
+/// gives them to another user.
+///
+/// # Arguments
+/// * principal (Principal): the administrator
+/// * id (web::Path<String>): the local user ID
+/// * query (web::Query<UserDeleteQuery>): what happens to the user's items
+///
+/// # Returns
+/// * (HttpResponse): 204 once deleted, 400 for a missing or invalid choice, 404 if the user or the new
+///   owner does not exist, 409 for the administrator's own account
+pub async fn delete(principal: Principal, id: web::Path<String>, query: web::Query<UserDeleteQuery>) -> HttpResponse {
+    let items = match (query.items.as_deref(), &query.reassign_to) {
+        (Some("delete"), None) => ItemDisposition::Delete,
+        (Some("reassign"), Some(owner)) if *owner != *id => ItemDisposition::ReassignTo(owner.clone()),
+        (Some("reassign"), Some(_)) => {
+            return HttpResponse::BadRequest().json(ErrorResponse::new("invalid_query", "Items cannot be reassigned to the deleted user"));
+        },
+        _ => {
+            return HttpResponse::BadRequest().json(ErrorResponse::new(
+                "invalid_query", "Choose items=delete, or items=reassign with reassign_to",
+            ));
+        }
+    };
+
+    let admin = match user_utils::find_or_create_user(&principal) {
+        Ok(u) => u,
+        Err(e) => {
+            error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
+            return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e));
+        }
+    };
+    if admin.id == *id {
+        return HttpResponse::Conflict().json(ErrorResponse::new("own_account", "Administrators cannot delete their own account"));
+    }
+    if let ItemDisposition::ReassignTo(owner) = &items {
+        match user_utils::find_user(owner) {
+            Ok(Some(_)) => {},
+            Ok(None) => return HttpResponse::NotFound().json(ErrorResponse::new("not_found", "No user to reassign the items to")),
+            Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+        }
+    }
+
+    match user_utils::delete_user(&id, &items, &admin.id) {
+        Ok(Some(moved)) => {
+            match &items {
+                ItemDisposition::Delete => info!("Admin {} deleted user {} and their {} item(s)", admin.id, id, moved),
+                ItemDisposition::ReassignTo(owner) => info!("Admin {} deleted user {} and gave their {} item(s) to {}", admin.id, id, moved, owner),
+            }
+            HttpResponse::NoContent().finish()
+        },
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse::new("not_found", "No such user")),
+        Err(e) => HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    }
+}
@@ -0,0 +1,55 @@
+use actix_web::{web, HttpResponse};
+use tracing::{info, error};
+
+use crate::auth::processes::Principal;
+use crate::json_serialization::admin_user::AdminUserView;
+use crate::json_serialization::error_response::ErrorResponse;
+use crate::models::user::user_utils;
+
+/// This view disables a user. Their sessions and refresh tokens end at once, and their access and
+/// personal access tokens are refused until they are enabled again.
+///
+/// # Arguments
+/// * principal (Principal): the administrator
+/// * id (web::Path<String>): the local user ID
+///
+/// # Returns
+/// * (HttpResponse): 200 with the user, 404 if there is no such user, 409 for the administrator's own account
+pub async fn disable(principal: Principal, id: web::Path<String>) -> HttpResponse {
+    let admin = match user_utils::find_or_create_user(&principal) {
+        Ok(u) => u,
+        Err(e) => {
+            error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
+            return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e));
+        }
+    };
+    if admin.id == *id {
+        return HttpResponse::Conflict().json(ErrorResponse::new("own_account", "Administrators cannot disable their own account"));
+    }
+    set_disabled(principal.subject(), &id, true)
+}
+
+/// This view enables a disabled user again.
+///
+/// # Arguments
+/// * principal (Principal): the administrator
+/// * id (web::Path<String>): the local user ID
+///
+/// # Returns
+/// * (HttpResponse): 200 with the user, 404 if there is no such user
+pub async fn enable(principal: Principal, id: web::Path<String>) -> HttpResponse {
+    set_disabled(principal.subject(), &id, false)
+}
+
+fn set_disabled(admin: &str, user_id: &str, disabled: bool) -> HttpResponse {
+    let user = match user_utils::set_disabled(user_id, disabled) {
+        Ok(Some(user)) => user,
+        Ok(None) => return HttpResponse::NotFound().json(ErrorResponse::new("not_found", "No such user")),
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    };
+    info!("Admin {} {} user {}", admin, if disabled { "disabled" } else { "enabled" }, user.id);
+    match user_utils::count_items(std::slice::from_ref(&user.id)) {
+        Ok(counts) => HttpResponse::Ok().json(AdminUserView::new(&user, counts.get(&user.id).copied().unwrap_or(0))),
+        Err(e) => HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    }
+}
@@ -0,0 +1,24 @@
+use actix_web::{web, HttpResponse};
+
+use crate::json_serialization::admin_user::AdminUserView;
+use crate::json_serialization::error_response::ErrorResponse;
+use crate::models::user::user_utils;
+
+/// This view shows one user with the number of to-do items they own.
+///
+/// # Arguments
+/// * id (web::Path<String>): the local user ID
+///
+/// # Returns
+/// * (HttpResponse): 200 with the user, 404 if there is no such user
+pub async fn get(id: web::Path<String>) -> HttpResponse {
+    let user = match user_utils::find_user(&id) {
+        Ok(Some(user)) => user,
+        Ok(None) => return HttpResponse::NotFound().json(ErrorResponse::new("not_found", "No such user")),
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    };
+    match user_utils::count_items(std::slice::from_ref(&user.id)) {
+        Ok(counts) => HttpResponse::Ok().json(AdminUserView::new(&user, counts.get(&user.id).copied().unwrap_or(0))),
+        Err(e) => HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    }
+}
@@ -0,0 +1,48 @@
+use actix_web::{web, HttpResponse};
+
+use crate::json_serialization::admin_user::{AdminUserPage, AdminUserView, UserListQuery};
+use crate::json_serialization::error_response::ErrorResponse;
+use crate::models::user::user_utils;
+
+/// Page size when the query does not name one.
+const DEFAULT_PER_PAGE: i64 = 20;
+
+/// Largest page size a query can ask for.
+const MAX_PER_PAGE: i64 = 100;
+
+/// This view lists users one page at a time, with the number of to-do items each owns.
+///
+/// # Arguments
+/// * query (web::Query<UserListQuery>): the search term (`q`), `page` (from 1) and `per_page`
+///
+/// # Returns
+/// * (HttpResponse): 200 with the page, 400 for an invalid page
+pub async fn list(query: web::Query<UserListQuery>) -> HttpResponse {
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if page < 1 || !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return HttpResponse::BadRequest().json(ErrorResponse::new(
+            "invalid_query", &format!("page must be at least 1 and per_page between 1 and {}", MAX_PER_PAGE),
+        ));
+    }
+    let search = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+    let (users, total) = match user_utils::list_users(search, (page - 1) * per_page, per_page) {
+        Ok(result) => result,
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    };
+    let ids: Vec<String> = users.iter().map(|user| user.id.clone()).collect();
+    let item_counts = match user_utils::count_items(&ids) {
+        Ok(counts) => counts,
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    };
+    HttpResponse::Ok().json(AdminUserPage {
+        users: users
+            .iter()
+            .map(|user| AdminUserView::new(user, item_counts.get(&user.id).copied().unwrap_or(0)))
+            .collect(),
+        page,
+        per_page,
+        total,
+    })
+}
@@ -0,0 +1,31 @@
+use actix_web::web;
+mod delete;
+mod disable;
+mod get;
+mod list;
+use super::path::Path;
+use crate::auth::guards::RequireRole;
+use crate::auth::permissions;
+
+
+/// This function adds the administrative user views to the web server. Every route needs the admin role.
+///
+/// # Arguments
+/// * (&mut web::ServiceConfig): reference to the app for configuration
+///
+/// # Returns
+/// None
+pub fn admin_factory(app: &mut web::ServiceConfig) {
+    let base_path: Path = Path{prefix: String::from("/admin/users"), backend: true};
+
+    app.route(&base_path.define(String::from("")),
+              web::get().to(list::list).wrap(RequireRole(permissions::ADMIN_ROLE)));
+    app.route(&base_path.define(String::from("/{id}")),
+              web::get().to(get::get).wrap(RequireRole(permissions::ADMIN_ROLE)));
+    app.route(&base_path.define(String::from("/{id}")),
+              web::delete().to(delete::delete).wrap(RequireRole(permissions::ADMIN_ROLE)));
+    app.route(&base_path.define(String::from("/{id}/disable")),
+              web::post().to(disable::disable).wrap(RequireRole(permissions::ADMIN_ROLE)));
+    app.route(&base_path.define(String::from("/{id}/enable")),
+              web::post().to(disable::enable).wrap(RequireRole(permissions::ADMIN_ROLE)));
+}
@@ -1,14 +1,9 @@
+use actix_web::http::header::LOCATION;
 use actix_web::HttpResponse;
 
+/// Sends the browser to the server-side logout, which ends the session and clears its cookie.
 pub async fn logout() -> HttpResponse {
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(
-            "<html>\
-                 <script>\
-                   localStorage.removeItem('user-token'); \
-                   window.location.replace(document.location.origin);\
-                 </script>\
-               </html>",
-        )
+    HttpResponse::Found()
+        .insert_header((LOCATION, "/auth/oidc/logout"))
+        .finish()
 }
@@ -0,0 +1,17 @@
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{web, HttpResponse};
+
+use crate::auth::local_issuer::LocalTokenIssuer;
+
+/// Publishes the public keys local login signs its access tokens with.
+///
+/// # Arguments
+/// * issuer (web::Data<LocalTokenIssuer>): the local token issuer
+///
+/// # Returns
+/// * (HttpResponse): the JSON Web Key Set
+pub async fn jwks(issuer: web::Data<LocalTokenIssuer>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(300)]))
+        .json(issuer.jwks())
+}
@@ -1,39 +1,113 @@
 use crate::diesel;
-use actix_web::{web, HttpResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, HttpRequest, HttpResponse};
 use diesel::prelude::*;
 
-// use crate::auth::jwt::JwtToken; // Replaced by jsonwebtoken
+use super::tokens::login_response;
+use crate::auth::local_issuer::LocalTokenIssuer;
+use crate::auth::login_throttle::{LoginThrottle, LoginThrottlePolicy};
 use crate::database::establish_connection;
+use crate::json_serialization::error_response::ErrorResponse;
 use crate::json_serialization::login::Login;
+use crate::models::credential::credential_utils;
+use crate::models::user::new_user::LOCAL_ISSUER;
 use crate::models::user::user::User;
 use crate::schema::users;
 
-pub async fn login(credentials: web::Json<Login>) -> HttpResponse {
+/// This view logs a local user in with a username and password.
+///
+/// Failed attempts are counted per username and per client IP; each one slows the next attempt
+/// down, and too many lock the username or the address for a while. An unknown username gets the
+/// same answer as a wrong password, so the endpoint does not reveal which usernames exist.
+///
+/// # Arguments
+/// * req (HttpRequest): the request, for the client IP (see `LoginThrottlePolicy::client_ip`)
+/// * credentials (web::Json<Login>): the username and password
+/// * issuer (web::Data<LocalTokenIssuer>): signs the access token
+/// * throttle (web::Data<LoginThrottlePolicy>): the limits on failed logins
+///
+/// # Returns
+/// * (HttpResponse): 200 with an access token and a refresh token, 401 for wrong credentials, 403 if the
+///   account is disabled or its email address must be verified first, 429 while locked
+pub async fn login(
+    req: HttpRequest,
+    credentials: web::Json<Login>,
+    issuer: web::Data<LocalTokenIssuer>,
+    throttle: web::Data<LoginThrottlePolicy>,
+) -> HttpResponse {
+    if !issuer.is_enabled() {
+        return HttpResponse::ServiceUnavailable().body("Local login is not configured");
+    }
     let username: String = credentials.username.clone();
     let password: String = credentials.password.clone();
-    
+    let client_ip = throttle.client_ip(&req);
+
+    match throttle.check(&username, &client_ip) {
+        Ok(LoginThrottle::Allowed(delay)) => {
+            if !delay.is_zero() {
+                actix_rt::time::sleep(delay).await;
+            }
+        },
+        Ok(LoginThrottle::Locked(locked_for)) => {
+            return HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, (locked_for.as_secs() + 1).to_string()))
+                .body("Too many failed logins, try again later");
+        },
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    }
+
     let mut connection = establish_connection();
-    let users = users::table
+    let users = match users::table
     .filter(users::columns::username.eq(username.as_str()))
-    .load::<User>(&mut connection)
-    .unwrap();
-    
+    .filter(users::columns::issuer.eq(LOCAL_ISSUER))
+    .load::<User>(&mut connection) {
+        Ok(users) => users,
+        Err(e) => return HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+    };
+
     if users.len() == 0 {
-        return HttpResponse::NotFound().await.unwrap()
+        if let Err(e) = throttle.record_failure(&username, &client_ip) {
+            return HttpResponse::InternalServerError().body(e);
+        }
+        return HttpResponse::Unauthorized().await.unwrap()
     } else if users.len() > 1 {
-        log::error!("multiple users have the username: {}",
+        tracing::error!("multiple users have the username: {}",
         credentials.username.clone());
         return HttpResponse::Conflict().await.unwrap()
     }
-    
-    match users[0].clone().verify(password) {
-        true => {
-            // Token generation is handled by Keycloak; this line is likely no longer needed.
-            // If local token generation is still required, it needs to be reimplemented using jsonwebtoken.
-            // let token: String = JwtToken::encode(users[0].clone().id);
-            // HttpResponse::Ok().header("token", token).await.unwrap()
-            HttpResponse::Ok().await.unwrap() // Return a basic OK for now
-        }
-        false => HttpResponse::Unauthorized().await.unwrap(),
+
+    // Accounts without a password credential (SSO users) cannot log in here
+    let verified = match credential_utils::find_password_credential(&users[0].id) {
+        Ok(Some(credential)) => credential.verify_password(&password),
+        Ok(None) => Ok(false),
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    match verified {
+        Ok(true) => {
+            if let Err(e) = throttle.record_success(&username) {
+                return HttpResponse::InternalServerError().body(e);
+            }
+            if users[0].disabled_at.is_some() {
+                return HttpResponse::Forbidden().json(ErrorResponse::new(
+                    "account_disabled", "This account has been disabled",
+                ));
+            }
+            if issuer.require_verified_email && users[0].email_verified_at.is_none() {
+                return HttpResponse::Forbidden().json(ErrorResponse::new(
+                    "email_not_verified", "Verify your email address before logging in",
+                ));
+            }
+            login_response(&issuer, &users[0])
+        },
+        Ok(false) => {
+            if let Err(e) = throttle.record_failure(&username, &client_ip) {
+                return HttpResponse::InternalServerError().body(e);
+            }
+            HttpResponse::Unauthorized().await.unwrap()
+        },
+        Err(e) => {
+            tracing::error!("{}", e);
+            HttpResponse::InternalServerError().body("The stored password cannot be checked")
+        },
     }
 }
@@ -1,10 +1,22 @@
-/// This function defines a logout view.
+use actix_web::{web, HttpResponse};
+
+use crate::auth::oidc_login::hash_secret;
+use crate::json_serialization::login::Refresh;
+use crate::models::refresh_token::refresh_token_utils;
+
+/// This view logs out of a local login by revoking its refresh token and every token rotated from it.
+///
+/// Access tokens already issued stay valid until they expire. An unknown or already revoked refresh
+/// token is not an error, so logging out twice succeeds.
 ///
 /// # Arguments
-/// None
+/// * refresh (web::Json<Refresh>): the refresh token of the login to end
 ///
 /// # Returns
-/// (String) message stating that it's the logout view
-pub async fn logout() -> String {
-    format!("Logout view")
+/// * (HttpResponse): 204 once the login's refresh tokens are revoked
+pub async fn logout(refresh: web::Json<Refresh>) -> HttpResponse {
+    match refresh_token_utils::revoke_refresh_family(&hash_secret(&refresh.refresh_token)) {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
 }
@@ -1,6 +1,10 @@
 use actix_web::web;
+mod jwks;
 mod login;
 mod logout;
+mod oidc;
+mod refresh;
+mod tokens;
 use super::path::Path;
 
 
@@ -13,11 +17,30 @@ use super::path::Path;
 /// None
 pub fn auth_factory(app: &mut web::ServiceConfig) {
     // define the path struct
-    let base_path: Path = Path{prefix: String::from("/auth"), backend: true};
+    let base_path: Path = Path{prefix: String::from(""), backend: true};
     // define the routes for the app
     let app = app.route(&base_path.define(String::from("/login")),
                         web::post().to(login::login));
+    // local login: rotate refresh tokens and publish the signing keys
+    app.route(&base_path.define(String::from("/refresh")),
+              web::post().to(refresh::refresh));
+    app.route(&base_path.define(String::from("/jwks")),
+              web::get().to(jwks::jwks));
     // define the logout route
     app.route(&base_path.define(String::from("/logout")),
               web::post().to(logout::logout));
+    // server-side OIDC login (backend-for-frontend); the browser only gets a session cookie
+    app.route(&base_path.define(String::from("/oidc/login")),
+              web::get().to(oidc::login::login));
+    app.route(&base_path.define(String::from("/oidc/callback")),
+              web::get().to(oidc::callback::callback));
+    app.route(&base_path.define(String::from("/oidc/logout")),
+              web::get().to(oidc::logout::logout));
+    app.route(&base_path.define(String::from("/oidc/session")),
+              web::get().to(oidc::session::session));
+    // logouts initiated by the identity provider
+    app.route(&base_path.define(String::from("/oidc/backchannel-logout")),
+              web::post().to(oidc::backchannel_logout::backchannel_logout));
+    app.route(&base_path.define(String::from("/oidc/frontchannel-logout")),
+              web::get().to(oidc::frontchannel_logout::frontchannel_logout));
 }
\ No newline at end of file
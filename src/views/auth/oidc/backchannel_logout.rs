@@ -0,0 +1,68 @@
+use std::time::SystemTime;
+
+use actix_web::http::header::{CacheControl, CacheDirective, RETRY_AFTER};
+use actix_web::{web, HttpResponse};
+use tracing::{info, warn};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::auth::logout::verify_logout_token;
+use crate::auth::oidc_login::OidcLoginSettings;
+use crate::auth::provider::ProviderRegistry;
+use crate::auth::validation_policy::TokenRejection;
+use crate::models::session::new_logout_revocation::NewLogoutRevocation;
+use crate::models::session::session_utils;
+
+#[derive(Deserialize)]
+pub struct BackchannelLogoutForm {
+    pub logout_token: String,
+}
+
+/// Receives a logout from the identity provider (OpenID Connect Back-Channel Logout): ends the
+/// browser sessions it names and rejects the tokens of that session or user from now on.
+///
+/// # Arguments
+/// * form (web::Form<BackchannelLogoutForm>): the posted `logout_token`
+/// * registry (web::Data<ProviderRegistry>): the trusted issuers
+/// * settings (web::Data<OidcLoginSettings>): the login settings
+///
+/// # Returns
+/// * (HttpResponse): 200 once the logout is recorded, 400 for an invalid logout token
+pub async fn backchannel_logout(
+    form: web::Form<BackchannelLogoutForm>,
+    registry: web::Data<ProviderRegistry>,
+    settings: web::Data<OidcLoginSettings>,
+) -> HttpResponse {
+    let logout = match verify_logout_token(&form.logout_token, &registry).await {
+        Ok(logout) => logout,
+        Err(TokenRejection::IssuerUnavailable(name)) => {
+            return HttpResponse::ServiceUnavailable()
+                .insert_header((RETRY_AFTER, "5"))
+                .body(format!("Issuer '{}' is not available yet", name));
+        },
+        Err(rejection) => {
+            warn!(reason = rejection.reason(), detail = %rejection, "Logout token rejected");
+            return HttpResponse::BadRequest()
+                .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+                .json(json!({"error": "invalid_request", "error_description": rejection.to_string()}));
+        }
+    };
+
+    let revocation = NewLogoutRevocation {
+        issuer: logout.iss.clone(),
+        sid: logout.sid.clone(),
+        subject: logout.sub.clone(),
+        revoked_at: logout.issued_at,
+        expires_at: SystemTime::now() + settings.logout_revocation_ttl,
+    };
+    match session_utils::record_logout(&revocation, &logout.issuer_name) {
+        Ok(ended) => {
+            info!(
+                "Back-channel logout from issuer '{}' (sid={:?}, sub={:?}) ended {} session(s).",
+                logout.issuer_name, logout.sid, logout.sub, ended
+            );
+            HttpResponse::Ok().insert_header(CacheControl(vec![CacheDirective::NoStore])).finish()
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
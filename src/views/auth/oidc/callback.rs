@@ -0,0 +1,130 @@
+use std::time::SystemTime;
+
+use actix_web::http::header::LOCATION;
+use actix_web::{web, HttpRequest, HttpResponse};
+use tracing::{info, warn};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::auth;
+use crate::auth::oidc_login::{self, hash_secret, login_state_cookie, random_secret, session_cookie, OidcLoginSettings, LOGIN_STATE_COOKIE};
+use crate::auth::processes::Principal;
+use crate::auth::provider::ProviderRegistry;
+use crate::models::session::new_session::NewSession;
+use crate::models::session::session_utils;
+use crate::models::user::user_utils;
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Completes a login: exchanges the code for tokens, stores them in a new session and sets the session cookie.
+///
+/// # Arguments
+/// * req (HttpRequest): the callback request, carrying the login-state cookie
+/// * query (web::Query<CallbackQuery>): the authorization response
+/// * registry (web::Data<ProviderRegistry>): the trusted issuers
+/// * settings (web::Data<OidcLoginSettings>): the login settings
+///
+/// # Returns
+/// * (HttpResponse): a redirect to the page the login started from, or an error
+pub async fn callback(
+    req: HttpRequest,
+    query: web::Query<CallbackQuery>,
+    registry: web::Data<ProviderRegistry>,
+    settings: web::Data<OidcLoginSettings>,
+) -> HttpResponse {
+    let mut clear_state = login_state_cookie("", &settings);
+    clear_state.make_removal();
+
+    if let Some(error) = &query.error {
+        warn!("Login failed at the identity provider: {} ({:?})", error, query.error_description);
+        return HttpResponse::BadRequest().cookie(clear_state).body(format!("Login failed: {}", error));
+    }
+    let (code, state_value) = match (&query.code, &query.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return HttpResponse::BadRequest().cookie(clear_state).body("Missing code or state"),
+    };
+    // The state must come back to the browser that started the login
+    if req.cookie(LOGIN_STATE_COOKIE).map(|cookie| cookie.value().to_string()).as_deref() != Some(state_value.as_str()) {
+        warn!("Login callback with a state that does not belong to this browser.");
+        return HttpResponse::BadRequest().cookie(clear_state).body("Login state mismatch");
+    }
+    let login_state = match session_utils::take_login_state(&hash_secret(state_value)) {
+        Ok(Some(login_state)) => login_state,
+        Ok(None) => return HttpResponse::BadRequest().cookie(clear_state).body("Login expired or already completed"),
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+
+    let provider = match registry.find_by_name(&login_state.issuer_name) {
+        Some(provider) => provider,
+        None => return HttpResponse::BadRequest().cookie(clear_state).body("Unknown issuer"),
+    };
+    let state = match provider.get() {
+        Some(state) => state,
+        None => return HttpResponse::ServiceUnavailable().body("Authentication is not available yet"),
+    };
+
+    let tokens = match oidc_login::exchange_code(provider, state, code, &login_state.code_verifier, &settings.redirect_uri).await {
+        Ok(tokens) => tokens,
+        Err(e) => return HttpResponse::BadGateway().cookie(clear_state).body(e),
+    };
+    let id_token = match &tokens.id_token {
+        Some(id_token) => id_token,
+        None => return HttpResponse::BadGateway().cookie(clear_state).body("The identity provider returned no ID token"),
+    };
+    let id_claims = match oidc_login::verify_id_token(provider, state, id_token, &login_state.nonce).await {
+        Ok(claims) => claims,
+        Err(rejection) => {
+            warn!(reason = rejection.reason(), detail = %rejection, "ID token rejected");
+            return HttpResponse::Unauthorized().cookie(clear_state).body(rejection.to_string());
+        }
+    };
+    // The access token must pass the same checks as a Bearer token, and belong to the same user
+    let claims = match auth::verify_token(&tokens.access_token, state).await {
+        Ok(claims) => claims,
+        Err(rejection) => {
+            warn!(reason = rejection.reason(), detail = %rejection, "Access token from the code exchange rejected");
+            return HttpResponse::Unauthorized().cookie(clear_state).body(rejection.to_string());
+        }
+    };
+    if id_claims.get("sub").and_then(Value::as_str) != Some(claims.sub.as_str()) {
+        return HttpResponse::Unauthorized().cookie(clear_state).body("ID token and access token subjects differ");
+    }
+
+    let user = match user_utils::find_or_create_user(&Principal::from_claims(&claims)) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    if user.disabled_at.is_some() {
+        warn!("Refusing a session for disabled user {}.", user.id);
+        return HttpResponse::Forbidden().cookie(clear_state).body("Account is disabled");
+    }
+    let session_id = random_secret();
+    let new_session = NewSession {
+        id_hash: hash_secret(&session_id),
+        user_id: user.id.clone(),
+        issuer_name: provider.issuer.name.clone(),
+        access_token_expires_at: tokens.access_token_expires_at(),
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        id_token: tokens.id_token,
+        expires_at: SystemTime::now() + settings.session_ttl,
+        // lets a back-channel or front-channel logout from the identity provider find this session
+        sid: id_claims.get("sid").and_then(Value::as_str).map(str::to_string),
+    };
+    if let Err(e) = session_utils::create_session(&new_session) {
+        return HttpResponse::InternalServerError().body(e);
+    }
+
+    info!("Started a session for user {} from issuer '{}'.", user.id, provider.issuer.name);
+    HttpResponse::Found()
+        .insert_header((LOCATION, login_state.return_to))
+        .cookie(session_cookie(&session_id, &settings))
+        .cookie(clear_state)
+        .finish()
+}
@@ -0,0 +1,56 @@
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{web, HttpRequest, HttpResponse};
+use tracing::info;
+use serde::Deserialize;
+
+use crate::auth::oidc_login::{hash_secret, session_cookie, OidcLoginSettings, SESSION_COOKIE};
+use crate::auth::provider::ProviderRegistry;
+use crate::models::session::session_utils;
+
+#[derive(Deserialize)]
+pub struct FrontchannelLogoutQuery {
+    pub iss: Option<String>,
+    pub sid: Option<String>,
+}
+
+/// Receives a logout through the browser (OpenID Connect Front-Channel Logout), which the identity
+/// provider loads in a hidden frame: ends the browser's own session and the sessions of the given `sid`.
+///
+/// The request is not authenticated, so it only ends browser sessions; Bearer tokens are only
+/// revoked by the signed back-channel logout.
+///
+/// # Arguments
+/// * req (HttpRequest): the request, carrying the session cookie if the browser sends it
+/// * query (web::Query<FrontchannelLogoutQuery>): the issuer and session ID, when the provider sends them
+/// * registry (web::Data<ProviderRegistry>): the trusted issuers
+/// * settings (web::Data<OidcLoginSettings>): the login settings
+///
+/// # Returns
+/// * (HttpResponse): an empty, uncacheable page that clears the session cookie
+pub async fn frontchannel_logout(
+    req: HttpRequest,
+    query: web::Query<FrontchannelLogoutQuery>,
+    registry: web::Data<ProviderRegistry>,
+    settings: web::Data<OidcLoginSettings>,
+) -> HttpResponse {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        if let Ok(Some(session)) = session_utils::delete_session(&hash_secret(cookie.value())) {
+            info!("Front-channel logout ended the session of user {}.", session.user_id);
+        }
+    }
+    if let (Some(iss), Some(sid)) = (&query.iss, &query.sid) {
+        if let Some(provider) = registry.find_by_issuer(iss) {
+            if let Ok(ended) = session_utils::delete_sessions_by_sid(&provider.issuer.name, sid) {
+                info!("Front-channel logout from issuer '{}' ended {} session(s).", provider.issuer.name, ended);
+            }
+        }
+    }
+
+    let mut clear_session = session_cookie("", &settings);
+    clear_session.make_removal();
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoCache, CacheDirective::NoStore]))
+        .content_type("text/html; charset=utf-8")
+        .cookie(clear_session)
+        .body("<!DOCTYPE html><html><body></body></html>")
+}
@@ -0,0 +1,142 @@
+use std::time::SystemTime;
+
+use actix_web::http::header::{LOCATION, RETRY_AFTER};
+use actix_web::{web, HttpResponse};
+use tracing::{info, warn, error};
+use serde::Deserialize;
+
+use crate::auth::oidc_login::{code_challenge, hash_secret, login_state_cookie, random_secret, OidcLoginSettings, LOGIN_STATE_TTL};
+use crate::auth::provider::ProviderRegistry;
+use crate::models::session::login_state::LoginState;
+use crate::models::session::session_utils;
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    pub issuer: Option<String>,
+    pub return_to: Option<String>,
+    pub prompt: Option<String>,
+}
+
+/// Starts a login: redirects the browser to the identity provider with an Authorization Code + PKCE request.
+/// Issuers whose discovery document does not offer the authorization code grant with `S256` code
+/// challenges are refused, rather than sent a request they would reject or run without PKCE.
+///
+/// # Arguments
+/// * query (web::Query<LoginQuery>): optional issuer name, page to return to, and `prompt=create` to register
+/// * registry (web::Data<ProviderRegistry>): the trusted issuers
+/// * settings (web::Data<OidcLoginSettings>): the login settings
+///
+/// # Returns
+/// * (HttpResponse): a redirect to the authorization endpoint, or an error
+pub async fn login(
+    query: web::Query<LoginQuery>,
+    registry: web::Data<ProviderRegistry>,
+    settings: web::Data<OidcLoginSettings>,
+) -> HttpResponse {
+    let provider = match &query.issuer {
+        Some(name) => match registry.find_by_name(name) {
+            Some(provider) => provider,
+            None => return HttpResponse::BadRequest().body(format!("Unknown issuer '{}'", name)),
+        },
+        None => match registry.default_provider() {
+            Some(provider) => provider,
+            None => return HttpResponse::NotFound().body("No identity provider is configured"),
+        },
+    };
+    let state = match provider.get() {
+        Some(state) => state,
+        None => {
+            warn!("Login for issuer '{}' refused: OIDC discovery has not succeeded yet.", provider.issuer.name);
+            return HttpResponse::ServiceUnavailable()
+                .insert_header((RETRY_AFTER, "5"))
+                .body("Authentication is not available yet");
+        }
+    };
+
+    let config = &state.openid_config;
+    if !config.supports_grant_type("authorization_code") || !config.supports_code_challenge_method("S256") {
+        warn!(
+            "Login for issuer '{}' refused: grant types {:?}, code challenge methods {:?}.",
+            provider.issuer.name, config.grant_types_supported, config.code_challenge_methods_supported
+        );
+        return HttpResponse::BadRequest().body(format!(
+            "Issuer '{}' does not support the authorization code flow with S256 PKCE",
+            provider.issuer.name
+        ));
+    }
+
+    let state_value = random_secret();
+    let nonce = random_secret();
+    let code_verifier = random_secret();
+    let login_state = LoginState {
+        state_hash: hash_secret(&state_value),
+        issuer_name: provider.issuer.name.clone(),
+        code_verifier: code_verifier.clone(),
+        nonce: nonce.clone(),
+        return_to: safe_return_to(query.return_to.as_deref()),
+        expires_at: SystemTime::now() + LOGIN_STATE_TTL,
+    };
+    if let Err(e) = session_utils::save_login_state(&login_state) {
+        return HttpResponse::InternalServerError().body(format!("Failed to start the login: {}", e));
+    }
+
+    let mut authorization_url = match reqwest::Url::parse(&state.openid_config.authorization_endpoint) {
+        Ok(url) => url,
+        Err(e) => {
+            error!("Invalid authorization endpoint {}: {}", state.openid_config.authorization_endpoint, e);
+            return HttpResponse::InternalServerError().body("Invalid authorization endpoint");
+        }
+    };
+    {
+        let mut params = authorization_url.query_pairs_mut();
+        params
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.issuer.client_id)
+            .append_pair("redirect_uri", &settings.redirect_uri)
+            .append_pair("scope", &settings.scopes)
+            .append_pair("state", &state_value)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge(&code_verifier))
+            .append_pair("code_challenge_method", "S256");
+        if let Some(prompt) = query.prompt.as_deref().filter(|prompt| ["create", "login"].contains(prompt)) {
+            params.append_pair("prompt", prompt);
+        }
+    }
+
+    info!("Redirecting to issuer '{}' for login.", provider.issuer.name);
+    HttpResponse::Found()
+        .insert_header((LOCATION, authorization_url.as_str()))
+        .cookie(login_state_cookie(&state_value, &settings))
+        .finish()
+}
+
+/// Only local paths are accepted as a return target, so the login cannot be used as an open redirect.
+/// The target must be made of URL path and query characters only: browsers drop tabs and newlines
+/// from a `Location` and read `\` as `/`, which would turn `/\t/host` or `/\host` into `//host`.
+fn safe_return_to(return_to: Option<&str>) -> String {
+    let is_path_char = |c: char| c.is_ascii_alphanumeric() || "-._~!$&'()*+,;=:@/?%#".contains(c);
+    match return_to {
+        Some(path) if path.starts_with('/') && !path.starts_with("//") && path.chars().all(is_path_char) => path.to_string(),
+        _ => "/".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_paths_are_kept() {
+        assert_eq!(safe_return_to(Some("/")), "/");
+        assert_eq!(safe_return_to(Some("/items?filter=done&page=2#top")), "/items?filter=done&page=2#top");
+        assert_eq!(safe_return_to(Some("/a%20b/~c")), "/a%20b/~c");
+    }
+
+    #[test]
+    fn other_hosts_are_replaced_by_the_home_page() {
+        for target in ["//x", "/\\x", "/\t/x", "/\n/x", "/ /x", "https://x", "x", ""] {
+            assert_eq!(safe_return_to(Some(target)), "/", "{:?} was accepted", target);
+        }
+        assert_eq!(safe_return_to(None), "/");
+    }
+}
@@ -0,0 +1,66 @@
+use actix_web::http::header::LOCATION;
+use actix_web::{web, HttpRequest, HttpResponse};
+use tracing::{info, warn};
+
+use crate::auth::oidc_login::{hash_secret, revoke_refresh_token, session_cookie, OidcLoginSettings, SESSION_COOKIE};
+use crate::auth::provider::ProviderRegistry;
+use crate::models::session::session_utils;
+
+/// Ends the browser session and sends the browser to the identity provider to end its session too.
+/// The session's refresh token is revoked at the issuer's revocation endpoint, if it has one, so a
+/// copy of it stops working even if the browser never reaches the end-session endpoint.
+///
+/// # Arguments
+/// * req (HttpRequest): the request, carrying the session cookie
+/// * registry (web::Data<ProviderRegistry>): the trusted issuers
+/// * settings (web::Data<OidcLoginSettings>): the login settings
+///
+/// # Returns
+/// * (HttpResponse): a redirect to the end-session endpoint, or to the login page if there is none
+pub async fn logout(
+    req: HttpRequest,
+    registry: web::Data<ProviderRegistry>,
+    settings: web::Data<OidcLoginSettings>,
+) -> HttpResponse {
+    let mut location = settings.post_logout_redirect_uri.clone();
+
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        if let Ok(Some(session)) = session_utils::delete_session(&hash_secret(cookie.value())) {
+            info!("Ended the session of user {}.", session.user_id);
+            let provider = registry.find_by_name(&session.issuer_name);
+            let state = provider.and_then(|provider| provider.get());
+            if let (Some(provider), Some(state), Some(refresh_token)) = (provider, state, &session.refresh_token) {
+                if state.openid_config.revocation_endpoint.is_some() {
+                    match revoke_refresh_token(provider, state, refresh_token).await {
+                        Ok(()) => info!("Revoked the refresh token of the session of user {}.", session.user_id),
+                        Err(e) => warn!("Could not revoke the refresh token of user {}: {}", session.user_id, e),
+                    }
+                }
+            }
+            let end_session_endpoint = provider
+                .and_then(|provider| provider.get())
+                .and_then(|state| state.openid_config.end_session_endpoint.clone());
+            if let (Some(provider), Some(endpoint)) = (provider, end_session_endpoint) {
+                if let Ok(mut url) = reqwest::Url::parse(&endpoint) {
+                    {
+                        let mut params = url.query_pairs_mut();
+                        params
+                            .append_pair("client_id", &provider.issuer.client_id)
+                            .append_pair("post_logout_redirect_uri", &settings.post_logout_redirect_uri);
+                        if let Some(id_token) = &session.id_token {
+                            params.append_pair("id_token_hint", id_token);
+                        }
+                    }
+                    location = url.to_string();
+                }
+            }
+        }
+    }
+
+    let mut clear_session = session_cookie("", &settings);
+    clear_session.make_removal();
+    HttpResponse::Found()
+        .insert_header((LOCATION, location))
+        .cookie(clear_session)
+        .finish()
+}
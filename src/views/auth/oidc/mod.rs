@@ -0,0 +1,6 @@
+pub mod backchannel_logout;
+pub mod callback;
+pub mod frontchannel_logout;
+pub mod login;
+pub mod logout;
+pub mod session;
@@ -0,0 +1,29 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+use crate::auth;
+use crate::auth::processes::Principal;
+use crate::auth::provider::ProviderRegistry;
+use crate::auth::validation_policy::TokenRejection;
+
+/// Tells the front end whether the browser has a session, and whose it is.
+///
+/// # Arguments
+/// * req (HttpRequest): the request, carrying the session cookie
+/// * registry (web::Data<ProviderRegistry>): the trusted issuers
+///
+/// # Returns
+/// * (HttpResponse): 200 with the username and email, 401 without a valid session
+pub async fn session(req: HttpRequest, registry: web::Data<ProviderRegistry>) -> HttpResponse {
+    match auth::process_token(&req, &registry).await {
+        Ok(claims) => {
+            let principal = Principal::from_claims(&claims);
+            HttpResponse::Ok().json(json!({
+                "username": principal.username(),
+                "email": principal.email(),
+            }))
+        },
+        Err(TokenRejection::IssuerUnavailable(_)) => HttpResponse::ServiceUnavailable().body("Authentication is not available yet"),
+        Err(rejection) => HttpResponse::Unauthorized().body(rejection.to_string()),
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::SystemTime;
+
+use actix_web::{web, HttpResponse};
+use diesel::prelude::*;
+use tracing::warn;
+
+use super::tokens::token_response;
+use crate::auth::local_issuer::LocalTokenIssuer;
+use crate::auth::oidc_login::{hash_secret, random_secret};
+use crate::database::establish_connection;
+use crate::json_serialization::login::Refresh;
+use crate::models::refresh_token::refresh_token_utils::{self, RotationOutcome};
+use crate::models::user::user::User;
+use crate::schema::users;
+
+/// This view exchanges a refresh token from local login for a new access token and a new refresh token.
+///
+/// Each refresh token can be used once. Presenting one that was already used revokes every token
+/// descended from the same login.
+///
+/// # Arguments
+/// * refresh (web::Json<Refresh>): the refresh token
+/// * issuer (web::Data<LocalTokenIssuer>): signs the access token
+///
+/// # Returns
+/// * (HttpResponse): 200 with the new tokens, 401 if the refresh token is not valid
+pub async fn refresh(refresh: web::Json<Refresh>, issuer: web::Data<LocalTokenIssuer>) -> HttpResponse {
+    if !issuer.is_enabled() {
+        return HttpResponse::ServiceUnavailable().body("Local login is not configured");
+    }
+    let new_refresh_token = random_secret();
+    let outcome = refresh_token_utils::rotate_refresh_token(
+        &hash_secret(&refresh.refresh_token),
+        &hash_secret(&new_refresh_token),
+        SystemTime::now() + issuer.refresh_token_ttl,
+    );
+    let used = match outcome {
+        Ok(RotationOutcome::Rotated(used)) => used,
+        Ok(RotationOutcome::Unknown) => return HttpResponse::Unauthorized().body("Unknown refresh token"),
+        Ok(RotationOutcome::Expired) => return HttpResponse::Unauthorized().body("Refresh token has expired"),
+        Ok(RotationOutcome::Reused) => return HttpResponse::Unauthorized().body("Refresh token was already used"),
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+
+    let mut connection = establish_connection();
+    match users::table.find(&used.user_id).first::<User>(&mut connection) {
+        Ok(user) if user.disabled_at.is_some() => HttpResponse::Unauthorized().body("Account is disabled"),
+        Ok(user) => token_response(&issuer, &user, new_refresh_token),
+        Err(e) => {
+            warn!("Refresh token of user {} could not be honoured: {}", used.user_id, e);
+            HttpResponse::Unauthorized().body("Unknown refresh token")
+        }
+    }
+}
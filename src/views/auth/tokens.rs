@@ -0,0 +1,54 @@
+use std::time::SystemTime;
+
+use actix_web::HttpResponse;
+use uuid::Uuid;
+
+use crate::auth::local_issuer::LocalTokenIssuer;
+use crate::auth::oidc_login::{hash_secret, random_secret};
+use crate::json_serialization::login::TokenResponse;
+use crate::models::refresh_token::new_refresh_token::NewRefreshToken;
+use crate::models::refresh_token::refresh_token_utils;
+use crate::models::user::user::User;
+
+/// Issues an access token and the first refresh token of a new family after a successful login.
+///
+/// # Arguments
+/// * issuer (&LocalTokenIssuer): the local token issuer
+/// * user (&User): the user who logged in
+///
+/// # Returns
+/// * (HttpResponse): 200 with the tokens, or 500 if they could not be issued
+pub fn login_response(issuer: &LocalTokenIssuer, user: &User) -> HttpResponse {
+    let refresh_token = random_secret();
+    let new_token = NewRefreshToken {
+        token_hash: hash_secret(&refresh_token),
+        family_id: Uuid::new_v4().to_string(),
+        user_id: user.id.clone(),
+        expires_at: SystemTime::now() + issuer.refresh_token_ttl,
+    };
+    if let Err(e) = refresh_token_utils::create_refresh_token(&new_token) {
+        return HttpResponse::InternalServerError().body(e);
+    }
+    token_response(issuer, user, refresh_token)
+}
+
+/// Answers with a new access token and the given refresh token.
+///
+/// # Arguments
+/// * issuer (&LocalTokenIssuer): the local token issuer
+/// * user (&User): the user the tokens are for
+/// * refresh_token (String): the refresh token, already stored
+///
+/// # Returns
+/// * (HttpResponse): 200 with the tokens, or 500 if the access token could not be signed
+pub fn token_response(issuer: &LocalTokenIssuer, user: &User, refresh_token: String) -> HttpResponse {
+    match issuer.issue_access_token(user) {
+        Ok(access_token) => HttpResponse::Ok().json(TokenResponse {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: issuer.access_token_ttl.as_secs(),
+            refresh_token,
+        }),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
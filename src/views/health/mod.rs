@@ -0,0 +1,18 @@
+use actix_web::web;
+mod ready;
+use super::path::Path;
+
+
+/// This function adds the health check views to the web server.
+///
+/// # Arguments
+/// * (&mut web::ServiceConfig): reference to the app for configuration
+///
+/// # Returns
+/// None
+pub fn health_factory(app: &mut web::ServiceConfig) {
+    let base_path: Path = Path{prefix: String::from(""), backend: true};
+
+    app.route(&base_path.define(String::from("/ready")),
+              web::get().to(ready::ready));
+}
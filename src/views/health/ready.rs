@@ -0,0 +1,43 @@
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+
+use crate::auth::provider::ProviderRegistry;
+
+/// Reports whether the service can authenticate requests, issuer by issuer.
+///
+/// # Arguments
+/// * registry (web::Data<ProviderRegistry>): the trusted issuers
+///
+/// # Returns
+/// * (HttpResponse): 200 once every issuer is discovered, 200 `partial` while only some are, and
+///   503 with the last errors while none is
+pub async fn ready(registry: web::Data<ProviderRegistry>) -> HttpResponse {
+    // the local issuer, when local login is enabled, is always ready
+    let providers: Vec<_> = registry.providers().iter().chain(registry.local()).collect();
+    let issuers: Vec<serde_json::Value> = providers
+        .iter()
+        .map(|provider| match provider.get() {
+            Some(state) => json!({
+                "name": provider.issuer.name,
+                "status": "ready",
+                "issuer": state.openid_config.issuer,
+            }),
+            None => json!({
+                "name": provider.issuer.name,
+                "status": "degraded",
+                "reason": provider
+                    .last_error()
+                    .unwrap_or_else(|| "OIDC discovery has not completed yet".to_string()),
+            }),
+        })
+        .collect();
+
+    let ready_count = providers.iter().filter(|provider| provider.get().is_some()).count();
+    if ready_count == 0 {
+        HttpResponse::ServiceUnavailable().json(json!({ "status": "degraded", "issuers": issuers }))
+    } else if ready_count < providers.len() {
+        HttpResponse::Ok().json(json!({ "status": "partial", "issuers": issuers }))
+    } else {
+        HttpResponse::Ok().json(json!({ "status": "ready", "issuers": issuers }))
+    }
+}
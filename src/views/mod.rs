@@ -1,13 +1,173 @@
+use actix_files as fs;
 use actix_web::web;
+use tracing::info;
+mod account;
+mod admin;
 mod app;
 mod auth;
+mod health;
 mod path;
 mod to_do;
+mod tokens;
 pub mod users;
+use crate::middleware::authentication::Authenticate;
+use crate::middleware::body_limit::BodyLimit;
+
+/// Largest body accepted by the authenticated API, whose largest bodies are item edits.
+const API_BODY_LIMIT: usize = 64 * 1024;
+
+/// Largest body accepted by the login and account forms; a logout token from the identity provider
+/// is the largest of them.
+const FORM_BODY_LIMIT: usize = 16 * 1024;
+
+/// For routes that only answer `GET`: any body is refused.
+const NO_BODY: usize = 0;
+
+/// A group of routes that answers without authentication, mounted in a scope of its own.
+///
+/// # Attributes
+/// * prefix (&'static str): the scope the group's routes are mounted under
+/// * factory (fn(&mut web::ServiceConfig)): registers the group's routes
+/// * reason (&'static str): why the routes must be reachable without credentials
+/// * body_limit (usize): the largest request body, in bytes, the group's routes accept
+struct PublicRoutes {
+    prefix: &'static str,
+    factory: fn(&mut web::ServiceConfig),
+    reason: &'static str,
+    body_limit: usize,
+}
+
+/// Every route that can be reached without credentials. Routes outside these groups go into the
+/// authenticated `/api` scope, which refuses unauthenticated requests to any path, matched or not.
+const PUBLIC_ROUTES: &[PublicRoutes] = &[
+    PublicRoutes {
+        prefix: "/auth",
+        factory: auth::auth_factory,
+        reason: "local and OIDC login, token refresh, signing keys and logouts from the identity provider",
+        body_limit: FORM_BODY_LIMIT,
+    },
+    PublicRoutes {
+        prefix: "/user",
+        factory: users::user_factory,
+        reason: "registration, email verification and password reset, before the user can log in",
+        body_limit: FORM_BODY_LIMIT,
+    },
+    PublicRoutes {
+        prefix: "/javascript",
+        factory: |app| { app.service(fs::Files::new("", "./javascript").show_files_listing()); },
+        reason: "scripts of the HTML pages",
+        body_limit: NO_BODY,
+    },
+    PublicRoutes {
+        prefix: "/css",
+        factory: |app| { app.service(fs::Files::new("", "./css").show_files_listing()); },
+        reason: "stylesheets of the HTML pages",
+        body_limit: NO_BODY,
+    },
+    PublicRoutes {
+        prefix: "/templates",
+        factory: |app| { app.service(fs::Files::new("", "./templates").show_files_listing()); },
+        reason: "templates of the HTML pages, including the header",
+        body_limit: NO_BODY,
+    },
+    PublicRoutes {
+        prefix: "/health",
+        factory: health::health_factory,
+        reason: "readiness probes of the orchestrator",
+        body_limit: NO_BODY,
+    },
+    // last, as its empty prefix matches every path
+    PublicRoutes {
+        prefix: "",
+        factory: app::app_factory,
+        reason: "HTML pages; they call the API with the user's session or token",
+        body_limit: NO_BODY,
+    },
+];
 
 pub fn views_factory(app: &mut web::ServiceConfig) {
-    auth::auth_factory(app);
-    to_do::item_factory(app);
-    app::app_factory(app);
-    users::user_factory(app);
+    app.service(
+        web::scope("/api")
+            .wrap(Authenticate)
+            .wrap(BodyLimit::new(API_BODY_LIMIT))
+            .service(
+                web::scope("/v1")
+                    .configure(tokens::token_factory)
+                    .configure(admin::admin_factory)
+                    .configure(account::account_factory)
+                    .configure(to_do::item_factory),
+            ),
+    );
+    for routes in PUBLIC_ROUTES {
+        info!("Public routes under '{}/': {}", routes.prefix, routes.reason);
+        app.service(
+            web::scope(routes.prefix)
+                .wrap(BodyLimit::new(routes.body_limit))
+                .configure(routes.factory),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::App;
+    use crate::auth::issuers::IssuerConfig;
+    use crate::auth::provider::ProviderRegistry;
+
+    // One issuer whose discovery never ran, so no request needs the network
+    fn registry() -> web::Data<ProviderRegistry> {
+        let issuer = IssuerConfig {
+            name: "myrealm".to_string(),
+            issuer_url: "http://keycloak.test/realms/myrealm".to_string(),
+            client_id: "myclient".to_string(),
+            client_secret: None,
+        };
+        web::Data::new(ProviderRegistry::new(vec![issuer], None))
+    }
+
+    #[actix_web::test]
+    async fn unknown_api_paths_need_credentials() {
+        let app = init_service(App::new().app_data(registry()).configure(views_factory)).await;
+
+        let res = call_service(&app, TestRequest::get().uri("/api/v1/nope").to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = call_service(&app, TestRequest::post().uri("/api/nope").to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn known_api_paths_need_credentials() {
+        let app = init_service(App::new().app_data(registry()).configure(views_factory)).await;
+
+        let res = call_service(&app, TestRequest::get().uri("/api/v1/item/get").to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = read_body_json(res).await;
+        assert_eq!(body["error"], "unauthorized");
+    }
+
+    #[actix_web::test]
+    async fn public_routes_answer_without_credentials() {
+        let app = init_service(App::new().app_data(registry()).configure(views_factory)).await;
+
+        // Not ready, as discovery has not run, but answered by the route rather than refused with 401
+        let res = call_service(&app, TestRequest::get().uri("/health/ready").to_request()).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = read_body_json(res).await;
+        assert_eq!(body["status"], "degraded");
+
+        let res = call_service(&app, TestRequest::get().uri("/css/main.css").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn public_route_prefixes_stay_outside_the_api() {
+        for routes in PUBLIC_ROUTES {
+            assert!(!routes.prefix.starts_with("/api"), "'{}' would bypass authentication", routes.prefix);
+            assert!(!routes.reason.is_empty());
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use actix_web::{web, HttpResponse};
-use log::{info, error};
+use tracing::{info, error};
 
 use diesel::prelude::*;
 use diesel::{RunQueryDsl, Insertable};
@@ -10,29 +10,25 @@ use crate::schema::to_do;
 use crate::models::user::user_utils; // Import user_utils
 
 use super::utils::return_state;
-use crate::auth::processes::Claims;
+use crate::auth::processes::Principal;
 use crate::models::item::item::Item; // Import Item to use in filter
 
 /// This view creates a new to do item in the database.
 ///
 /// # Arguments
-/// * claims (Claims): Authenticated user claims extracted from the request.
+/// * principal (Principal): The authenticated user or service client making the request.
 /// * path_title (web::Path<String>): The title of the to-do item from the path.
 ///
 /// # Returns
 /// * (HttpResponse): A JSON response containing all of the stored to do items for the authenticated user, or an error.
-pub async fn create(claims: Claims, path_title: web::Path<String>) -> HttpResponse {
-    info!("Attempting to create a new to-do item for authenticated user: {}", claims.sub);
-
-    // Ensure the user exists in our local database
-    let user = match user_utils::find_or_create_user(
-        &claims.sub,
-        &claims.email,
-        &claims.preferred_username,
-    ) {
+pub async fn create(principal: Principal, path_title: web::Path<String>) -> HttpResponse {
+    info!("Attempting to create a new to-do item for authenticated principal: {}", principal.subject());
+
+    // Ensure the user exists in our local database; items belong to it, not to the token subject
+    let user = match user_utils::find_or_create_user(&principal) {
         Ok(u) => u,
         Err(e) => {
-            error!("Failed to find or create user for claims.sub {}: {}", claims.sub, e);
+            error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
             return HttpResponse::InternalServerError().body(format!("Failed to prepare user: {}", e));
         }
     };
@@ -42,18 +38,18 @@ pub async fn create(claims: Claims, path_title: web::Path<String>) -> HttpRespon
 
     let items = to_do::table
         .filter(to_do::columns::title.eq(&title))
-        .filter(to_do::columns::user_id.eq(&claims.sub))
+        .filter(to_do::columns::user_id.eq(&user.id))
         .order(to_do::columns::id.asc())
         .load::<Item>(&mut connection)
         .unwrap();
 
     if items.is_empty() {
-        let new_post = NewItem::new(title, claims.sub.clone());
+        let new_post = NewItem::new(title, user.id.clone());
         diesel::insert_into(to_do::table)
             .values(&new_post)
             .execute(&mut connection)
             .expect("Error saving new post");
     }
 
-    HttpResponse::Ok().json(return_state(&claims.sub))
+    HttpResponse::Ok().json(return_state(&user.id))
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use actix_web::{web, HttpResponse};
-use log::{warn, info, error};
+use tracing::{warn, info, error};
 
 use diesel::prelude::*;
 use diesel::RunQueryDsl;
@@ -8,43 +8,52 @@ use super::utils::return_state;
 use crate::database::establish_connection;
 use crate::models::item::item::Item;
 use crate::schema::to_do;
-use crate::auth::processes::Claims;
+use crate::auth::processes::Principal;
+use crate::models::user::user_utils;
 use crate::models::item::delete_item::DeleteItem; // Import DeleteItem
 
 /// This function deletes a to-do item for the authenticated user.
 ///
 /// # Arguments
-/// * claims (Claims): Authenticated user claims extracted from the request.
+/// * principal (Principal): The authenticated user or service client making the request.
 /// * delete_data (web::Json<DeleteItem>): The title of the to-do item to be deleted from the request body.
 ///
 /// # Returns
 /// * (HttpResponse): Response body to be passed to the viewer.
-pub async fn delete(claims: Claims, delete_data: web::Json<DeleteItem>) -> HttpResponse {
-    info!("Attempting to delete to-do item '{}' for authenticated user: {}", delete_data.title, claims.sub);
+pub async fn delete(principal: Principal, delete_data: web::Json<DeleteItem>) -> HttpResponse {
+    info!("Attempting to delete to-do item '{}' for authenticated user: {}", delete_data.title, principal.subject());
 
     let title: String = delete_data.title.clone(); // Clone the title for use in filter and logging
+    // Items belong to the local user, which is scoped by issuer and subject
+    let user = match user_utils::find_or_create_user(&principal) {
+        Ok(u) => u,
+        Err(e) => {
+            error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
+            return HttpResponse::InternalServerError().body(format!("Failed to prepare user: {}", e));
+        }
+    };
     let mut connection = establish_connection();
 
     let items = to_do::table
         .filter(to_do::columns::title.eq(&title))
-        .filter(to_do::columns::user_id.eq(&claims.sub))
+        .filter(to_do::columns::user_id.eq(&user.id))
         .order(to_do::columns::id.asc())
         .load::<Item>(&mut connection)
         .unwrap_or_else(|e| {
-            error!("Error loading items during delete for user {}: {}", claims.sub, e);
+            error!("Error loading items during delete for user {}: {}", principal.subject(), e);
             vec![]
         });
 
     if !items.is_empty() {
-        diesel::delete(to_do::table.filter(to_do::columns::title.eq(&title).and(to_do::columns::user_id.eq(&claims.sub))))
+        diesel::delete(to_do::table.filter(to_do::columns::title.eq(&title).and(to_do::columns::user_id.eq(&user.id))))
             .execute(&mut connection)
             .unwrap_or_else(|e| {
-                error!("Error deleting to-do item '{}' for user {}: {}", title, claims.sub, e);
+                error!("Error deleting to-do item '{}' for user {}: {}", title, principal.subject(), e);
                 0
             });
     } else {
-        warn!("Attempted to delete non-existent item or item not owned by user '{}' for user {}", title, claims.sub);
+        warn!("Attempted to delete non-existent item or item not owned by user '{}' for user {}", title, principal.subject());
     }
 
-    HttpResponse::Ok().json(return_state(&claims.sub))
+    HttpResponse::Ok().json(return_state(&user.id))
 }
\ No newline at end of file
@@ -1,9 +1,10 @@
 use actix_web::{web, Responder, HttpRequest, HttpResponse};
 use actix_web::HttpMessage; // Import HttpMessage for extensions()
-use log::{warn, info};
+use tracing::{warn, info, error};
 
 use super::utils::return_state;
-use crate::auth::processes::Claims;
+use crate::auth::processes::Principal;
+use crate::models::user::user_utils;
 
 /// This view gets all of the saved to do items for the authenticated user.
 ///
@@ -13,7 +14,15 @@ use crate::auth::processes::Claims;
 /// # Returns
 /// * (web::Json): all of the stored to do items for the authenticated user
 /// * (HttpResponse::Unauthorized): if the user is not authenticated
-pub async fn get(claims: Claims) -> HttpResponse {
-    info!("Attempting to retrieve to-do items for authenticated user: {}", claims.sub);
-    HttpResponse::Ok().json(return_state(&claims.sub))
+pub async fn get(principal: Principal) -> HttpResponse {
+    info!("Attempting to retrieve to-do items for authenticated principal: {}", principal.subject());
+    // Items belong to the local user, which is scoped by issuer and subject
+    let user = match user_utils::find_or_create_user(&principal) {
+        Ok(u) => u,
+        Err(e) => {
+            error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
+            return HttpResponse::InternalServerError().body(format!("Failed to prepare user: {}", e));
+        }
+    };
+    HttpResponse::Ok().json(return_state(&user.id))
 }
\ No newline at end of file
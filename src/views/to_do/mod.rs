@@ -1,5 +1,5 @@
 use actix_web::{web};
-use log::info; // Add this import
+use tracing::info; // Add this import
 
 mod utils;
 mod create;
@@ -8,25 +8,26 @@ mod edit;
 mod delete;
 mod test_edit; // New module
 use super::path::Path;
+use crate::auth::guards::RequireScope;
+use crate::auth::permissions;
 
 /// This function adds the to-do item views to the web server.
 ///
 /// # Arguments
-/// * app: &mut web::ServiceConfig - the app service config
-/// * keycloak_auth: KeycloakAuth - middleware for protecting these routes
+/// * app: &mut web::ServiceConfig - the authenticated API scope
 pub fn item_factory(app: &mut web::ServiceConfig) {
     info!("Setting up to-do item routes."); // Add this log
     // define the path struct
     let base_path: Path = Path { prefix: String::from("/item"), backend: true };
 
-    // apply auth middleware only to this scoped group
-    app.service(
-        web::scope("/api/v1")
-            // Removed .wrap(keycloak_auth) as we are using a custom middleware for authentication
-            .route(&base_path.define(String::from("/create/{title}")), web::post().to(create::create))
-            .route(&base_path.define(String::from("/get")), web::get().to(get::get))
-            .route(&base_path.define(String::from("/edit")), web::put().to(edit::edit))
-            .route(&base_path.define(String::from("/delete")), web::post().to(delete::delete))
-            .route(&base_path.define(String::from("/test_edit")), web::put().to(test_edit::test_edit_json)) // New test route
-    );
+    app.route(&base_path.define(String::from("/create/{title}")),
+              web::post().to(create::create).wrap(RequireScope(permissions::ITEMS_WRITE)));
+    app.route(&base_path.define(String::from("/get")),
+              web::get().to(get::get).wrap(RequireScope(permissions::ITEMS_READ)));
+    app.route(&base_path.define(String::from("/edit")),
+              web::put().to(edit::edit).wrap(RequireScope(permissions::ITEMS_WRITE)));
+    app.route(&base_path.define(String::from("/delete")),
+              web::post().to(delete::delete).wrap(RequireScope(permissions::ITEMS_WRITE)));
+    app.route(&base_path.define(String::from("/test_edit")),
+              web::put().to(test_edit::test_edit_json).wrap(RequireScope(permissions::ITEMS_WRITE))); // New test route
 }
@@ -0,0 +1,85 @@
+use std::time::{Duration, SystemTime};
+
+use actix_web::{web, HttpResponse};
+use tracing::{info, error};
+
+use crate::auth::oidc_login::hash_secret;
+use crate::auth::personal_access_token::{generate_token, grantable_scopes, ScopeRefusal};
+use crate::auth::processes::{Claims, Principal};
+use crate::json_serialization::personal_access_token::{NewPersonalAccessTokenSchema, PersonalAccessTokenView};
+use crate::models::personal_access_token::new_personal_access_token::NewPersonalAccessToken;
+use crate::models::personal_access_token::personal_access_token_utils;
+use crate::models::user::user_utils;
+
+/// Lifetime of a token created without `expires_in_days`.
+const DEFAULT_LIFETIME_DAYS: u64 = 30;
+
+/// Longest lifetime a token can be created with.
+const MAX_LIFETIME_DAYS: u64 = 365;
+
+/// This view creates a personal access token for the authenticated user.
+///
+/// The token can only carry scopes the caller holds itself, and it is returned once; afterwards only
+/// its hash is stored.
+///
+/// # Arguments
+/// * claims (Claims): the authenticated caller's claims
+/// * new_token (web::Json<NewPersonalAccessTokenSchema>): the name, scopes and lifetime of the token
+///
+/// # Returns
+/// * (HttpResponse): 201 with the token, 400 for invalid input, 403 if a scope cannot be granted, 409 for a duplicate name
+pub async fn create(claims: Claims, new_token: web::Json<NewPersonalAccessTokenSchema>) -> HttpResponse {
+    // A personal access token must not be able to mint longer-lived copies of itself
+    if claims.personal_access_token.is_some() {
+        return HttpResponse::Forbidden().body("Forbidden: personal access tokens cannot create personal access tokens");
+    }
+    let principal = Principal::from_claims(&claims);
+    if let Principal::ServiceClient { .. } = principal {
+        return HttpResponse::Forbidden().body("Forbidden: service accounts cannot create personal access tokens");
+    }
+
+    let name = new_token.name.trim().to_string();
+    if name.is_empty() || name.len() > 100 {
+        return HttpResponse::BadRequest().body("Token name must be between 1 and 100 characters");
+    }
+    if new_token.scopes.is_empty() {
+        return HttpResponse::BadRequest().body("At least one scope is required");
+    }
+    let scopes = match grantable_scopes(&claims, &new_token.scopes) {
+        Ok(scopes) => scopes,
+        Err(ScopeRefusal::Unknown(scope)) => return HttpResponse::BadRequest().body(format!("Unknown scope '{}'", scope)),
+        Err(ScopeRefusal::NotHeld(scope)) => {
+            return HttpResponse::Forbidden().body(format!("Forbidden: missing scope '{}'", scope))
+        },
+    };
+    let lifetime_days = new_token.expires_in_days.unwrap_or(DEFAULT_LIFETIME_DAYS);
+    if lifetime_days == 0 || lifetime_days > MAX_LIFETIME_DAYS {
+        return HttpResponse::BadRequest().body(format!("expires_in_days must be between 1 and {}", MAX_LIFETIME_DAYS));
+    }
+
+    let user = match user_utils::find_or_create_user(&principal) {
+        Ok(u) => u,
+        Err(e) => {
+            error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
+            return HttpResponse::InternalServerError().body(format!("Failed to prepare user: {}", e));
+        }
+    };
+    let token = generate_token();
+    let record = NewPersonalAccessToken::new(
+        user.id.clone(),
+        name,
+        hash_secret(&token),
+        scopes,
+        SystemTime::now() + Duration::from_secs(lifetime_days * 24 * 60 * 60),
+    );
+    match personal_access_token_utils::create_token(&record) {
+        Ok(Some(created)) => {
+            info!("Created personal access token {} for user {}", created.id, user.id);
+            let mut view = PersonalAccessTokenView::new(&created);
+            view.token = Some(token);
+            HttpResponse::Created().json(view)
+        },
+        Ok(None) => HttpResponse::Conflict().body(format!("A token named '{}' already exists", record.name)),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
@@ -0,0 +1,28 @@
+use actix_web::HttpResponse;
+use tracing::error;
+
+use crate::auth::processes::Principal;
+use crate::json_serialization::personal_access_token::PersonalAccessTokenView;
+use crate::models::personal_access_token::personal_access_token_utils;
+use crate::models::user::user_utils;
+
+/// This view lists the authenticated user's personal access tokens, without the tokens themselves.
+///
+/// # Arguments
+/// * principal (Principal): the authenticated caller
+///
+/// # Returns
+/// * (HttpResponse): the user's tokens
+pub async fn list(principal: Principal) -> HttpResponse {
+    let user = match user_utils::find_or_create_user(&principal) {
+        Ok(u) => u,
+        Err(e) => {
+            error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
+            return HttpResponse::InternalServerError().body(format!("Failed to prepare user: {}", e));
+        }
+    };
+    match personal_access_token_utils::list_tokens(&user.id) {
+        Ok(tokens) => HttpResponse::Ok().json(tokens.iter().map(PersonalAccessTokenView::new).collect::<Vec<_>>()),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
@@ -0,0 +1,26 @@
+use actix_web::web;
+mod create;
+mod list;
+mod revoke;
+use super::path::Path;
+use crate::auth::guards::RequireScope;
+use crate::auth::permissions;
+
+
+/// This function adds the personal access token views to the web server. Every route needs the `profile` scope.
+///
+/// # Arguments
+/// * (&mut web::ServiceConfig): reference to the app for configuration
+///
+/// # Returns
+/// None
+pub fn token_factory(app: &mut web::ServiceConfig) {
+    let base_path: Path = Path{prefix: String::from("/tokens"), backend: true};
+
+    app.route(&base_path.define(String::from("")),
+              web::post().to(create::create).wrap(RequireScope(permissions::PROFILE)));
+    app.route(&base_path.define(String::from("")),
+              web::get().to(list::list).wrap(RequireScope(permissions::PROFILE)));
+    app.route(&base_path.define(String::from("/{id}")),
+              web::delete().to(revoke::revoke).wrap(RequireScope(permissions::PROFILE)));
+}
@@ -0,0 +1,32 @@
+use actix_web::{web, HttpResponse};
+use tracing::{info, error};
+
+use crate::auth::processes::Principal;
+use crate::models::personal_access_token::personal_access_token_utils;
+use crate::models::user::user_utils;
+
+/// This view revokes one of the authenticated user's personal access tokens.
+///
+/// # Arguments
+/// * principal (Principal): the authenticated caller
+/// * id (web::Path<String>): the ID of the token
+///
+/// # Returns
+/// * (HttpResponse): 204 once revoked, 404 if the user has no such token
+pub async fn revoke(principal: Principal, id: web::Path<String>) -> HttpResponse {
+    let user = match user_utils::find_or_create_user(&principal) {
+        Ok(u) => u,
+        Err(e) => {
+            error!("Failed to find or create user for principal {}: {}", principal.subject(), e);
+            return HttpResponse::InternalServerError().body(format!("Failed to prepare user: {}", e));
+        }
+    };
+    match personal_access_token_utils::delete_token(&user.id, &id) {
+        Ok(true) => {
+            info!("Revoked personal access token {} of user {}", id, user.id);
+            HttpResponse::NoContent().finish()
+        },
+        Ok(false) => HttpResponse::NotFound().body("No such personal access token"),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
@@ -0,0 +1,52 @@
+use crate::auth::account_token::{AccountTokenPurpose, AccountTokenSigner};
+use crate::mailer::Email;
+use crate::models::user::user::User;
+
+/// Writes the email that asks a new user to confirm their address.
+///
+/// # Arguments
+/// * signer (&AccountTokenSigner): signs the verification token
+/// * user (&User): the user, who must have an email address
+///
+/// # Returns
+/// (Option<Email>): the email, or `None` if the user has no email address
+pub fn verification_email(signer: &AccountTokenSigner, user: &User) -> Option<Email> {
+    let to = user.email.clone()?;
+    let token = signer.issue(AccountTokenPurpose::VerifyEmail, user, None);
+    Some(Email {
+        to,
+        subject: "Confirm your email address".to_string(),
+        body: format!(
+            "Hello {},\n\nPlease confirm your email address by opening this link within {} hours:\n\n{}/user/verify-email?token={}\n\nIf you did not create an account, you can ignore this email.\n",
+            user.username,
+            signer.verification_ttl.as_secs() / 3600,
+            signer.public_url,
+            token,
+        ),
+    })
+}
+
+/// Writes the email that lets a user choose a new password.
+///
+/// # Arguments
+/// * signer (&AccountTokenSigner): signs the reset token
+/// * user (&User): the user, who must have an email address
+/// * password_hash (&str): the hash of the user's password credential
+///
+/// # Returns
+/// (Option<Email>): the email, or `None` if the user has no email address
+pub fn password_reset_email(signer: &AccountTokenSigner, user: &User, password_hash: &str) -> Option<Email> {
+    let to = user.email.clone()?;
+    let token = signer.issue(AccountTokenPurpose::ResetPassword, user, Some(password_hash));
+    Some(Email {
+        to,
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Hello {},\n\nSomeone asked to reset the password of your account. To choose a new one within {} minutes, send\n\n  POST {}/user/password-reset/confirm\n  {{\"token\": \"{}\", \"password\": \"<new password>\"}}\n\nThe token works once. If you did not ask for this, you can ignore this email.\n",
+            user.username,
+            signer.reset_ttl.as_secs() / 60,
+            signer.public_url,
+            token,
+        ),
+    })
+}
@@ -1,22 +1,98 @@
-use crate::database::establish_connection;
+use crate::auth::account_token::AccountTokenSigner;
+use crate::auth::password_policy::PasswordPolicy;
+use crate::database::try_establish_connection;
 use crate::diesel;
+use crate::json_serialization::error_response::{ErrorResponse, FieldError};
 use crate::json_serialization::new_user::NewUserSchema;
+use crate::mailer::{self, Mailer};
+use crate::models::credential::credential_utils;
+use crate::models::credential::new_credential::NewCredential;
 use crate::models::user::new_user::NewUser;
-use crate::schema::users;
+use crate::models::user::user::User;
+use crate::schema::{credentials, users};
 use actix_web::{web, HttpResponse};
 use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error};
+use tracing::{info, error};
 
-pub async fn create(new_user: web::Json<NewUserSchema>) -> HttpResponse {
-    let mut connection = establish_connection();
-    let name: String = new_user.name.clone();
-    let email: String = new_user.email.clone();
-    let password: String = new_user.password.clone();
-    let new_user = NewUser::new(name, email, password);
-    let insert_result = diesel::insert_into(users::table)
-        .values(&new_user)
-        .execute(&mut connection);
+use super::account_email::verification_email;
+
+/// This view registers a local user and emails them a link to verify their address.
+///
+/// # Arguments
+/// * new_user (web::Json<NewUserSchema>): the username, email address and password
+/// * policy (web::Data<PasswordPolicy>): the password rules
+/// * signer (web::Data<AccountTokenSigner>): signs the verification token
+/// * mailer (web::Data<dyn Mailer>): sends the verification email
+///
+/// # Returns
+/// * (HttpResponse): 201 when created; otherwise a JSON error: 400 `validation_failed` listing the
+///   invalid fields, 409 `username_taken` or `email_taken`, 503 `database_unavailable`
+pub async fn create(
+    new_user: web::Json<NewUserSchema>,
+    policy: web::Data<PasswordPolicy>,
+    signer: web::Data<AccountTokenSigner>,
+    mailer: web::Data<dyn Mailer>,
+) -> HttpResponse {
+    let mut new_user = new_user.into_inner();
+    new_user.name = new_user.name.trim().to_string();
+    new_user.email = new_user.email.trim().to_string();
+
+    let field_errors = new_user.validate(&policy);
+    if !field_errors.is_empty() {
+        return HttpResponse::BadRequest().json(ErrorResponse::with_fields(
+            "validation_failed", "The registration is not valid", field_errors,
+        ));
+    }
+
+    let mut connection = match try_establish_connection() {
+        Ok(connection) => connection,
+        Err(e) => {
+            error!("Cannot register user: {}", e);
+            return HttpResponse::ServiceUnavailable()
+                .json(ErrorResponse::new("database_unavailable", "Registration is temporarily unavailable"));
+        }
+    };
+    let password_hash = match credential_utils::hash_password(&new_user.password) {
+        Ok(password_hash) => password_hash,
+        Err(e) => {
+            error!("Cannot register user: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(ErrorResponse::new("internal_error", "The user could not be registered"));
+        }
+    };
+    let new_user = NewUser::new(new_user.name, new_user.email);
+    let insert_result = connection.transaction::<User, Error, _>(|connection| {
+        let user = diesel::insert_into(users::table)
+            .values(&new_user)
+            .get_result::<User>(connection)?;
+        diesel::insert_into(credentials::table)
+            .values(&NewCredential::password(&user.id, password_hash))
+            .execute(connection)?;
+        Ok(user)
+    });
     match insert_result {
-        Ok(_) => HttpResponse::Created().await.unwrap(),
-        Err(_) => HttpResponse::Conflict().await.unwrap(),
+        Ok(user) => {
+            info!("Registered local user {}", user.id);
+            if let Some(email) = verification_email(&signer, &user) {
+                mailer::send_in_background(mailer, email);
+            }
+            HttpResponse::Created().await.unwrap()
+        },
+        Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info)) => match info.constraint_name() {
+            Some("uc_user_email") => HttpResponse::Conflict().json(ErrorResponse::with_fields(
+                "email_taken", "The email address is already registered",
+                vec![FieldError::new("email", "email_taken", "is already registered")],
+            )),
+            _ => HttpResponse::Conflict().json(ErrorResponse::with_fields(
+                "username_taken", "The username is already taken",
+                vec![FieldError::new("name", "username_taken", "is already taken")],
+            )),
+        },
+        Err(e) => {
+            error!("Error registering user: {}", e);
+            HttpResponse::InternalServerError()
+                .json(ErrorResponse::new("internal_error", "The user could not be registered"))
+        },
     }
 }
@@ -1,5 +1,8 @@
 use actix_web::web;
+mod account_email;
 mod create;
+mod password_reset;
+mod verify_email;
 use super::path::Path;
 
 
@@ -11,8 +14,20 @@ use super::path::Path;
 /// # Returns
 /// None
 pub fn user_factory(app:  &mut web::ServiceConfig) {
-    let base_path: Path = Path{prefix: String::from("/user"), backend: true};
+    let base_path: Path = Path{prefix: String::from(""), backend: true};
 
     app.route(&base_path.define(String::from("/create")),
               web::post().to(create::create));
-}
\ No newline at end of file
+    // email verification; the GET route is the link sent by email
+    app.route(&base_path.define(String::from("/verify-email")),
+              web::post().to(verify_email::verify_email));
+    app.route(&base_path.define(String::from("/verify-email")),
+              web::get().to(verify_email::verify_email_link));
+    app.route(&base_path.define(String::from("/verify-email/resend")),
+              web::post().to(verify_email::resend_verification));
+    // password reset for local accounts
+    app.route(&base_path.define(String::from("/password-reset")),
+              web::post().to(password_reset::request_reset));
+    app.route(&base_path.define(String::from("/password-reset/confirm")),
+              web::post().to(password_reset::confirm_reset));
+}
@@ -0,0 +1,97 @@
+use actix_web::{web, HttpResponse};
+use tracing::{info, warn};
+
+use super::account_email::password_reset_email;
+use crate::auth::account_token::{AccountTokenPurpose, AccountTokenSigner};
+use crate::auth::password_policy::PasswordPolicy;
+use crate::json_serialization::account::{EmailSchema, PasswordResetSchema};
+use crate::json_serialization::error_response::{ErrorResponse, FieldError};
+use crate::mailer::{self, Mailer};
+use crate::models::credential::credential_utils;
+use crate::models::user::user_utils;
+
+/// This view emails a password reset token to a local account. It answers the same whether or not
+/// the address is registered, so it cannot be used to find out who is.
+///
+/// # Arguments
+/// * body (web::Json<EmailSchema>): the email address of the account
+/// * signer (web::Data<AccountTokenSigner>): signs the reset token
+/// * mailer (web::Data<dyn Mailer>): sends the email
+///
+/// # Returns
+/// * (HttpResponse): 202, or 500 if the database call failed
+pub async fn request_reset(
+    body: web::Json<EmailSchema>,
+    signer: web::Data<AccountTokenSigner>,
+    mailer: web::Data<dyn Mailer>,
+) -> HttpResponse {
+    let user = match user_utils::find_local_user_by_email(body.email.trim()) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    };
+    if let Some(user) = user {
+        let credential = match credential_utils::find_password_credential(&user.id) {
+            Ok(credential) => credential,
+            Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+        };
+        let email = credential
+            .and_then(|credential| credential.password_hash)
+            .and_then(|password_hash| password_reset_email(&signer, &user, &password_hash));
+        if let Some(email) = email {
+            info!("Password reset requested for user {}", user.id);
+            mailer::send_in_background(mailer, email);
+        }
+    }
+    HttpResponse::Accepted().finish()
+}
+
+/// This view sets a new password with the token from the reset email. The token stops working once
+/// the password has changed, and the user's refresh tokens are revoked.
+///
+/// # Arguments
+/// * body (web::Json<PasswordResetSchema>): the reset token and the new password
+/// * signer (web::Data<AccountTokenSigner>): checks the token
+/// * policy (web::Data<PasswordPolicy>): the password rules
+///
+/// # Returns
+/// * (HttpResponse): 204 when the password is changed, 400 `invalid_token` or `validation_failed` otherwise
+pub async fn confirm_reset(
+    body: web::Json<PasswordResetSchema>,
+    signer: web::Data<AccountTokenSigner>,
+    policy: web::Data<PasswordPolicy>,
+) -> HttpResponse {
+    let invalid = |reason: &str| {
+        HttpResponse::BadRequest().json(ErrorResponse::new("invalid_token", &format!("The reset {}", reason)))
+    };
+    let user_id = match AccountTokenSigner::user_id(&body.token) {
+        Some(user_id) => user_id,
+        None => return invalid("token is malformed"),
+    };
+    let user = match user_utils::find_user(&user_id) {
+        Ok(Some(user)) => user,
+        Ok(None) => return invalid("token is invalid or has already been used"),
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    };
+    let password_hash = match credential_utils::find_password_credential(&user.id) {
+        Ok(credential) => credential.and_then(|credential| credential.password_hash),
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    };
+    let password_hash = match password_hash {
+        Some(password_hash) => password_hash,
+        None => return invalid("token is invalid or has already been used"),
+    };
+    if let Err(reason) = signer.verify(AccountTokenPurpose::ResetPassword, &body.token, &user, Some(&password_hash)) {
+        warn!("Password reset for user {} refused: {}", user.id, reason);
+        return invalid(&reason);
+    }
+
+    let problems = policy.check(&body.password, &user.username);
+    if !problems.is_empty() {
+        let fields = problems.iter().map(|problem| FieldError::new("password", "weak_password", problem)).collect();
+        return HttpResponse::BadRequest().json(ErrorResponse::with_fields("validation_failed", "The new password is not valid", fields));
+    }
+    match credential_utils::reset_password(&user.id, &body.password) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    }
+}
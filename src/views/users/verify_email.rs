@@ -0,0 +1,86 @@
+use actix_web::{web, HttpResponse};
+use tracing::{info, warn};
+
+use super::account_email::verification_email;
+use crate::auth::account_token::{AccountTokenPurpose, AccountTokenSigner};
+use crate::json_serialization::account::{AccountTokenSchema, EmailSchema};
+use crate::json_serialization::error_response::ErrorResponse;
+use crate::mailer::{self, Mailer};
+use crate::models::user::user_utils;
+
+/// This view confirms an email address with the token from the verification email.
+///
+/// # Arguments
+/// * body (web::Json<AccountTokenSchema>): the verification token
+/// * signer (web::Data<AccountTokenSigner>): checks the token
+///
+/// # Returns
+/// * (HttpResponse): 204 when verified, 400 `invalid_token` if the token is invalid, expired or used
+pub async fn verify_email(body: web::Json<AccountTokenSchema>, signer: web::Data<AccountTokenSigner>) -> HttpResponse {
+    match verify(&body.token, &signer) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(response) => response,
+    }
+}
+
+/// This view confirms an email address when the link in the verification email is opened.
+///
+/// # Arguments
+/// * query (web::Query<AccountTokenSchema>): the verification token
+/// * signer (web::Data<AccountTokenSigner>): checks the token
+///
+/// # Returns
+/// * (HttpResponse): 200 with a confirmation, 400 `invalid_token` if the token is invalid, expired or used
+pub async fn verify_email_link(query: web::Query<AccountTokenSchema>, signer: web::Data<AccountTokenSigner>) -> HttpResponse {
+    match verify(&query.token, &signer) {
+        Ok(()) => HttpResponse::Ok().content_type("text/plain; charset=utf-8").body("Your email address is verified."),
+        Err(response) => response,
+    }
+}
+
+/// This view sends a new verification email. It answers the same whether or not the address
+/// belongs to an unverified account, so it cannot be used to find out who is registered.
+///
+/// # Arguments
+/// * body (web::Json<EmailSchema>): the email address
+/// * signer (web::Data<AccountTokenSigner>): signs the verification token
+/// * mailer (web::Data<dyn Mailer>): sends the email
+///
+/// # Returns
+/// * (HttpResponse): 202, or 500 if the database call failed
+pub async fn resend_verification(
+    body: web::Json<EmailSchema>,
+    signer: web::Data<AccountTokenSigner>,
+    mailer: web::Data<dyn Mailer>,
+) -> HttpResponse {
+    match user_utils::find_local_user_by_email(body.email.trim()) {
+        Ok(Some(user)) if user.email_verified_at.is_none() => {
+            if let Some(email) = verification_email(&signer, &user) {
+                mailer::send_in_background(mailer, email);
+            }
+        },
+        Ok(_) => {},
+        Err(e) => return HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)),
+    }
+    HttpResponse::Accepted().finish()
+}
+
+fn verify(token: &str, signer: &AccountTokenSigner) -> Result<(), HttpResponse> {
+    let invalid = |reason: &str| {
+        HttpResponse::BadRequest().json(ErrorResponse::new("invalid_token", &format!("The verification {}", reason)))
+    };
+    let user_id = AccountTokenSigner::user_id(token).ok_or_else(|| invalid("token is malformed"))?;
+    let user = match user_utils::find_user(&user_id) {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(invalid("token is invalid or has already been used")),
+        Err(e) => return Err(HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e))),
+    };
+    if let Err(reason) = signer.verify(AccountTokenPurpose::VerifyEmail, token, &user, None) {
+        warn!("Email verification for user {} refused: {}", user.id, reason);
+        return Err(invalid(&reason));
+    }
+    user_utils::mark_email_verified(&user.id)
+        .map_err(|e| HttpResponse::InternalServerError().json(ErrorResponse::new("internal_error", &e)))?;
+    info!("User {} verified their email address", user.id);
+    Ok(())
+}